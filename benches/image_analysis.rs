@@ -1,18 +1,25 @@
 //! Image analysis benchmarks.
 
-use std::{collections::VecDeque, time::Duration};
+use std::time::Duration;
 
-use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use image::{Pixel, RgbImage};
-use imageproc::{filter, point::Point};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use image::{GrayImage, Luma, Pixel, RgbImage};
+use imageproc::{distance_transform::Norm, filter, morphology, point::Point};
 use rayon::prelude::*;
-use webcam_mouse::analysis;
+use webcam_mouse::{
+	analysis::{self, ColorMode, Frame, Threshold},
+	morphology::{Element, Morphology, Operation},
+	testing::Lcg,
+	FramePool,
+};
 
 criterion_main!(benches);
 criterion_group! {
 	name = benches;
 	config = Criterion::default().measurement_time(Duration::from_secs(15));
-	targets = mirror_benches, image_processing_benches, image_diff_benches, find_rightmost_benches
+	targets = mirror_benches, image_processing_benches, median_benches, image_diff_benches,
+		morphology_benches, find_rightmost_benches, processing_resolution_benches,
+		color_mode_benches
 }
 
 /// Benchmark mirroring images
@@ -84,7 +91,21 @@ pub fn image_processing_benches(crit: &mut Criterion) {
 
 	group.bench_function("imageproc", |b| b.iter(|| process_frame_imageproc(black_box(&image))));
 
-	group.bench_function("try", |b| b.iter(|| process_frame_try(black_box(&image))));
+	group.finish();
+}
+
+/// Benchmark and compare median filters on a noisy frame.
+pub fn median_benches(crit: &mut Criterion) {
+	let mut group = crit.benchmark_group("Median filter");
+
+	let image = noisy_frame_with_square(960, 500);
+
+	group.bench_function("analysis::median_filter", |b| {
+		b.iter(|| analysis::median_filter(black_box(&image), 2))
+	});
+
+	group
+		.bench_function("imageproc", |b| b.iter(|| filter::median_filter(black_box(&image), 2, 2)));
 
 	group.finish();
 }
@@ -105,90 +126,6 @@ fn process_frame_imageproc(image: &RgbImage) -> RgbImage {
 	filter::filter3x3(&image, &kernel)
 }
 
-fn process_frame_try(image: &RgbImage) -> RgbImage {
-	let radius = 2;
-	let kernel_width = radius * 2 + 1;
-	let layout = image.as_flat_samples().layout;
-
-	let radius = radius as isize;
-	let mut out = vec![0; image.as_raw().len()];
-	out.par_chunks_exact_mut(layout.height_stride).zip(0..layout.height).for_each(|(line, y)| {
-		// Initialize histogram buffers
-		let mut hists =
-			vec![VecDeque::with_capacity(kernel_width * kernel_width); layout.channels.into()];
-		for filter_x in -radius..=radius {
-			for filter_y in -radius..=radius {
-				for (c_val, hist) in get_pixel_clamped(image, filter_x, y as isize + filter_y)
-					.channels()
-					.iter()
-					.zip(hists.iter_mut())
-				{
-					hist.push_back(*c_val);
-				}
-			}
-		}
-
-		let mut i = 0;
-		for val in median_pixel(&hists) {
-			line[i] = val;
-			i += 1;
-		}
-
-		for x in 1..layout.width {
-			for filter_y in -radius..=radius {
-				for (c_val, hist) in
-					get_pixel_clamped(image, x as isize + radius, y as isize + filter_y)
-						.channels()
-						.iter()
-						.zip(hists.iter_mut())
-				{
-					hist.pop_front();
-					hist.push_back(*c_val);
-				}
-			}
-
-			for val in median_pixel(&hists) {
-				line[i] = val;
-				i += 1;
-			}
-		}
-	});
-
-	RgbImage::from_raw(layout.width, layout.height, out).unwrap()
-}
-
-/// Compute median of each queue buffer and return raw pixel values of the
-/// combined results.
-#[inline]
-fn median_pixel(bufs: &[VecDeque<u8>]) -> impl Iterator<Item = u8> + '_ {
-	bufs.iter().map(|buf| {
-		let mut hist = [0_u8; 256];
-		for val in buf {
-			hist[*val as usize] += 1;
-		}
-
-		let mid = (buf.len() / 2) as u8;
-		let mut found = 0;
-		for i in 0..=0xFF {
-			found += hist[i as usize];
-			if found > mid {
-				return i;
-			}
-		}
-
-		panic!("Should've returned by now!");
-	})
-}
-
-/// Get pixel by clamped coordinates.
-#[inline]
-fn get_pixel_clamped(image: &RgbImage, x: isize, y: isize) -> &image::Rgb<u8> {
-	let (w, h) = image.dimensions();
-	let w = w as isize - 1;
-	let h = h as isize - 1;
-	image.get_pixel(x.clamp(0, w) as u32, y.clamp(0, h) as u32)
-}
-
 /// Benchmark computing difference images.
 pub fn image_diff_benches(crit: &mut Criterion) {
 	let mut group = crit.benchmark_group("Compute difference image");
@@ -205,7 +142,18 @@ pub fn image_diff_benches(crit: &mut Criterion) {
 	group.bench_function("analysis::original", |b| {
 		b.iter(|| {
 			let mut image = image1.clone();
-			analysis::frame_difference(black_box(&mut image), black_box(&image2))
+			analysis::frame_difference(
+				black_box(&mut image),
+				black_box(&image2),
+				Threshold::default(),
+			)
+		})
+	});
+
+	group.bench_function("scalar", |b| {
+		b.iter(|| {
+			let mut image = image1.clone();
+			frame_difference_scalar(black_box(&mut image), black_box(&image2), 32)
 		})
 	});
 
@@ -216,9 +164,60 @@ pub fn image_diff_benches(crit: &mut Criterion) {
 		})
 	});
 
+	let previous = noisy_frame_with_square(900, 500);
+	let current = noisy_frame_with_square(960, 500);
+	group.bench_function("analysis::original 1080p", |b| {
+		b.iter(|| {
+			let mut image = previous.clone();
+			analysis::frame_difference(
+				black_box(&mut image),
+				black_box(&current),
+				Threshold::default(),
+			)
+		})
+	});
+
+	group.bench_function("analysis::otsu 1080p", |b| {
+		b.iter(|| {
+			let mut image = previous.clone();
+			let threshold = Threshold::Otsu(Threshold::DEFAULT_OTSU_MINIMUM);
+			analysis::frame_difference(black_box(&mut image), black_box(&current), threshold)
+		})
+	});
+
+	group.bench_function("scalar 1080p", |b| {
+		b.iter(|| {
+			let mut image = previous.clone();
+			frame_difference_scalar(black_box(&mut image), black_box(&current), 32)
+		})
+	});
+
 	group.finish();
 }
 
+/// Parallel scalar byte loop with a global threshold, as used before
+/// vectorising.
+fn frame_difference_scalar(
+	previous: &mut RgbImage,
+	current: &RgbImage,
+	threshold: u8,
+) -> Vec<bool> {
+	let stride = previous.as_flat_samples().layout.height_stride;
+	previous
+		.par_chunks_mut(stride)
+		.zip(current.par_chunks(stride))
+		.map(|(prev, cur)| {
+			let mut motion = 0;
+			for (prev, cur) in prev.iter_mut().zip(cur) {
+				let diff = prev.abs_diff(*cur);
+				*prev = if diff >= threshold { diff } else { 0 };
+				motion |= *prev;
+			}
+			motion != 0
+		})
+		.collect()
+}
+
 fn frame_difference_try(previous: &mut RgbImage, current: &RgbImage) {
 	for (prev, cur) in previous.iter_mut().zip(current.as_raw()) {
 		*prev = match prev.abs_diff(*cur) {
@@ -228,6 +227,40 @@ fn frame_difference_try(previous: &mut RgbImage, current: &RgbImage) {
 	}
 }
 
+/// Benchmark and compare opening the 1080p mask of moving pixels.
+pub fn morphology_benches(crit: &mut Criterion) {
+	let mut group = crit.benchmark_group("Morphology");
+	group.sample_size(20);
+
+	let mut previous = analysis::to_luma(&noisy_frame_with_square(900, 500));
+	let current = analysis::to_luma(&noisy_frame_with_square(960, 500));
+	// Low threshold, so that the noise leaves speckles.
+	analysis::frame_difference(&mut previous, &current, Threshold::Global(12));
+	let mask = GrayImage::from_fn(1920, 1080, |x, y| {
+		Luma([if previous.get_pixel(x, y).0[0] != 0 { 0xFF } else { 0x00 }])
+	});
+	let pool = FramePool::new();
+
+	for element in [Element::Square, Element::Cross, Element::Disc] {
+		let opening = Morphology { operation: Operation::Open, element, radius: 2 };
+		group.bench_function(BenchmarkId::new("analysis::open", opening), |b| {
+			b.iter(|| {
+				// Copy the mask into a recycled buffer, like the difference is.
+				let mut copy: GrayImage = pool.image(1920, 1080);
+				copy.copy_from_slice(&mask);
+				let opened = opening.apply(black_box(copy), &pool);
+				pool.recycle_image(opened);
+			})
+		});
+	}
+
+	group.bench_function("imageproc::open square", |b| {
+		b.iter(|| morphology::open(black_box(&mask), Norm::LInf, 2))
+	});
+
+	group.finish();
+}
+
 /// Benchmark and compare finding the rightmost pixel that is not black (with
 /// movement when applied to diff images).
 pub fn find_rightmost_benches(crit: &mut Criterion) {
@@ -274,3 +307,103 @@ fn find_right_try(diff_img: &RgbImage) -> Option<Point<u32>> {
 	}
 	None
 }
+
+/// Process the current frame at the given downscaling factor, compare it to the
+/// processed previous frame and locate the movement in full frame coordinates.
+fn locate_movement(
+	processed_previous: &RgbImage,
+	current: &RgbImage,
+	factor: u32,
+) -> Option<Point<u32>> {
+	let current = if factor > 1 {
+		analysis::process_frame(&analysis::downscale(current, factor))
+	} else {
+		analysis::process_frame(current)
+	};
+	let mut difference = processed_previous.clone();
+	analysis::frame_difference(&mut difference, &current, Threshold::default());
+	analysis::find_right_movement(&difference).map(|point| analysis::upscale_point(point, factor))
+}
+
+/// Benchmark and compare the analysis of a 1080p frame at full resolution and
+/// downscaled by different factors. The accuracy is printed as distance of the
+/// located point to the one located at full resolution on the same frames.
+pub fn processing_resolution_benches(crit: &mut Criterion) {
+	let mut group = crit.benchmark_group("Processing resolution");
+	group.sample_size(20);
+
+	let previous = noisy_frame_with_square(900, 500);
+	let current = noisy_frame_with_square(960, 500);
+	let reference = locate_movement(&analysis::process_frame(&previous), &current, 1)
+		.expect("movement at full resolution");
+	for factor in [1, 2, 3, 4] {
+		// The previous frame is processed in an earlier iteration of the
+		// pipeline, so only the current frame counts.
+		let processed_previous = analysis::process_frame(&analysis::downscale(&previous, factor));
+		match locate_movement(&processed_previous, &current, factor) {
+			Some(point) => {
+				let error = (f64::from(point.x) - f64::from(reference.x))
+					.hypot(f64::from(point.y) - f64::from(reference.y));
+				println!("Factor {factor}: point {point:?}, error {error:.1} px");
+			}
+			None => println!("Factor {factor}: no movement found"),
+		}
+
+		group.bench_with_input(BenchmarkId::new("analysis", factor), &factor, |b, &factor| {
+			b.iter(|| locate_movement(&processed_previous, black_box(&current), factor))
+		});
+	}
+
+	group.finish();
+}
+
+/// Benchmark and compare the analysis of a 1080p frame on all color channels
+/// and on the luma channel only, including the conversion.
+pub fn color_mode_benches(crit: &mut Criterion) {
+	let mut group = crit.benchmark_group("Color mode");
+	group.sample_size(20);
+
+	let previous = noisy_frame_with_square(900, 500);
+	let current = noisy_frame_with_square(960, 500);
+	let current_rgb = Frame::Rgb(current.clone());
+	let pool = FramePool::new();
+	for color_mode in [ColorMode::Rgb, ColorMode::Luma] {
+		// The previous frame is processed in an earlier iteration of the
+		// pipeline, so only the current frame counts.
+		let processed_previous = analysis::prepare_frame(&previous, color_mode, 1, &pool)
+			.unwrap_or_else(|| Frame::Rgb(previous.clone()))
+			.process(analysis::DEFAULT_BLUR_SIGMA, &pool);
+		group.bench_function(BenchmarkId::new("analysis", color_mode), |b| {
+			b.iter(|| {
+				let converted = analysis::prepare_frame(black_box(&current), color_mode, 1, &pool);
+				let processed = converted
+					.as_ref()
+					.unwrap_or(&current_rgb)
+					.process(analysis::DEFAULT_BLUR_SIGMA, &pool);
+				let previous = pool.copy_frame(&processed_previous);
+				let mask = previous.difference(&processed, Threshold::default(), &pool).unwrap();
+				let point = mask.find_right_movement();
+				// Return the buffers like the pipeline does.
+				for frame in converted.into_iter().chain([processed, mask.into_frame()]) {
+					pool.recycle_frame(frame);
+				}
+				point
+			})
+		});
+	}
+
+	group.finish();
+}
+
+/// 1080p frame with sensor-like noise on a gradient and a bright 100x100 square
+/// at the given position.
+fn noisy_frame_with_square(square_x: u32, square_y: u32) -> RgbImage {
+	let mut lcg = Lcg::new();
+	RgbImage::from_fn(1920, 1080, |x, y| {
+		let noise = lcg.next_u8() % 16;
+		let inside =
+			(square_x..square_x + 100).contains(&x) && (square_y..square_y + 100).contains(&y);
+		let value = if inside { 0xE0 } else { (x / 16) as u8 / 2 + (y / 32) as u8 } + noise;
+		*Pixel::from_slice(&[value; 3])
+	})
+}
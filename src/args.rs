@@ -0,0 +1,115 @@
+//! Command line arguments.
+
+use std::path::PathBuf;
+
+use clap::Parser;
+
+use crate::camera::{
+	analysis::{ColorMode, Threshold},
+	morphology::Morphology,
+	FramePolicy,
+};
+
+/// Track movement in the webcam's video to control the computer.
+#[derive(Debug, Parser)]
+#[clap(version, about)]
+pub struct Args {
+	/// Use the MJPEG stream at this HTTP URL instead of a system camera, e.g.
+	/// from an IP camera.
+	#[clap(long, conflicts_with_all = &["replay", "synthetic"])]
+	pub mjpeg_url: Option<String>,
+	/// Replay the images in this directory in a loop instead of using a
+	/// system camera.
+	#[clap(long, conflicts_with = "synthetic")]
+	pub replay: Option<PathBuf>,
+	/// Use synthetic frames with a moving square instead of a system camera.
+	#[clap(long)]
+	pub synthetic: bool,
+	/// Handling of frames arriving faster than they are processed: `latest`
+	/// processes only the newest frame, `queue:<length>` keeps the newest
+	/// frames and `block` makes the camera wait.
+	#[clap(long, default_value_t)]
+	pub frame_policy: FramePolicy,
+	/// Run the motion analysis on frames downscaled to at most this width,
+	/// which is much faster for high resolutions. Detected points are still
+	/// reported in full frame coordinates.
+	#[clap(long)]
+	pub processing_width: Option<u32>,
+	/// Color channels the motion analysis runs on: `rgb` for all channels or
+	/// `luma` for only the brightness, which is about three times faster.
+	#[clap(long, default_value_t)]
+	pub color_mode: ColorMode,
+	/// Threshold of the differences between frames, below which they are noise:
+	/// one value for all channels like `32`, one per channel like `24,32,48`,
+	/// or `otsu` to adapt it to the noise of each frame, optionally with a
+	/// minimum like `otsu:16`. Can be adjusted in the UI.
+	#[clap(long, default_value_t)]
+	pub threshold: Threshold,
+	/// Clean up the mask of moving pixels with a morphological operation
+	/// (`erode`, `dilate`, `open` or `close`) using a structuring element
+	/// (`square`, `cross` or `disc`) of the given radius, e.g. `open:disc:2`
+	/// to remove speckles of noise.
+	#[clap(long)]
+	pub morphology: Option<Morphology>,
+	/// Compensate camera shake, e.g. of a wobbling laptop lid, by aligning the
+	/// previous frame to the current one before comparing them.
+	#[clap(long)]
+	pub stabilise: bool,
+	/// Compensate changes of the brightness in bands of rows, e.g. from lights
+	/// switching on or flickering, before comparing frames.
+	#[clap(long)]
+	pub compensate_illumination: bool,
+	/// Locate no movement in frames, where more than this fraction of the
+	/// pixels moves, like `0.5`. Such frames changed as a whole, e.g. because
+	/// of the lighting.
+	#[clap(long)]
+	pub motion_limit: Option<f32>,
+	/// Preprocess the next frame on a separate thread while the current one is
+	/// analysed, so high resolution cameras reach a higher frame rate. Points
+	/// are still emitted in the order of the frames.
+	#[clap(long)]
+	pub pipelined: bool,
+	/// Emit the detected point as virtual joystick (Linux only).
+	#[clap(long)]
+	pub joystick: bool,
+	/// Horizontal neutral position of the joystick, as fraction of the frame
+	/// width.
+	#[clap(long, default_value_t = 0.5)]
+	pub joystick_neutral_x: f32,
+	/// Vertical neutral position of the joystick, as fraction of the frame
+	/// height.
+	#[clap(long, default_value_t = 0.5)]
+	pub joystick_neutral_y: f32,
+	/// Dead zone around the neutral position of the joystick, as fraction of
+	/// the axis range.
+	#[clap(long, default_value_t = 0.1)]
+	pub joystick_dead_zone: f32,
+	/// Publish tracking events on a Unix domain socket at this path and accept
+	/// commands from its clients.
+	#[clap(long)]
+	pub ipc_socket: Option<PathBuf>,
+	/// Send tracking results as OSC messages via UDP to this port on
+	/// localhost.
+	#[clap(long)]
+	pub osc_port: Option<u16>,
+	/// OSC address for the point, normalised to the frame size.
+	#[clap(long, default_value = "/webcam/point")]
+	pub osc_point_address: String,
+	/// OSC address for the area of movement, as fraction of the frame.
+	#[clap(long, default_value = "/webcam/area")]
+	pub osc_area_address: String,
+	/// OSC address for gestures.
+	#[clap(long, default_value = "/webcam/gesture")]
+	pub osc_gesture_address: String,
+	/// OSC address for the frame rate.
+	#[clap(long, default_value = "/webcam/fps")]
+	pub osc_fps_address: String,
+	/// Append the pipeline statistics to a CSV file at this path twice per
+	/// second.
+	#[clap(long)]
+	pub stats_csv: Option<PathBuf>,
+	/// Serve a preview of the frames and events via HTTP on this port on
+	/// localhost.
+	#[clap(long)]
+	pub preview_port: Option<u16>,
+}
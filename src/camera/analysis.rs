@@ -1,11 +1,14 @@
 //! Video / Image analysis.
 
-use std::ops::BitAnd;
+use std::{cell::RefCell, fmt, str::FromStr};
 
-use image::RgbImage;
-use imageproc::{filter, point::Point};
+use color_eyre::{eyre::eyre, Report, Result};
+use image::{GrayImage, ImageBuffer, Pixel, RgbImage};
+use imageproc::point::Point;
 use rayon::prelude::*;
 
+use super::{morphology::Morphology, pool::FramePool, simd};
+
 /// Mirror / flip image horizontally in place
 pub fn flip_in_place(image: &mut RgbImage) {
 	let layout = image.as_flat_samples().layout;
@@ -22,26 +25,880 @@ pub fn flip_in_place(image: &mut RgbImage) {
 	});
 }
 
+/// Color channels the analysis runs on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorMode {
+	/// Red, green and blue channels.
+	#[default]
+	Rgb,
+	/// Only the luma channel, a third of the work of all channels.
+	Luma,
+}
+
+impl FromStr for ColorMode {
+	type Err = Report;
+
+	/// Parse `rgb` or `luma`.
+	fn from_str(s: &str) -> Result<Self> {
+		match s {
+			"rgb" => Ok(Self::Rgb),
+			"luma" => Ok(Self::Luma),
+			_ => Err(eyre!("Unknown color mode {s}, expected rgb or luma")),
+		}
+	}
+}
+
+impl fmt::Display for ColorMode {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Rgb => f.write_str("rgb"),
+			Self::Luma => f.write_str("luma"),
+		}
+	}
+}
+
+/// Threshold of the differences between frames, below which differences are
+/// noise instead of motion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Threshold {
+	/// Same threshold for all channels.
+	Global(u8),
+	/// Thresholds of the red, green and blue channels. Luma frames use the
+	/// luma of the thresholds.
+	PerChannel([u8; 3]),
+	/// Threshold separating noise from motion in the frame's differences by
+	/// Otsu's method, but at least the given one, so that the noise of still
+	/// scenes is not taken as motion.
+	Otsu(u8),
+}
+
+impl Threshold {
+	/// Minimum of the adaptive threshold if none is given.
+	pub const DEFAULT_OTSU_MINIMUM: u8 = 16;
+
+	/// Thresholds of the channels of frames with the given number of channels.
+	/// Adaptive thresholds keep all differences, they are applied afterwards.
+	fn channels(self, channel_count: u8) -> [u8; 3] {
+		match self {
+			Self::Global(threshold) => [threshold; 3],
+			Self::PerChannel(thresholds) if channel_count == 1 => [pixel_luma(&thresholds); 3],
+			Self::PerChannel(thresholds) => thresholds,
+			Self::Otsu(_) => [0; 3],
+		}
+	}
+}
+
+impl Default for Threshold {
+	/// Differences of at least 32 are motion.
+	fn default() -> Self {
+		Self::Global(32)
+	}
+}
+
+impl FromStr for Threshold {
+	type Err = Report;
+
+	/// Parse a global threshold like `32`, per-channel thresholds like
+	/// `24,32,48` or `otsu` with an optional minimum like `otsu:16`.
+	fn from_str(s: &str) -> Result<Self> {
+		if s == "otsu" {
+			return Ok(Self::Otsu(Self::DEFAULT_OTSU_MINIMUM));
+		}
+		if let Some(minimum) = s.strip_prefix("otsu:") {
+			return Ok(Self::Otsu(minimum.parse()?));
+		}
+		let thresholds = s.split(',').map(str::parse).collect::<Result<Vec<u8>, _>>()?;
+		match thresholds[..] {
+			[threshold] => Ok(Self::Global(threshold)),
+			[red, green, blue] => Ok(Self::PerChannel([red, green, blue])),
+			_ => Err(eyre!("Expected one or three thresholds, got {s}")),
+		}
+	}
+}
+
+impl fmt::Display for Threshold {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Global(threshold) => write!(f, "{threshold}"),
+			Self::PerChannel([red, green, blue]) => write!(f, "{red},{green},{blue}"),
+			Self::Otsu(minimum) => write!(f, "otsu:{minimum}"),
+		}
+	}
+}
+
+/// Frame of the pipeline, in the color mode it is analysed in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Frame {
+	/// Frame with red, green and blue channels.
+	Rgb(RgbImage),
+	/// Frame with only the luma channel.
+	Luma(GrayImage),
+}
+
+impl Frame {
+	/// Width and height of the frame.
+	pub fn dimensions(&self) -> (u32, u32) {
+		match self {
+			Self::Rgb(image) => image.dimensions(),
+			Self::Luma(image) => image.dimensions(),
+		}
+	}
+
+	/// Process the frame to reduce noise with the given blur strength, see
+	/// [`process_frame_into`]. The buffers are taken from the pool.
+	pub fn process(&self, blur_sigma: f32, pool: &FramePool) -> Self {
+		match self {
+			Self::Rgb(image) => Self::Rgb(process_frame_pooled(image, blur_sigma, pool)),
+			Self::Luma(image) => Self::Luma(process_frame_pooled(image, blur_sigma, pool)),
+		}
+	}
+
+	/// Compare this previous frame to the current one and return the mask of
+	/// moving pixels, see [`frame_difference`] and [`MotionMask`]. Returns
+	/// `None` and recycles the frame, if the frames differ in color mode or
+	/// size.
+	pub fn difference(
+		self,
+		current: &Self,
+		threshold: Threshold,
+		pool: &FramePool,
+	) -> Option<MotionMask> {
+		match (self, current) {
+			(Self::Rgb(mut previous), Self::Rgb(current))
+				if previous.dimensions() == current.dimensions() =>
+			{
+				let motion_rows = frame_difference(&mut previous, current, threshold);
+				Some(MotionMask::from_difference(previous, motion_rows, pool))
+			}
+			(Self::Luma(mut previous), Self::Luma(current))
+				if previous.dimensions() == current.dimensions() =>
+			{
+				let motion_rows = frame_difference(&mut previous, current, threshold);
+				Some(MotionMask::from_difference(previous, motion_rows, pool))
+			}
+			(previous, _) => {
+				pool.recycle_frame(previous);
+				None
+			}
+		}
+	}
+
+	/// Find the rightmost movement in this difference image, see
+	/// [`find_right_movement`].
+	pub fn find_right_movement(&self) -> Option<Point<u32>> {
+		match self {
+			Self::Rgb(image) => find_right_movement(image),
+			Self::Luma(image) => find_right_movement(image),
+		}
+	}
+
+	/// Fraction of moving pixels in the region of this difference image, see
+	/// [`motion_fraction`].
+	pub fn motion_fraction(&self, x: u32, y: u32, width: u32, height: u32) -> f32 {
+		match self {
+			Self::Rgb(image) => motion_fraction(image, x, y, width, height),
+			Self::Luma(image) => motion_fraction(image, x, y, width, height),
+		}
+	}
+}
+
+/// Convert the mirrored camera frame for the analysis in the given color mode,
+/// downscaled by the given factor. Returns `None` if the frame can be analysed
+/// as it is. The buffers are taken from the pool.
+pub fn prepare_frame(
+	image: &RgbImage,
+	color_mode: ColorMode,
+	factor: u32,
+	pool: &FramePool,
+) -> Option<Frame> {
+	let factor = factor.max(1);
+	let (width, height) = image.dimensions();
+	let (small_width, small_height) = (width / factor, height / factor);
+	match color_mode {
+		ColorMode::Rgb if factor > 1 => {
+			let mut small = pool.image(small_width, small_height);
+			downscale_into(image, factor, &mut small);
+			Some(Frame::Rgb(small))
+		}
+		ColorMode::Rgb => None,
+		ColorMode::Luma => {
+			let mut luma = pool.image(width, height);
+			to_luma_into(image, &mut luma);
+			if factor > 1 {
+				let mut small = pool.image(small_width, small_height);
+				downscale_into(&luma, factor, &mut small);
+				pool.recycle_image(luma);
+				luma = small;
+			}
+			Some(Frame::Luma(luma))
+		}
+	}
+}
+
+/// Convert the image to its luma channel, weighting the colors as in BT.601.
+pub fn to_luma(image: &RgbImage) -> GrayImage {
+	let (width, height) = image.dimensions();
+	let mut luma = GrayImage::new(width, height);
+	to_luma_into(image, &mut luma);
+	luma
+}
+
+/// Convert the image to its luma channel like [`to_luma`], writing into the
+/// given image of the same size.
+pub fn to_luma_into(image: &RgbImage, luma: &mut GrayImage) {
+	debug_assert_eq!(image.dimensions(), luma.dimensions());
+	let stride = image.as_flat_samples().layout.height_stride;
+	let width = luma.width();
+	luma.par_chunks_exact_mut(width as usize).zip(image.par_chunks(stride)).for_each(
+		|(luma_row, row)| {
+			for (value, pix) in luma_row.iter_mut().zip(row.chunks_exact(3)) {
+				*value = pixel_luma(pix);
+			}
+		},
+	);
+}
+
+/// Luma of a pixel's red, green and blue values, weighted as in BT.601.
+fn pixel_luma(pix: &[u8]) -> u8 {
+	let weighted = 77 * u32::from(pix[0]) + 150 * u32::from(pix[1]) + 29 * u32::from(pix[2]);
+	((weighted + 128) >> 8) as u8
+}
+
+/// Integer factor to downscale frames of the given width with, so that they
+/// are at most `max_width` wide.
+pub fn downscale_factor(width: u32, max_width: u32) -> u32 {
+	(width as f32 / max_width.max(1) as f32).ceil().max(1.0) as u32
+}
+
+/// Downscale the image by the given factor, averaging the pixels of each
+/// `factor` x `factor` block. Remaining pixels at the right and bottom edge,
+/// which do not fill a whole block, are ignored.
+pub fn downscale<P>(image: &ImageBuffer<P, Vec<u8>>, factor: u32) -> ImageBuffer<P, Vec<u8>>
+where
+	P: Pixel<Subpixel = u8> + 'static,
+{
+	let factor = factor.max(1);
+	let mut out = ImageBuffer::new(image.width() / factor, image.height() / factor);
+	downscale_into(image, factor, &mut out);
+	out
+}
+
+/// Downscale the image by the given factor like [`downscale`], writing into
+/// the given image of the downscaled size.
+pub fn downscale_into<P>(
+	image: &ImageBuffer<P, Vec<u8>>,
+	factor: u32,
+	out: &mut ImageBuffer<P, Vec<u8>>,
+) where
+	P: Pixel<Subpixel = u8> + 'static,
+{
+	let factor = factor.max(1);
+	let width = image.width() / factor;
+	debug_assert_eq!(out.dimensions(), (width, image.height() / factor));
+	let layout = image.as_flat_samples().layout;
+	let block_rows = layout.height_stride * factor as usize;
+	let pix_size = layout.width_stride;
+	let factor = factor as usize;
+	let area = (factor * factor) as u32;
+
+	out.par_chunks_exact_mut(width as usize * pix_size).zip(image.par_chunks(block_rows)).for_each(
+		|(out_row, rows)| {
+			// Sum each block per channel, then average it.
+			for (x, out_pix) in out_row.chunks_exact_mut(pix_size).enumerate() {
+				let block = x * factor * pix_size..(x + 1) * factor * pix_size;
+				for (channel, out_value) in out_pix.iter_mut().enumerate() {
+					let sum: u32 = rows
+						.chunks_exact(layout.height_stride)
+						.flat_map(|row| row[block.clone()].iter().skip(channel).step_by(pix_size))
+						.map(|value| u32::from(*value))
+						.sum();
+					*out_value = ((sum + area / 2) / area) as u8;
+				}
+			}
+		},
+	);
+}
+
+/// Scale a point found in an image downscaled by the given factor back to the
+/// original image, pointing at the center of the block.
+pub fn upscale_point(point: Point<u32>, factor: u32) -> Point<u32> {
+	let factor = factor.max(1);
+	Point::new(point.x * factor + factor / 2, point.y * factor + factor / 2)
+}
+
+/// Radius of the median filter of processed frames, i.e. a 5x5 kernel.
+const MEDIAN_RADIUS: u32 = 2;
+/// Standard deviation of the Gaussian blur of processed frames, unless it is
+/// calibrated to the camera's noise.
+pub const DEFAULT_BLUR_SIGMA: f32 = 1.0;
+
 /// Process image frame to reduce noise and such for optimal comparison to
 /// previous image. Makes motion detection reliable.
-pub fn process_frame(image: &RgbImage) -> RgbImage {
-	let image = filter::median_filter(image, 2, 2); // 5x5
-	filter::gaussian_blur_f32(&image, 1.0)
+pub fn process_frame<P>(image: &ImageBuffer<P, Vec<u8>>) -> ImageBuffer<P, Vec<u8>>
+where
+	P: Pixel<Subpixel = u8> + 'static,
+{
+	let (width, height) = image.dimensions();
+	let mut out = ImageBuffer::new(width, height);
+	let mut scratch = ImageBuffer::new(width, height);
+	process_frame_into(image, DEFAULT_BLUR_SIGMA, &mut out, &mut scratch);
+	out
+}
+
+/// Process the image frame like [`process_frame`] with the given blur
+/// strength, writing into the given image of the same size. The scratch image
+/// of the same size holds intermediate results.
+pub fn process_frame_into<P>(
+	image: &ImageBuffer<P, Vec<u8>>,
+	blur_sigma: f32,
+	out: &mut ImageBuffer<P, Vec<u8>>,
+	scratch: &mut ImageBuffer<P, Vec<u8>>,
+) where
+	P: Pixel<Subpixel = u8> + 'static,
+{
+	median_filter_into(image, MEDIAN_RADIUS, out);
+	gaussian_blur_in_place(out, blur_sigma, scratch);
+}
+
+/// Process the image frame like [`process_frame_into`], using buffers of the
+/// pool.
+fn process_frame_pooled<P>(
+	image: &ImageBuffer<P, Vec<u8>>,
+	blur_sigma: f32,
+	pool: &FramePool,
+) -> ImageBuffer<P, Vec<u8>>
+where
+	P: Pixel<Subpixel = u8> + 'static,
+{
+	let (width, height) = image.dimensions();
+	let mut out = pool.image(width, height);
+	let mut scratch = pool.image(width, height);
+	process_frame_into(image, blur_sigma, &mut out, &mut scratch);
+	pool.recycle_image(scratch);
+	out
+}
+
+/// Gaussian blur with the given standard deviation, padding the image by
+/// continuity. Gives the same result as [`imageproc::filter::gaussian_blur_f32`], but
+/// filters in place with a scratch image of the same size and runs rows in
+/// parallel.
+pub fn gaussian_blur_in_place<P>(
+	image: &mut ImageBuffer<P, Vec<u8>>,
+	sigma: f32,
+	scratch: &mut ImageBuffer<P, Vec<u8>>,
+) where
+	P: Pixel<Subpixel = u8> + 'static,
+{
+	debug_assert_eq!(image.dimensions(), scratch.dimensions());
+	let (width, height) = image.dimensions();
+	if width == 0 || height == 0 {
+		return;
+	}
+
+	let kernel = gaussian_kernel(sigma);
+	let radius = (kernel.len() / 2) as isize;
+	let layout = image.as_flat_samples().layout;
+	let channels = layout.width_stride;
+	let row_len = layout.height_stride;
+
+	// Horizontally into the scratch image.
+	scratch.par_chunks_exact_mut(row_len).zip(image.par_chunks_exact(row_len)).for_each(
+		|(out_row, row)| {
+			for (x, out_pix) in (0..width as isize).zip(out_row.chunks_exact_mut(channels)) {
+				for (channel, out_value) in out_pix.iter_mut().enumerate() {
+					let mut sum = 0.0;
+					for (dx, weight) in (-radius..=radius).zip(&kernel) {
+						let source = (x + dx).clamp(0, width as isize - 1) as usize;
+						sum += f32::from(row[source * channels + channel]) * weight;
+					}
+					// Saturating and truncating like imageproc.
+					*out_value = sum as u8;
+				}
+			}
+		},
+	);
+
+	// Vertically back into the image.
+	let samples = scratch.as_raw();
+	image.par_chunks_exact_mut(row_len).enumerate().for_each(|(y, out_row)| {
+		for (index, out_value) in out_row.iter_mut().enumerate() {
+			let mut sum = 0.0;
+			for (dy, weight) in (-radius..=radius).zip(&kernel) {
+				let source = (y as isize + dy).clamp(0, height as isize - 1) as usize;
+				sum += f32::from(samples[source * row_len + index]) * weight;
+			}
+			*out_value = sum as u8;
+		}
+	});
+}
+
+/// Kernel of the Gaussian blur with the given standard deviation. Same kernel
+/// as imageproc, which is not normalised.
+fn gaussian_kernel(sigma: f32) -> Vec<f32> {
+	let radius = (2.0 * sigma).ceil() as usize;
+	(0..=2 * radius)
+		.map(|i| {
+			let x = i.abs_diff(radius) as f32;
+			((2.0 * std::f32::consts::PI).sqrt() * sigma).recip()
+				* (-x.powi(2) / (2.0 * sigma.powi(2))).exp()
+		})
+		.collect()
+}
+
+/// Factor the Gaussian blur with the given standard deviation scales the
+/// standard deviation of noise by, which was uncorrelated before processing.
+pub fn blur_noise_gain(sigma: f32) -> f32 {
+	// The median filter leaves the noise correlated over its kernel, which is
+	// approximated by averaging over the kernel before blurring.
+	let kernel = gaussian_kernel(sigma);
+	let median_size = 2 * MEDIAN_RADIUS as usize + 1;
+	let combined = (0..kernel.len() + median_size - 1).map(|i| {
+		let start = i.saturating_sub(median_size - 1);
+		kernel[start..kernel.len().min(i + 1)].iter().sum::<f32>() / median_size as f32
+	});
+	// The blur is separable, so the gains of both directions multiply.
+	combined.map(|weight| weight * weight).sum::<f32>() * median_size as f32
+}
+
+/// Median filter with a square kernel of `2 * radius + 1` pixels, padding the
+/// image by continuity like [`imageproc::filter::median_filter`].
+///
+/// Uses the constant time algorithm of Perreault and Hébert: every column keeps
+/// a histogram of its pixels in the rows of the kernel, which is updated by one
+/// pixel per row. The kernel's histogram slides right by subtracting and adding
+/// column histograms. Histograms have 16 coarse bins and 256 fine bins, and the
+/// fine bins of the kernel are only updated for the coarse bin with the median.
+/// Bands of rows are filtered in parallel.
+pub fn median_filter<P>(image: &ImageBuffer<P, Vec<u8>>, radius: u32) -> ImageBuffer<P, Vec<u8>>
+where
+	P: Pixel<Subpixel = u8> + 'static,
+{
+	let (width, height) = image.dimensions();
+	let mut out = ImageBuffer::new(width, height);
+	median_filter_into(image, radius, &mut out);
+	out
+}
+
+/// Median filter like [`median_filter`], writing into the given image of the
+/// same size.
+pub fn median_filter_into<P>(
+	image: &ImageBuffer<P, Vec<u8>>,
+	radius: u32,
+	out: &mut ImageBuffer<P, Vec<u8>>,
+) where
+	P: Pixel<Subpixel = u8> + 'static,
+{
+	debug_assert_eq!(image.dimensions(), out.dimensions());
+	let (width, height) = image.dimensions();
+	if width == 0 || height == 0 {
+		return;
+	}
+
+	let layout = image.as_flat_samples().layout;
+	let band = MedianBand {
+		samples: image.as_raw(),
+		width: width as isize,
+		height: height as isize,
+		channels: layout.width_stride,
+		radius: radius as isize,
+	};
+	out.par_chunks_mut(layout.height_stride * MEDIAN_BAND_HEIGHT)
+		.enumerate()
+		.for_each(|(index, out_rows)| band.filter(index * MEDIAN_BAND_HEIGHT, out_rows));
+}
+
+/// Number of rows filtered as one band by [`median_filter`]. Each band builds
+/// its column histograms from scratch.
+const MEDIAN_BAND_HEIGHT: usize = 64;
+
+thread_local! {
+	/// Column and kernel histograms of [`median_filter`], kept per thread so
+	/// their memory is reused for every band.
+	static MEDIAN_HISTOGRAMS: RefCell<(Vec<ColumnHistogram>, Vec<KernelHistogram>)> =
+		RefCell::default();
+}
+
+/// Image to filter by [`median_filter`] in bands of rows.
+struct MedianBand<'a> {
+	samples: &'a [u8],
+	width: isize,
+	height: isize,
+	channels: usize,
+	radius: isize,
+}
+
+impl MedianBand<'_> {
+	/// Samples of the row, clamped to the image.
+	fn row(&self, y: isize) -> &[u8] {
+		let row_len = self.width as usize * self.channels;
+		let y = y.clamp(0, self.height - 1) as usize;
+		&self.samples[y * row_len..(y + 1) * row_len]
+	}
+
+	/// Filter the rows starting at the given one into the output rows.
+	fn filter(&self, first_row: usize, out_rows: &mut [u8]) {
+		MEDIAN_HISTOGRAMS.with(|histograms| {
+			let (columns, kernels) = &mut *histograms.borrow_mut();
+			self.filter_with(first_row, out_rows, columns, kernels);
+		});
+	}
+
+	/// Filter the rows like [`Self::filter`], using the given histograms.
+	fn filter_with(
+		&self,
+		first_row: usize,
+		out_rows: &mut [u8],
+		columns: &mut Vec<ColumnHistogram>,
+		kernels: &mut Vec<KernelHistogram>,
+	) {
+		let channels = self.channels;
+		let radius = self.radius;
+		let half = ((2 * radius + 1) * (2 * radius + 1) / 2) as u32;
+
+		// Histograms of the kernel's rows for every column and channel.
+		columns.clear();
+		columns.resize(self.width as usize * channels, ColumnHistogram::default());
+		for dy in -radius..=radius {
+			for (column, value) in columns.iter_mut().zip(self.row(first_row as isize + dy)) {
+				column.add(*value);
+			}
+		}
+
+		let row_len = self.width as usize * channels;
+		for (y, out_row) in (first_row as isize..).zip(out_rows.chunks_exact_mut(row_len)) {
+			// Move the column histograms down by a row right before the kernel
+			// reaches them, so the histograms are only read from memory once.
+			let moved_rows =
+				(y > first_row as isize).then(|| (self.row(y - radius - 1), self.row(y + radius)));
+			let move_down = |columns: &mut [ColumnHistogram], x: isize| {
+				if let (Some((removed, added)), true) = (moved_rows, x < self.width) {
+					let pixel = x as usize * channels..(x as usize + 1) * channels;
+					for ((column, old), new) in columns[pixel.clone()]
+						.iter_mut()
+						.zip(&removed[pixel.clone()])
+						.zip(&added[pixel])
+					{
+						column.remove(*old);
+						column.add(*new);
+					}
+				}
+			};
+			for x in 0..=radius {
+				move_down(columns, x);
+			}
+
+			// Slide the kernels of all channels right together, so they use
+			// neighbouring column histograms.
+			kernels.clear();
+			kernels.resize_with(channels, KernelHistogram::default);
+			for (channel, kernel) in kernels.iter_mut().enumerate() {
+				for dx in -radius..=radius {
+					kernel.add_coarse(
+						&columns[dx.clamp(0, self.width - 1) as usize * channels + channel],
+					);
+				}
+			}
+			for (x, out_pix) in (0..self.width).zip(out_row.chunks_exact_mut(channels)) {
+				move_down(columns, x + radius + 1);
+				for (channel, (kernel, out_value)) in kernels.iter_mut().zip(out_pix).enumerate() {
+					let column = |x: isize| {
+						&columns[x.clamp(0, self.width - 1) as usize * channels + channel]
+					};
+					*out_value = kernel.median(column, x, radius, half);
+					kernel.remove_coarse(column(x - radius));
+					kernel.add_coarse(column(x + radius + 1));
+				}
+			}
+		}
+	}
+}
+
+/// Histogram of a column's pixels within the rows of the kernel.
+#[derive(Clone, Default)]
+struct ColumnHistogram {
+	coarse: [u16; 16],
+	/// Fine bins, in segments per coarse bin.
+	fine: [[u16; 16]; 16],
+}
+
+impl ColumnHistogram {
+	fn add(&mut self, value: u8) {
+		self.coarse[usize::from(value >> 4)] += 1;
+		self.fine[usize::from(value >> 4)][usize::from(value & 0x0F)] += 1;
+	}
+
+	fn remove(&mut self, value: u8) {
+		self.coarse[usize::from(value >> 4)] -= 1;
+		self.fine[usize::from(value >> 4)][usize::from(value & 0x0F)] -= 1;
+	}
+}
+
+/// Histogram of the pixels within the kernel, for one channel.
+#[derive(Default)]
+struct KernelHistogram {
+	coarse: [u32; 16],
+	/// Fine bins, in segments per coarse bin.
+	fine: [[u32; 16]; 16],
+	/// Kernel position each segment of fine bins is up to date for.
+	fine_position: [Option<isize>; 16],
+}
+
+impl KernelHistogram {
+	fn add_coarse(&mut self, column: &ColumnHistogram) {
+		add_bins(&mut self.coarse, &column.coarse);
+	}
+
+	fn remove_coarse(&mut self, column: &ColumnHistogram) {
+		remove_bins(&mut self.coarse, &column.coarse);
+	}
+
+	/// Median of the kernel at position `x`, whose coarse bins are up to date.
+	/// `half` is half the number of pixels in the kernel, rounded down.
+	fn median<'a>(
+		&mut self,
+		column: impl Fn(isize) -> &'a ColumnHistogram,
+		x: isize,
+		radius: isize,
+		half: u32,
+	) -> u8 {
+		let mut count = 0;
+		let mut segment = 0;
+		while segment < 15 && count + self.coarse[segment] <= half {
+			count += self.coarse[segment];
+			segment += 1;
+		}
+
+		// Slide the fine bins of the segment to the current position, or
+		// rebuild them if that is cheaper.
+		let fine = &mut self.fine[segment];
+		match self.fine_position[segment] {
+			Some(position) if x - position <= radius => {
+				for moved in position..x {
+					remove_bins(fine, &column(moved - radius).fine[segment]);
+					add_bins(fine, &column(moved + radius + 1).fine[segment]);
+				}
+			}
+			_ => {
+				*fine = [0; 16];
+				for dx in -radius..=radius {
+					add_bins(fine, &column(x + dx).fine[segment]);
+				}
+			}
+		}
+		self.fine_position[segment] = Some(x);
+
+		for (offset, bin) in fine.iter().enumerate() {
+			count += bin;
+			if count > half {
+				return (segment * 16 + offset) as u8;
+			}
+		}
+		0xFF
+	}
+}
+
+/// Add the counts of a column's bins to the kernel's bins.
+fn add_bins(bins: &mut [u32; 16], counts: &[u16; 16]) {
+	for (bin, count) in bins.iter_mut().zip(counts) {
+		*bin += u32::from(*count);
+	}
+}
+
+/// Remove the counts of a column's bins from the kernel's bins.
+fn remove_bins(bins: &mut [u32; 16], counts: &[u16; 16]) {
+	for (bin, count) in bins.iter_mut().zip(counts) {
+		*bin -= u32::from(*count);
+	}
 }
 
 /// Compute the difference image, which is just the absolute difference in the
-/// pixel values compared to the previous version.
-pub fn frame_difference(previous: &mut RgbImage, current: &RgbImage) {
+/// pixel values compared to the previous version. Differences below the
+/// threshold are noise and set to zero. Returns which rows have motion left.
+pub fn frame_difference<P>(
+	previous: &mut ImageBuffer<P, Vec<u8>>,
+	current: &ImageBuffer<P, Vec<u8>>,
+	threshold: Threshold,
+) -> Vec<bool>
+where
+	P: Pixel<Subpixel = u8> + 'static,
+{
 	let stride = previous.as_flat_samples().layout.height_stride;
-	previous.par_chunks_mut(stride).zip(current.par_chunks(stride)).for_each(|(prev, cur)| {
-		for (prev, cur) in prev.iter_mut().zip(cur) {
-			*prev = prev.abs_diff(*cur).bitand(0b11100000);
+	let thresholds = threshold.channels(P::CHANNEL_COUNT);
+	let thresholds = &thresholds[..usize::from(P::CHANNEL_COUNT)];
+	let differences = previous.par_chunks_mut(stride).zip(current.par_chunks(stride));
+	match threshold {
+		Threshold::Otsu(minimum) => {
+			// Which rows have motion is only known after the adaptive threshold.
+			differences.for_each(|(prev, cur)| {
+				simd::difference_row(prev, cur, thresholds);
+			});
+			let threshold = otsu_threshold(&histogram(previous)).max(minimum);
+			previous.par_chunks_mut(stride).map(|row| threshold_row(row, threshold)).collect()
+		}
+		_ => differences.map(|(prev, cur)| simd::difference_row(prev, cur, thresholds)).collect(),
+	}
+}
+
+/// Histogram of all sample values of the image.
+fn histogram<P>(image: &ImageBuffer<P, Vec<u8>>) -> [u32; 256]
+where
+	P: Pixel<Subpixel = u8> + 'static,
+{
+	let stride = image.as_flat_samples().layout.height_stride;
+	image
+		.par_chunks(stride)
+		.fold(
+			|| [0; 256],
+			|mut histogram, row| {
+				for value in row {
+					histogram[usize::from(*value)] += 1;
+				}
+				histogram
+			},
+		)
+		.reduce(
+			|| [0; 256],
+			|mut histogram, other| {
+				for (count, other) in histogram.iter_mut().zip(other) {
+					*count += other;
+				}
+				histogram
+			},
+		)
+}
+
+/// Threshold separating the values of the histogram into two classes with the
+/// maximal variance between them, by Otsu's method. Values of at least the
+/// threshold belong to the upper class.
+pub fn otsu_threshold(histogram: &[u32; 256]) -> u8 {
+	let total: f64 = histogram.iter().map(|count| f64::from(*count)).sum();
+	let total_sum: f64 =
+		histogram.iter().enumerate().map(|(value, count)| value as f64 * f64::from(*count)).sum();
+
+	let mut best = (0, 0.0);
+	let mut lower_count = 0.0;
+	let mut lower_sum = 0.0;
+	for (value, count) in histogram.iter().enumerate().take(255) {
+		lower_count += f64::from(*count);
+		lower_sum += value as f64 * f64::from(*count);
+		let upper_count = total - lower_count;
+		if lower_count == 0.0 || upper_count == 0.0 {
+			continue;
+		}
+		let mean_difference = lower_sum / lower_count - (total_sum - lower_sum) / upper_count;
+		let variance = lower_count * upper_count * mean_difference * mean_difference;
+		if variance > best.1 {
+			best = (value + 1, variance);
+		}
+	}
+	best.0 as u8
+}
+
+/// Set values below the threshold to zero. Returns whether any value is left.
+fn threshold_row(row: &mut [u8], threshold: u8) -> bool {
+	let mut motion = 0;
+	for value in row {
+		if *value < threshold {
+			*value = 0;
 		}
-	})
+		motion |= *value;
+	}
+	motion != 0
+}
+
+/// Binary mask of the pixels with motion, with the rows that have any.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MotionMask {
+	/// `0xFF` for pixels with motion, zero otherwise.
+	mask: GrayImage,
+	/// Whether each row has motion.
+	motion_rows: Vec<bool>,
+}
+
+impl MotionMask {
+	/// Turn the difference image with the rows that have motion, as returned
+	/// by [`frame_difference`], into the mask of pixels with any difference
+	/// left. The buffers are taken from and returned to the pool.
+	pub fn from_difference<P>(
+		diff_img: ImageBuffer<P, Vec<u8>>,
+		motion_rows: Vec<bool>,
+		pool: &FramePool,
+	) -> Self
+	where
+		P: Pixel<Subpixel = u8> + 'static,
+	{
+		let (width, height) = diff_img.dimensions();
+		let pix_size = usize::from(P::CHANNEL_COUNT);
+		if pix_size == 1 {
+			// Luma differences become the mask in place.
+			let mut mask = diff_img.into_raw();
+			mask.par_iter_mut().for_each(|value| *value = if *value != 0 { 0xFF } else { 0x00 });
+			let mask = GrayImage::from_raw(width, height, mask).expect("buffer fits the mask");
+			return Self { mask, motion_rows };
+		}
+
+		let mut mask: GrayImage = pool.image(width, height);
+		mask.par_chunks_exact_mut(width as usize)
+			.zip(diff_img.par_chunks_exact(width as usize * pix_size))
+			.for_each(|(mask_row, row)| {
+				for (value, pix) in mask_row.iter_mut().zip(row.chunks_exact(pix_size)) {
+					*value = if pix.iter().any(|value| *value != 0) { 0xFF } else { 0x00 };
+				}
+			});
+		pool.recycle_image(diff_img);
+		Self { mask, motion_rows }
+	}
+
+	/// Width and height of the mask.
+	pub fn dimensions(&self) -> (u32, u32) {
+		self.mask.dimensions()
+	}
+
+	/// Whether the pixel has motion.
+	pub fn is_moving(&self, x: u32, y: u32) -> bool {
+		self.mask.get_pixel(x, y).0[0] != 0
+	}
+
+	/// Whether each row has motion.
+	pub fn motion_rows(&self) -> &[bool] {
+		&self.motion_rows
+	}
+
+	/// Find the rightmost pixel with motion, see
+	/// [`find_right_movement_in_rows`].
+	pub fn find_right_movement(&self) -> Option<Point<u32>> {
+		find_right_movement_in_rows(&self.mask, &self.motion_rows)
+	}
+
+	/// Fraction of pixels with motion in the region, see [`motion_fraction`].
+	pub fn motion_fraction(&self, x: u32, y: u32, width: u32, height: u32) -> f32 {
+		motion_fraction(&self.mask, x, y, width, height)
+	}
+
+	/// Clean up the mask with the morphological operation, e.g. to remove
+	/// speckles of noise. The buffers are taken from and returned to the pool.
+	pub fn clean(self, morphology: &Morphology, pool: &FramePool) -> Self {
+		let mask = morphology.apply(self.mask, pool);
+		let mut motion_rows = self.motion_rows;
+		motion_rows.clear();
+		motion_rows.par_extend(
+			mask.par_chunks_exact(mask.width() as usize)
+				.map(|row| row.iter().any(|value| *value != 0)),
+		);
+		Self { mask, motion_rows }
+	}
+
+	/// Turn the mask into a luma frame, e.g. to display it.
+	pub fn into_frame(self) -> Frame {
+		Frame::Luma(self.mask)
+	}
 }
 
 /// Find the rightmost pixel that is not black and return its position.
-pub fn find_right_movement(diff_img: &RgbImage) -> Option<Point<u32>> {
+pub fn find_right_movement<P>(diff_img: &ImageBuffer<P, Vec<u8>>) -> Option<Point<u32>>
+where
+	P: Pixel<Subpixel = u8> + 'static,
+{
 	let layout = diff_img.as_flat_samples().layout;
 	let pix_size = layout.channel_stride * layout.channels as usize;
 	diff_img
@@ -50,24 +907,331 @@ pub fn find_right_movement(diff_img: &RgbImage) -> Option<Point<u32>> {
 		.filter_map(|(y, row)| {
 			row.chunks_exact(pix_size)
 				.enumerate()
-				.rfind(|(_, pix)| **pix != [0x00, 0x00, 0x00])
+				.rfind(|(_, pix)| pix.iter().any(|value| *value != 0x00))
 				.map(|(x, _)| (x, y))
 		})
 		.max_by_key(|(x, _y)| *x)
 		.map(|(x, y)| Point::new(x as u32, y as u32))
 }
 
+/// Find the rightmost pixel that is not black like [`find_right_movement`],
+/// but only search the rows with motion, as returned by [`frame_difference`].
+pub fn find_right_movement_in_rows<P>(
+	diff_img: &ImageBuffer<P, Vec<u8>>,
+	motion_rows: &[bool],
+) -> Option<Point<u32>>
+where
+	P: Pixel<Subpixel = u8> + 'static,
+{
+	let layout = diff_img.as_flat_samples().layout;
+	let pix_size = layout.channel_stride * layout.channels as usize;
+	diff_img
+		.par_chunks_exact(layout.height_stride)
+		.zip(motion_rows)
+		.enumerate()
+		.filter(|(_y, (_row, motion))| **motion)
+		.filter_map(|(y, (row, _motion))| {
+			row.chunks_exact(pix_size)
+				.rposition(|pix| pix.iter().any(|value| *value != 0x00))
+				.map(|x| (x, y))
+		})
+		.max_by_key(|(x, _y)| *x)
+		.map(|(x, y)| Point::new(x as u32, y as u32))
+}
+
+/// Compute the fraction of pixels that are not black within the given region
+/// of the difference image. The region is clipped to the image.
+pub fn motion_fraction<P>(
+	diff_img: &ImageBuffer<P, Vec<u8>>,
+	x: u32,
+	y: u32,
+	width: u32,
+	height: u32,
+) -> f32
+where
+	P: Pixel<Subpixel = u8> + 'static,
+{
+	let x_end = x.saturating_add(width).min(diff_img.width());
+	let y_end = y.saturating_add(height).min(diff_img.height());
+	if x >= x_end || y >= y_end {
+		return 0.0;
+	}
+
+	let layout = diff_img.as_flat_samples().layout;
+	let pix_size = layout.channel_stride * layout.channels as usize;
+	let moving: usize = diff_img
+		.par_chunks_exact(layout.height_stride)
+		.skip(y as usize)
+		.take((y_end - y) as usize)
+		.map(|row| {
+			row[x as usize * pix_size..x_end as usize * pix_size]
+				.chunks_exact(pix_size)
+				.filter(|pix| pix.iter().any(|value| *value != 0x00))
+				.count()
+		})
+		.sum();
+	moving as f32 / ((x_end - x) * (y_end - y)) as f32
+}
+
 #[cfg(test)]
 mod tests {
+	use imageproc::filter;
+
 	use super::*;
+	use crate::camera::{morphology, testing::Lcg};
 
 	#[test]
 	fn flip_image() {
-		let mut image1 =
-			RgbImage::from_fn(256, 1, |x, _y| *image::Pixel::from_slice(&[x as u8; 3]));
+		let mut image1 = RgbImage::from_fn(256, 1, |x, _y| *Pixel::from_slice(&[x as u8; 3]));
 		let mut image2 = image1.clone();
 		flip_in_place(&mut image1);
 		image::imageops::flip_horizontal_in_place(&mut image2);
 		assert_eq!(image1, image2);
 	}
+
+	#[test]
+	fn downscale_by_area() {
+		let image = RgbImage::from_fn(5, 4, |x, y| {
+			*Pixel::from_slice(&[(x + y * 5) as u8 * 10, 0xFF, 0x00])
+		});
+		let small = downscale(&image, 2);
+		assert_eq!(small.dimensions(), (2, 2));
+		// Block of (0, 0), (1, 0), (0, 1), (1, 1).
+		assert_eq!(small.get_pixel(0, 0).0, [30, 0xFF, 0x00]);
+		assert_eq!(small.get_pixel(1, 1).0, [150, 0xFF, 0x00]);
+		assert_eq!(downscale(&image, 1), image);
+
+		assert_eq!(downscale_factor(1920, 640), 3);
+		assert_eq!(downscale_factor(1920, 700), 3);
+		assert_eq!(downscale_factor(640, 1920), 1);
+		assert_eq!(upscale_point(Point::new(1, 2), 4), Point::new(6, 10));
+	}
+
+	#[test]
+	fn downscaled_analysis_is_accurate() {
+		// Noisy gradient with a bright square, which moves to the right.
+		let frame = |square_x: u32| {
+			let mut lcg = Lcg::new();
+			RgbImage::from_fn(480, 270, |x, y| {
+				let noise = lcg.next_u8() % 16;
+				let inside = (square_x..square_x + 40).contains(&x) && (100..140).contains(&y);
+				let value = if inside { 0xE0 } else { (x / 8) as u8 + (y / 8) as u8 } + noise;
+				*Pixel::from_slice(&[value; 3])
+			})
+		};
+		let (previous, current) = (frame(200), frame(230));
+		let analyse = |factor: u32| {
+			let mut previous = process_frame(&downscale(&previous, factor));
+			let current = process_frame(&downscale(&current, factor));
+			frame_difference(&mut previous, &current, Threshold::default());
+			find_right_movement(&previous).map(|point| upscale_point(point, factor))
+		};
+
+		// Any point on the square's right edge is right, so only the
+		// horizontal error counts.
+		let reference = analyse(1).unwrap();
+		assert!((265..=275).contains(&reference.x), "{reference:?}");
+		for factor in 2..=4 {
+			let point = analyse(factor).unwrap();
+			assert!(point.x.abs_diff(reference.x) <= 2 * factor, "{factor}: {point:?}");
+			assert!((95..145).contains(&point.y), "{factor}: {point:?}");
+		}
+	}
+
+	#[test]
+	fn luma_analysis() {
+		let rgb = RgbImage::from_fn(8, 4, |x, y| {
+			*Pixel::from_slice(&[if (x, y) == (5, 0) { 0xFF } else { 0x00 }, 0xFF, 0x00])
+		});
+		let luma = to_luma(&rgb);
+		assert_eq!(luma.get_pixel(0, 0).0, [149]);
+		assert_eq!(luma.get_pixel(5, 0).0, [226]);
+
+		// Both color modes find the same movement.
+		let background = RgbImage::from_pixel(8, 4, *Pixel::from_slice(&[0x00, 0xFF, 0x00]));
+		let pool = FramePool::new();
+		for color_mode in [ColorMode::Rgb, ColorMode::Luma] {
+			let current =
+				prepare_frame(&rgb, color_mode, 1, &pool).unwrap_or(Frame::Rgb(rgb.clone()));
+			let previous = prepare_frame(&background, color_mode, 1, &pool)
+				.unwrap_or(Frame::Rgb(background.clone()));
+			let mask = previous.difference(&current, Threshold::default(), &pool).unwrap();
+			assert_eq!(mask.motion_rows(), [true, false, false, false]);
+			assert_eq!(mask.find_right_movement(), Some(Point::new(5, 0)));
+			assert_eq!(mask.motion_fraction(0, 0, 8, 4), 1.0 / 32.0);
+			let difference = mask.into_frame();
+			assert_eq!(difference.find_right_movement(), Some(Point::new(5, 0)));
+			assert_eq!(difference.motion_fraction(0, 0, 8, 4), 1.0 / 32.0);
+		}
+		let mismatch = Frame::Luma(luma).difference(&Frame::Rgb(rgb), Threshold::default(), &pool);
+		assert_eq!(mismatch, None);
+		assert_eq!("luma".parse::<ColorMode>().unwrap(), ColorMode::Luma);
+	}
+
+	#[test]
+	fn median_like_imageproc() {
+		let mut lcg = Lcg::new();
+		let mut random = || lcg.next_u8();
+
+		for (width, height) in [(1, 1), (3, 2), (17, 9), (90, 70), (64, 130)] {
+			let rgb = RgbImage::from_fn(width, height, |_x, _y| {
+				*Pixel::from_slice(&[random(), random(), random()])
+			});
+			// Few distinct values, so many pixels are equal.
+			let luma =
+				GrayImage::from_fn(width, height, |_x, _y| *Pixel::from_slice(&[random() & 0x07]));
+			for radius in 0..=3 {
+				assert_eq!(
+					median_filter(&rgb, radius),
+					filter::median_filter(&rgb, radius, radius)
+				);
+				assert_eq!(
+					median_filter(&luma, radius),
+					filter::median_filter(&luma, radius, radius)
+				);
+			}
+		}
+	}
+
+	#[test]
+	fn blur_like_imageproc() {
+		let mut lcg = Lcg::new();
+		let mut random = || lcg.next_u8();
+
+		for (width, height) in [(1, 1), (3, 2), (17, 9), (90, 70)] {
+			let image = RgbImage::from_fn(width, height, |_x, _y| {
+				*Pixel::from_slice(&[random(), random(), random()])
+			});
+			let mut blurred = image.clone();
+			gaussian_blur_in_place(&mut blurred, 1.0, &mut RgbImage::new(width, height));
+			assert_eq!(blurred, filter::gaussian_blur_f32(&image, 1.0));
+
+			let pool = FramePool::new();
+			let processed = Frame::Rgb(image.clone()).process(DEFAULT_BLUR_SIGMA, &pool);
+			let expected = filter::gaussian_blur_f32(&filter::median_filter(&image, 2, 2), 1.0);
+			assert_eq!(processed, Frame::Rgb(expected));
+		}
+	}
+
+	/// Frame with the given values left of the middle and zero right of it.
+	fn halves(left: [u8; 3]) -> RgbImage {
+		RgbImage::from_fn(8, 2, |x, _y| image::Rgb(if x < 4 { left } else { [0x00; 3] }))
+	}
+
+	#[test]
+	fn global_threshold() {
+		let pool = FramePool::new();
+		let moving = |difference: [u8; 3], threshold: Threshold| {
+			let previous = Frame::Rgb(halves(difference));
+			let mask = previous.difference(&Frame::Rgb(halves([0; 3])), threshold, &pool).unwrap();
+			mask.find_right_movement()
+		};
+		assert_eq!(moving([31, 0, 0], Threshold::Global(32)), None);
+		assert_eq!(moving([0, 0, 32], Threshold::Global(32)), Some(Point::new(3, 1)));
+		assert_eq!(moving([5, 5, 5], Threshold::Global(6)), None);
+		assert_eq!(moving([5, 5, 5], Threshold::Global(5)), Some(Point::new(3, 1)));
+		assert_eq!("32".parse::<Threshold>().unwrap(), Threshold::default());
+		assert!("32,32".parse::<Threshold>().is_err());
+	}
+
+	#[test]
+	fn per_channel_threshold() {
+		let pool = FramePool::new();
+		let threshold = Threshold::PerChannel([10, 20, 100]);
+		let mask = |difference: [u8; 3]| {
+			Frame::Rgb(halves(difference))
+				.difference(&Frame::Rgb(halves([0; 3])), threshold, &pool)
+				.unwrap()
+		};
+		assert!(!mask([9, 19, 99]).is_moving(0, 0));
+		assert!(mask([10, 0, 0]).is_moving(0, 0));
+		assert!(mask([0, 20, 0]).is_moving(0, 0));
+		assert!(!mask([0, 0, 99]).is_moving(0, 0));
+		assert!(!mask([10, 0, 0]).is_moving(4, 0));
+
+		// Luma frames use the luma of the thresholds, which is 26.
+		let luma = |value: u8| Frame::Luma(GrayImage::from_pixel(8, 2, image::Luma([value])));
+		let mask = luma(25).difference(&luma(0), threshold, &pool).unwrap();
+		assert_eq!(mask.motion_rows(), [false, false]);
+		let mask = luma(26).difference(&luma(0), threshold, &pool).unwrap();
+		assert_eq!(mask.motion_rows(), [true, true]);
+
+		assert_eq!("10,20,100".parse::<Threshold>().unwrap(), threshold);
+		assert_eq!(threshold.to_string(), "10,20,100");
+	}
+
+	#[test]
+	fn otsu_threshold_adapts_to_noise() {
+		let mut histogram = [0; 256];
+		histogram[2] = 900;
+		histogram[5] = 800;
+		histogram[120] = 30;
+		histogram[200] = 20;
+		let threshold = otsu_threshold(&histogram);
+		assert!((6..=120).contains(&threshold), "{threshold}");
+		assert_eq!(otsu_threshold(&[0; 256]), 0);
+
+		// Noise up to 40 stays below the adaptive threshold, while the moving
+		// square is found, even though a global threshold of 32 fails.
+		let noisy = |seed: u32, square: bool| {
+			let mut lcg = Lcg::with_seed(seed);
+			GrayImage::from_fn(64, 32, |x, y| {
+				let noise = (lcg.next_u16() % 41) as u8;
+				let square = square && (10..20).contains(&x) && (10..20).contains(&y);
+				image::Luma([if square { 200 } else { noise }])
+			})
+		};
+		let pool = FramePool::new();
+		let difference = |threshold: Threshold| {
+			Frame::Luma(noisy(1, false))
+				.difference(&Frame::Luma(noisy(2, true)), threshold, &pool)
+				.unwrap()
+		};
+		let mask = difference(Threshold::Otsu(Threshold::DEFAULT_OTSU_MINIMUM));
+		assert_eq!(mask.find_right_movement().map(|point| point.x), Some(19));
+		assert_eq!(mask.motion_fraction(0, 0, 64, 32), 100.0 / 2048.0);
+		assert_ne!(difference(Threshold::Global(32)).find_right_movement().map(|p| p.x), Some(19));
+
+		// Still scenes only have noise, which the minimum keeps out.
+		let mask = Frame::Luma(noisy(1, false))
+			.difference(&Frame::Luma(noisy(2, false)), Threshold::Otsu(41), &pool)
+			.unwrap();
+		assert_eq!(mask.find_right_movement(), None);
+		assert_eq!("otsu".parse::<Threshold>().unwrap(), Threshold::Otsu(16));
+		assert_eq!("otsu:8".parse::<Threshold>().unwrap(), Threshold::Otsu(8));
+	}
+
+	#[test]
+	fn cleaned_mask() {
+		// Speckle of noise in the first row and a moving object in the others.
+		let pool = FramePool::new();
+		let previous = GrayImage::from_fn(16, 8, |x, y| {
+			let moving = (x, y) == (14, 0) || ((2..8).contains(&x) && (2..8).contains(&y));
+			image::Luma([if moving { 0xFF } else { 0x00 }])
+		});
+		let mask = Frame::Luma(previous)
+			.difference(&Frame::Luma(GrayImage::new(16, 8)), Threshold::default(), &pool)
+			.unwrap();
+		assert_eq!(mask.find_right_movement(), Some(Point::new(14, 0)));
+
+		let opening = Morphology {
+			operation: morphology::Operation::Open,
+			element: morphology::Element::Square,
+			radius: 1,
+		};
+		let mask = mask.clean(&opening, &pool);
+		assert_eq!(mask.find_right_movement().map(|point| point.x), Some(7));
+		assert_eq!(mask.motion_rows(), [false, false, true, true, true, true, true, true]);
+	}
+
+	#[test]
+	fn motion_in_region() {
+		let image = RgbImage::from_fn(10, 10, |x, _y| {
+			*Pixel::from_slice(&[if x < 5 { 0xFF } else { 0x00 }, 0x00, 0x00])
+		});
+		assert_eq!(motion_fraction(&image, 0, 0, 5, 10), 1.0);
+		assert_eq!(motion_fraction(&image, 5, 5, 10, 10), 0.0);
+		assert_eq!(motion_fraction(&image, 3, 0, 4, 2), 0.5);
+		assert_eq!(motion_fraction(&image, 20, 20, 5, 5), 0.0);
+	}
 }
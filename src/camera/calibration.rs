@@ -0,0 +1,258 @@
+//! Calibration of the analysis to the noise of the camera, measured on the
+//! processed frames while the scene is still.
+
+use std::{
+	fmt,
+	time::{Duration, Instant},
+};
+
+use image::{ImageBuffer, Pixel};
+use rayon::prelude::*;
+
+use super::analysis::{self, Frame, Threshold};
+
+/// Time the scene has to be still for the calibration.
+pub const CALIBRATION_TIME: Duration = Duration::from_secs(2);
+/// Minimum number of frames to measure the noise on.
+const MIN_FRAMES: u32 = 10;
+/// Percentile of the pixels' noise the calibration covers, so that a few
+/// flickering pixels do not raise the threshold for all others.
+const NOISE_PERCENTILE: usize = 95;
+/// Standard deviation of the noise of processed frames, which the blur aims
+/// for. More blur also blurs away small movements.
+const TARGET_NOISE: f32 = 1.5;
+/// Blur strengths the calibration picks from, in steps of a tenth.
+const BLUR_SIGMAS: (u8, u8) = (5, 30);
+/// Thresholds are this many standard deviations of the noise of differences,
+/// which is larger than the frames' noise by the square root of 2.
+const THRESHOLD_DEVIATIONS: f32 = 3.0;
+
+/// Settings derived from the noise of the camera.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CalibrationResult {
+	/// Standard deviation of the noise of the processed frames per channel, as
+	/// measured with the previous blur strength.
+	pub noise: [f32; 3],
+	/// Standard deviation of the Gaussian blur of processed frames.
+	pub blur_sigma: f32,
+	/// Threshold of the differences between frames.
+	pub threshold: Threshold,
+}
+
+impl fmt::Display for CalibrationResult {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let noise = self.noise.iter().copied().fold(0.0, f32::max);
+		write!(f, "noise {noise:.1}, blur {:.1}, threshold {}", self.blur_sigma, self.threshold)
+	}
+}
+
+/// Progress of a calibration, as reported to the UI.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CalibrationStatus {
+	/// Measuring the noise, the scene has to be still.
+	Measuring,
+	/// Calibrated with the result, which the pipeline uses from now on.
+	Done(CalibrationResult),
+	/// The frames changed size or color mode, or there were too few.
+	Failed,
+}
+
+/// Running measurement of the temporal noise of each sample of the processed
+/// frames, by Welford's algorithm.
+#[derive(Debug)]
+pub struct NoiseCalibration {
+	started: Instant,
+	/// Blur strength the frames are processed with.
+	blur_sigma: f32,
+	/// Width, height and number of channels of the frames.
+	layout: Option<(u32, u32, u8)>,
+	frames: u32,
+	/// Mean of each sample.
+	means: Vec<f32>,
+	/// Sum of squared deviations from the mean of each sample.
+	deviations: Vec<f32>,
+	/// Whether the frames changed size or color mode.
+	failed: bool,
+}
+
+impl NoiseCalibration {
+	/// Start measuring frames processed with the given blur strength.
+	pub fn new(blur_sigma: f32, started: Instant) -> Self {
+		Self {
+			started,
+			blur_sigma,
+			layout: None,
+			frames: 0,
+			means: Vec::new(),
+			deviations: Vec::new(),
+			failed: false,
+		}
+	}
+
+	/// Add the processed frame to the measurement.
+	pub fn add(&mut self, frame: &Frame) {
+		match frame {
+			Frame::Rgb(image) => self.add_image(image),
+			Frame::Luma(image) => self.add_image(image),
+		}
+	}
+
+	/// Add the processed image to the measurement.
+	fn add_image<P>(&mut self, image: &ImageBuffer<P, Vec<u8>>)
+	where
+		P: Pixel<Subpixel = u8> + 'static,
+	{
+		let layout = (image.width(), image.height(), P::CHANNEL_COUNT);
+		if *self.layout.get_or_insert(layout) != layout {
+			self.failed = true;
+			return;
+		}
+		if self.means.is_empty() {
+			self.means = vec![0.0; image.len()];
+			self.deviations = vec![0.0; image.len()];
+		}
+
+		self.frames += 1;
+		let count = self.frames as f32;
+		let chunk = image.width() as usize * usize::from(P::CHANNEL_COUNT);
+		self.means
+			.par_chunks_mut(chunk)
+			.zip(self.deviations.par_chunks_mut(chunk))
+			.zip(image.par_chunks(chunk))
+			.for_each(|((means, deviations), row)| {
+				for ((mean, deviation), value) in means.iter_mut().zip(deviations).zip(row) {
+					let value = f32::from(*value);
+					let delta = value - *mean;
+					*mean += delta / count;
+					*deviation += delta * (value - *mean);
+				}
+			});
+	}
+
+	/// Whether the scene was measured long enough.
+	pub fn is_done(&self, now: Instant) -> bool {
+		self.failed || (now - self.started >= CALIBRATION_TIME && self.frames >= MIN_FRAMES)
+	}
+
+	/// Derive the settings from the measured noise. `None` if the frames
+	/// changed size or color mode.
+	pub fn result(&self) -> Option<CalibrationResult> {
+		let (_, _, channels) = self.layout?;
+		if self.failed || self.frames < 2 {
+			return None;
+		}
+		let channels = usize::from(channels);
+
+		let mut noise = [0.0; 3];
+		for (channel, noise) in noise.iter_mut().enumerate().take(channels) {
+			let mut deviations: Vec<f32> = self
+				.deviations
+				.iter()
+				.skip(channel)
+				.step_by(channels)
+				.map(|deviation| (deviation / (self.frames - 1) as f32).sqrt())
+				.collect();
+			let index = (deviations.len() * NOISE_PERCENTILE / 100).min(deviations.len() - 1);
+			*noise = *deviations.select_nth_unstable_by(index, f32::total_cmp).1;
+		}
+		let max_noise = noise.iter().copied().fold(0.0, f32::max);
+
+		// Use the least blur reaching the target noise, to keep small movements.
+		let gain = analysis::blur_noise_gain(self.blur_sigma);
+		let predicted = |sigma: f32| max_noise * analysis::blur_noise_gain(sigma) / gain;
+		let blur_sigma = (BLUR_SIGMAS.0..=BLUR_SIGMAS.1)
+			.map(|tenths| f32::from(tenths) / 10.0)
+			.find(|sigma| predicted(*sigma) <= TARGET_NOISE)
+			.unwrap_or(f32::from(BLUR_SIGMAS.1) / 10.0);
+
+		let threshold = |noise: f32| {
+			let noise = noise * analysis::blur_noise_gain(blur_sigma) / gain;
+			(THRESHOLD_DEVIATIONS * std::f32::consts::SQRT_2 * noise).ceil().clamp(1.0, 255.0) as u8
+		};
+		let threshold = if channels == 1 {
+			Threshold::Global(threshold(noise[0]))
+		} else {
+			Threshold::PerChannel(noise.map(threshold))
+		};
+		Some(CalibrationResult { noise, blur_sigma, threshold })
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use image::{GrayImage, Luma, RgbImage};
+
+	use super::*;
+	use crate::camera::testing::Lcg;
+
+	/// Frames of a still scene with noise of the given amplitude.
+	fn noisy_frames(amplitude: u8) -> impl Iterator<Item = GrayImage> {
+		let mut lcg = Lcg::new();
+		(0..20).map(move |_| {
+			GrayImage::from_fn(96, 64, |x, _y| {
+				let noise = (lcg.next_u16() % (2 * u16::from(amplitude) + 1)) as u8;
+				Luma([(x as u8 + 40 + noise).saturating_sub(amplitude)])
+			})
+		})
+	}
+
+	/// Calibrate on the frames, processed with the default blur.
+	fn calibrate(frames: impl Iterator<Item = GrayImage>) -> CalibrationResult {
+		let start = Instant::now();
+		let mut calibration = NoiseCalibration::new(analysis::DEFAULT_BLUR_SIGMA, start);
+		for frame in frames {
+			calibration.add(&Frame::Luma(analysis::process_frame(&frame)));
+		}
+		assert!(!calibration.is_done(start));
+		assert!(calibration.is_done(start + CALIBRATION_TIME));
+		calibration.result().unwrap()
+	}
+
+	#[test]
+	fn noisier_cameras_get_more_blur_and_higher_thresholds() {
+		let clean = calibrate(noisy_frames(1));
+		let noisy = calibrate(noisy_frames(30));
+		assert!(clean.noise[0] < noisy.noise[0], "{clean} / {noisy}");
+		assert!(clean.blur_sigma < noisy.blur_sigma, "{clean} / {noisy}");
+		let (Threshold::Global(clean_threshold), Threshold::Global(noisy_threshold)) =
+			(clean.threshold, noisy.threshold)
+		else {
+			panic!("Luma frames get global thresholds: {clean} / {noisy}");
+		};
+		assert!(clean_threshold < noisy_threshold, "{clean} / {noisy}");
+
+		// With the calibrated settings, hardly any noise of a still scene is
+		// taken as motion.
+		for amplitude in [1, 30] {
+			let result = calibrate(noisy_frames(amplitude));
+			let mut frames = noisy_frames(amplitude).map(|frame| {
+				let mut processed = frame.clone();
+				let mut scratch = frame.clone();
+				analysis::process_frame_into(
+					&frame,
+					result.blur_sigma,
+					&mut processed,
+					&mut scratch,
+				);
+				processed
+			});
+			let mut previous = frames.next().unwrap();
+			for current in frames {
+				analysis::frame_difference(&mut previous, &current, result.threshold);
+				let moving = analysis::motion_fraction(&previous, 0, 0, 96, 64);
+				assert!(moving < 0.01, "{moving} with {result}");
+				previous = current;
+			}
+		}
+	}
+
+	#[test]
+	fn changed_frames_fail() {
+		let start = Instant::now();
+		let mut calibration = NoiseCalibration::new(analysis::DEFAULT_BLUR_SIGMA, start);
+		calibration.add(&Frame::Luma(GrayImage::new(8, 8)));
+		calibration.add(&Frame::Rgb(RgbImage::new(8, 8)));
+		assert!(calibration.is_done(start));
+		assert_eq!(calibration.result(), None);
+	}
+}
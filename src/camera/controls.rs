@@ -0,0 +1,152 @@
+//! Camera controls like exposure, white balance and focus.
+
+use std::fmt;
+
+use druid::Data;
+use nokhwa::{CameraControl, KnownCameraControlFlag, KnownCameraControls};
+use serde::{Deserialize, Serialize};
+
+/// Selector name for the controls of the opened camera.
+pub const S_CAMERA_CONTROLS: &str = "CameraControls";
+
+/// Kind of camera control.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Data, Serialize, Deserialize)]
+pub enum ControlKind {
+	/// Brightness.
+	Brightness,
+	/// Contrast.
+	Contrast,
+	/// Hue.
+	Hue,
+	/// Saturation.
+	Saturation,
+	/// Sharpness.
+	Sharpness,
+	/// Gamma.
+	Gamma,
+	/// White balance.
+	WhiteBalance,
+	/// Backlight compensation.
+	BacklightComp,
+	/// Gain.
+	Gain,
+	/// Pan.
+	Pan,
+	/// Tilt.
+	Tilt,
+	/// Roll.
+	Roll,
+	/// Zoom.
+	Zoom,
+	/// Exposure.
+	Exposure,
+	/// Iris.
+	Iris,
+	/// Focus.
+	Focus,
+}
+
+impl ControlKind {
+	/// Whether the automatic adjustment of this control causes whole-frame
+	/// differences, so it should be possible to lock it, even if the camera
+	/// currently reports it as manual, e.g. as it was locked by a profile.
+	pub fn lockable(self) -> bool {
+		matches!(self, Self::Exposure | Self::WhiteBalance | Self::Focus)
+	}
+}
+
+impl fmt::Display for ControlKind {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::WhiteBalance => f.write_str("White balance"),
+			Self::BacklightComp => f.write_str("Backlight compensation"),
+			kind => fmt::Debug::fmt(kind, f),
+		}
+	}
+}
+
+impl From<KnownCameraControls> for ControlKind {
+	fn from(control: KnownCameraControls) -> Self {
+		match control {
+			KnownCameraControls::Brightness => Self::Brightness,
+			KnownCameraControls::Contrast => Self::Contrast,
+			KnownCameraControls::Hue => Self::Hue,
+			KnownCameraControls::Saturation => Self::Saturation,
+			KnownCameraControls::Sharpness => Self::Sharpness,
+			KnownCameraControls::Gamma => Self::Gamma,
+			KnownCameraControls::WhiteBalance => Self::WhiteBalance,
+			KnownCameraControls::BacklightComp => Self::BacklightComp,
+			KnownCameraControls::Gain => Self::Gain,
+			KnownCameraControls::Pan => Self::Pan,
+			KnownCameraControls::Tilt => Self::Tilt,
+			KnownCameraControls::Roll => Self::Roll,
+			KnownCameraControls::Zoom => Self::Zoom,
+			KnownCameraControls::Exposure => Self::Exposure,
+			KnownCameraControls::Iris => Self::Iris,
+			KnownCameraControls::Focus => Self::Focus,
+		}
+	}
+}
+
+impl From<ControlKind> for KnownCameraControls {
+	fn from(kind: ControlKind) -> Self {
+		match kind {
+			ControlKind::Brightness => Self::Brightness,
+			ControlKind::Contrast => Self::Contrast,
+			ControlKind::Hue => Self::Hue,
+			ControlKind::Saturation => Self::Saturation,
+			ControlKind::Sharpness => Self::Sharpness,
+			ControlKind::Gamma => Self::Gamma,
+			ControlKind::WhiteBalance => Self::WhiteBalance,
+			ControlKind::BacklightComp => Self::BacklightComp,
+			ControlKind::Gain => Self::Gain,
+			ControlKind::Pan => Self::Pan,
+			ControlKind::Tilt => Self::Tilt,
+			ControlKind::Roll => Self::Roll,
+			ControlKind::Zoom => Self::Zoom,
+			ControlKind::Exposure => Self::Exposure,
+			ControlKind::Iris => Self::Iris,
+			ControlKind::Focus => Self::Focus,
+		}
+	}
+}
+
+/// Value of a camera control to set, as saved in the profile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ControlSetting {
+	/// Control to set.
+	pub kind: ControlKind,
+	/// Value, only relevant if not automatic.
+	pub value: i32,
+	/// Whether the camera adjusts the control automatically.
+	pub automatic: bool,
+}
+
+/// Control supported by a camera with its range and current setting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ControlInfo {
+	/// Minimum value.
+	pub minimum: i32,
+	/// Maximum value.
+	pub maximum: i32,
+	/// Step between values.
+	pub step: i32,
+	/// Whether the control can be adjusted automatically by the camera.
+	pub automatic_supported: bool,
+	/// Current setting.
+	pub setting: ControlSetting,
+}
+
+impl From<&CameraControl> for ControlInfo {
+	fn from(control: &CameraControl) -> Self {
+		let kind = ControlKind::from(control.control());
+		let automatic = control.flag() == KnownCameraControlFlag::Automatic;
+		Self {
+			minimum: control.minimum_value(),
+			maximum: control.maximum_value(),
+			step: control.step(),
+			automatic_supported: automatic || kind.lockable(),
+			setting: ControlSetting { kind, value: control.value(), automatic },
+		}
+	}
+}
@@ -0,0 +1,228 @@
+//! Monitoring of the system cameras, to notice cameras being plugged in or
+//! out.
+
+use std::{
+	sync::mpsc,
+	thread,
+	time::{Duration, Instant},
+};
+
+use color_eyre::Result;
+use druid::{Data, ExtEventSink, Selector, Target};
+use nokhwa::{query_devices, CameraInfo, CaptureAPIBackend};
+
+use super::source::Source;
+
+/// Selector name for the list of system cameras.
+pub const S_CAMERA_DEVICES: &str = "CameraDevices";
+
+/// Interval between scanning for cameras.
+const SCAN_INTERVAL: Duration = Duration::from_secs(2);
+
+/// System camera.
+#[derive(Debug, Clone, PartialEq, Eq, Data)]
+pub struct CameraDevice {
+	/// Index to open the camera with, which can change when cameras are
+	/// plugged in or out.
+	pub index: usize,
+	/// Human readable name.
+	pub name: String,
+	/// Backend specific identification, e.g. the device path.
+	pub path: String,
+}
+
+impl From<&CameraInfo> for CameraDevice {
+	fn from(info: &CameraInfo) -> Self {
+		Self { index: info.index(), name: info.human_name(), path: info.misc() }
+	}
+}
+
+/// System cameras and the one currently opened.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CameraDevices {
+	/// Cameras currently available.
+	pub available: Vec<CameraDevice>,
+	/// Index of the opened camera, if a system camera is open.
+	pub current: Option<usize>,
+}
+
+/// Monitor for the system cameras. Remembers the preferred camera, so it can
+/// be found again when it is plugged back in with another index.
+#[derive(Debug, Default)]
+pub struct DeviceMonitor {
+	/// Cameras found by the last scan and the opened one.
+	devices: CameraDevices,
+	/// Camera last opened successfully.
+	preferred: Option<CameraDevice>,
+	/// Time of the last scan.
+	last_scan: Option<Instant>,
+	/// Result of the scan running on its own thread.
+	background_scan: Option<mpsc::Receiver<Result<Vec<CameraDevice>>>>,
+}
+
+impl DeviceMonitor {
+	/// Create new device monitor, which has not scanned yet.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Scan for cameras and report changes to the UI.
+	pub fn scan(&mut self, event_sender: &ExtEventSink) -> Result<()> {
+		self.last_scan = Some(Instant::now());
+		// A scan still running in the background would be outdated.
+		self.background_scan = None;
+		let available = query()?;
+		self.update(available, event_sender)
+	}
+
+	/// Scan for cameras if the last scan is old enough. Scanning errors are
+	/// only printed, as the current source might still work.
+	pub fn scan_if_due(&mut self, event_sender: &ExtEventSink) {
+		if self.until_next_scan().is_zero() {
+			if let Err(err) = self.scan(event_sender) {
+				eprintln!("Error listing cameras: {err}");
+			}
+		}
+	}
+
+	/// Scan for cameras now, e.g. when the user asked for it.
+	pub fn rescan(&mut self, event_sender: &ExtEventSink) {
+		self.last_scan = None;
+		self.scan_if_due(event_sender);
+	}
+
+	/// Like [`Self::scan_if_due`], but scan on another thread, so that the
+	/// frames of the open camera are not delayed. The cameras found are
+	/// reported by a later call.
+	pub fn scan_in_background_if_due(&mut self, event_sender: &ExtEventSink) {
+		if let Some(background_scan) = &self.background_scan {
+			let received = background_scan.try_recv();
+			if matches!(received, Err(mpsc::TryRecvError::Empty)) {
+				return;
+			}
+			self.background_scan = None;
+			// The scanning thread only hangs up without result, if it panicked.
+			if let Ok(result) = received {
+				let result = result.and_then(|available| self.update(available, event_sender));
+				if let Err(err) = result {
+					eprintln!("Error listing cameras: {err}");
+				}
+			}
+		}
+		if self.until_next_scan().is_zero() {
+			self.last_scan = Some(Instant::now());
+			let (sender, receiver) = mpsc::channel();
+			thread::spawn(move || sender.send(query()));
+			self.background_scan = Some(receiver);
+		}
+	}
+
+	/// Scan for cameras on another thread as soon as possible, e.g. when the
+	/// user asked for it while a camera is open.
+	pub fn rescan_in_background(&mut self, event_sender: &ExtEventSink) {
+		self.last_scan = None;
+		self.scan_in_background_if_due(event_sender);
+	}
+
+	/// Prefer the camera with the given index, e.g. when the user picked it.
+	pub fn prefer(&mut self, index: usize) {
+		self.preferred =
+			self.devices.available.iter().find(|device| device.index == index).cloned();
+	}
+
+	/// Remember the source as opened successfully. System cameras become the
+	/// preferred camera.
+	pub fn opened(&mut self, source: &Source, event_sender: &ExtEventSink) -> Result<()> {
+		self.devices.current = match source {
+			Source::Camera(index) => {
+				let device = self.devices.available.iter().find(|device| device.index == *index);
+				if let Some(device) = device {
+					self.preferred = Some(device.clone());
+				}
+				Some(*index)
+			}
+			Source::Mjpeg(_) | Source::Replay(_) | Source::Synthetic => None,
+		};
+		self.report(event_sender)
+	}
+
+	/// Wait time until the next scan is due.
+	pub fn until_next_scan(&self) -> Duration {
+		self.last_scan.map_or(Duration::ZERO, |time| SCAN_INTERVAL.saturating_sub(time.elapsed()))
+	}
+
+	/// Source to reconnect to after the given source failed, if it is
+	/// available. The preferred camera is found by path or name, as its index
+	/// might have changed.
+	pub fn reconnect_source(&self, source: &Source) -> Option<Source> {
+		match source {
+			Source::Camera(index) => {
+				let available = &self.devices.available;
+				let device = match &self.preferred {
+					Some(preferred) => available
+						.iter()
+						.find(|device| device.path == preferred.path)
+						.or_else(|| available.iter().find(|device| device.name == preferred.name)),
+					None => available.iter().find(|device| device.index == *index),
+				};
+				device.map(|device| Source::Camera(device.index))
+			}
+			// Other sources can only be found by trying.
+			Source::Mjpeg(_) | Source::Replay(_) | Source::Synthetic => Some(source.clone()),
+		}
+	}
+
+	/// Remember the available cameras and report changes to the UI.
+	fn update(&mut self, available: Vec<CameraDevice>, event_sender: &ExtEventSink) -> Result<()> {
+		if available != self.devices.available {
+			self.devices.available = available;
+			self.report(event_sender)?;
+		}
+		Ok(())
+	}
+
+	/// Send the cameras to the UI.
+	fn report(&self, event_sender: &ExtEventSink) -> Result<()> {
+		event_sender.submit_command(
+			Selector::<CameraDevices>::new(S_CAMERA_DEVICES),
+			self.devices.clone(),
+			Target::Auto,
+		)?;
+		Ok(())
+	}
+}
+
+/// List the system cameras, sorted by index.
+fn query() -> Result<Vec<CameraDevice>> {
+	let mut available: Vec<CameraDevice> =
+		query_devices(CaptureAPIBackend::Auto)?.iter().map(CameraDevice::from).collect();
+	available.sort_by_key(|device| device.index);
+	Ok(available)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn device(index: usize, name: &str, path: &str) -> CameraDevice {
+		CameraDevice { index, name: name.into(), path: path.into() }
+	}
+
+	#[test]
+	fn reconnect_to_preferred() {
+		let mut monitor = DeviceMonitor::new();
+		monitor.preferred = Some(device(0, "Webcam", "/dev/video0"));
+
+		// Gone.
+		monitor.devices.available = vec![device(0, "Laptop camera", "/dev/video1")];
+		assert_eq!(monitor.reconnect_source(&Source::Camera(0)), None);
+
+		// Back with another index and path.
+		monitor.devices.available.push(device(1, "Webcam", "/dev/video2"));
+		assert_eq!(monitor.reconnect_source(&Source::Camera(0)), Some(Source::Camera(1)));
+
+		// Same path is preferred over the same name.
+		monitor.devices.available.push(device(2, "Webcam", "/dev/video0"));
+		assert_eq!(monitor.reconnect_source(&Source::Camera(0)), Some(Source::Camera(2)));
+	}
+}
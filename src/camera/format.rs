@@ -0,0 +1,143 @@
+//! Camera formats, i.e. resolution, frame rate and pixel format.
+
+use std::fmt;
+
+use druid::{Data, Lens};
+use nokhwa::{CameraFormat, FrameFormat};
+use serde::{Deserialize, Serialize};
+
+/// Selector name for the formats of the opened camera.
+pub const S_CAMERA_FORMATS: &str = "CameraFormats";
+
+/// Pixel format of the camera stream.
+#[derive(
+	Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Data, Serialize, Deserialize,
+)]
+pub enum PixelFormat {
+	/// Motion JPEG, compressed.
+	#[default]
+	Mjpeg,
+	/// YUYV 4:2:2, uncompressed.
+	Yuyv,
+}
+
+impl fmt::Display for PixelFormat {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Mjpeg => f.write_str("MJPEG"),
+			Self::Yuyv => f.write_str("YUYV"),
+		}
+	}
+}
+
+impl From<FrameFormat> for PixelFormat {
+	fn from(format: FrameFormat) -> Self {
+		match format {
+			FrameFormat::MJPEG => Self::Mjpeg,
+			FrameFormat::YUYV => Self::Yuyv,
+		}
+	}
+}
+
+impl From<PixelFormat> for FrameFormat {
+	fn from(format: PixelFormat) -> Self {
+		match format {
+			PixelFormat::Mjpeg => Self::MJPEG,
+			PixelFormat::Yuyv => Self::YUYV,
+		}
+	}
+}
+
+/// Format of the camera stream.
+#[derive(
+	Debug,
+	Clone,
+	Copy,
+	Default,
+	PartialEq,
+	Eq,
+	PartialOrd,
+	Ord,
+	Hash,
+	Data,
+	Lens,
+	Serialize,
+	Deserialize,
+)]
+pub struct FormatChoice {
+	/// Pixel format.
+	pub pixel_format: PixelFormat,
+	/// Width and height.
+	pub resolution: (u32, u32),
+	/// Frames per second.
+	pub frame_rate: u32,
+}
+
+impl FormatChoice {
+	/// Find the available format closest to this one. The pixel format is
+	/// most important, then the resolution, then the frame rate.
+	pub fn closest_in(&self, available: &[FormatChoice]) -> Option<FormatChoice> {
+		let pixels = |format: &FormatChoice| format.resolution.0 * format.resolution.1;
+		available
+			.iter()
+			.min_by_key(|format| {
+				(
+					format.pixel_format != self.pixel_format,
+					pixels(format).abs_diff(pixels(self)),
+					format.frame_rate.abs_diff(self.frame_rate),
+				)
+			})
+			.copied()
+	}
+}
+
+impl From<CameraFormat> for FormatChoice {
+	fn from(format: CameraFormat) -> Self {
+		Self {
+			pixel_format: format.format().into(),
+			resolution: (format.width(), format.height()),
+			frame_rate: format.frame_rate(),
+		}
+	}
+}
+
+impl From<FormatChoice> for CameraFormat {
+	fn from(format: FormatChoice) -> Self {
+		let (width, height) = format.resolution;
+		CameraFormat::new_from(width, height, format.pixel_format.into(), format.frame_rate)
+	}
+}
+
+/// Formats supported by a camera and its current format.
+#[derive(Debug, Clone, Default)]
+pub struct CameraFormats {
+	/// All supported formats, sorted.
+	pub available: Vec<FormatChoice>,
+	/// Format currently used.
+	pub current: Option<FormatChoice>,
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn closest_format() {
+		let format = |pixel_format, resolution, frame_rate| FormatChoice {
+			pixel_format,
+			resolution,
+			frame_rate,
+		};
+		let available = [
+			format(PixelFormat::Mjpeg, (640, 480), 30),
+			format(PixelFormat::Mjpeg, (1280, 720), 30),
+			format(PixelFormat::Mjpeg, (1280, 720), 15),
+			format(PixelFormat::Yuyv, (640, 480), 30),
+		];
+		let wanted = format(PixelFormat::Mjpeg, (1280, 720), 20);
+		assert_eq!(wanted.closest_in(&available), Some(available[2]));
+		let wanted = format(PixelFormat::Yuyv, (1280, 720), 30);
+		assert_eq!(wanted.closest_in(&available), Some(available[3]));
+		assert_eq!(wanted.closest_in(&[]), None);
+	}
+}
@@ -0,0 +1,81 @@
+//! Gesture detection on the stream of detected points.
+
+use std::time::{Duration, Instant};
+
+use crate::output::Gesture;
+
+/// Time the point has to rest to trigger a dwell gesture.
+const DWELL_TIME: Duration = Duration::from_millis(1000);
+/// Radius the point may move while resting, as fraction of the frame width.
+const DWELL_RADIUS: f32 = 0.03;
+
+/// Detector for dwell gestures, i.e. the point resting at one position for
+/// some time after it moved there.
+#[derive(Debug, Default)]
+pub struct DwellDetector {
+	/// Position the point rests at and since when.
+	anchor: Option<((u32, u32), Instant)>,
+	/// Whether the dwell gesture was already emitted for the current anchor.
+	fired: bool,
+}
+
+impl DwellDetector {
+	/// Create new dwell detector.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Update the detector with the detected point of the current frame, if
+	/// there was one. Returns the gesture once the point rested long enough.
+	pub fn update(
+		&mut self,
+		point: Option<(u32, u32)>,
+		frame_width: u32,
+		now: Instant,
+	) -> Option<Gesture> {
+		let radius = DWELL_RADIUS * frame_width as f32;
+		if let Some(point) = point {
+			let moved = match self.anchor {
+				Some((anchor, _)) => {
+					let dx = point.0 as f32 - anchor.0 as f32;
+					let dy = point.1 as f32 - anchor.1 as f32;
+					dx.hypot(dy) > radius
+				}
+				None => true,
+			};
+			if moved {
+				self.anchor = Some((point, now));
+				self.fired = false;
+				return None;
+			}
+		}
+
+		match self.anchor {
+			Some((_, since)) if !self.fired && now.duration_since(since) >= DWELL_TIME => {
+				self.fired = true;
+				Some(Gesture::Dwell)
+			}
+			_ => None,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn dwell_after_resting() {
+		let mut detector = DwellDetector::new();
+		let start = Instant::now();
+		let at = |millis| start + Duration::from_millis(millis);
+
+		assert_eq!(detector.update(None, 100, at(0)), None);
+		assert_eq!(detector.update(Some((10, 10)), 100, at(0)), None);
+		assert_eq!(detector.update(Some((11, 10)), 100, at(500)), None);
+		assert_eq!(detector.update(None, 100, at(1000)), Some(Gesture::Dwell));
+		assert_eq!(detector.update(None, 100, at(1500)), None);
+		assert_eq!(detector.update(Some((50, 10)), 100, at(1600)), None);
+		assert_eq!(detector.update(Some((50, 11)), 100, at(2600)), Some(Gesture::Dwell));
+	}
+}
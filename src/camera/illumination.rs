@@ -0,0 +1,172 @@
+//! Compensation of changes of the frame's brightness, like lights switching on
+//! or flickering with the mains frequency, which would otherwise make every
+//! pixel appear to move. Cameras with rolling shutters read the rows one after
+//! the other, so flicker changes the brightness in horizontal bands.
+
+use image::{ImageBuffer, Pixel};
+use rayon::prelude::*;
+
+use super::analysis::Frame;
+
+/// Number of possible changes of a sample, from -255 to 255.
+const CHANGES: usize = 511;
+/// Number of rows sharing one brightness shift. Bands of flicker are much
+/// higher, so neighbouring rows change about the same.
+const BAND_HEIGHT: usize = 8;
+
+/// Shift the brightness of the previous frame to the one of the current frame
+/// in each band of rows, so that only local changes are left for the
+/// difference. Returns the shift of each channel per band, which is empty for
+/// frames of different color mode or size.
+pub fn compensate(previous: &mut Frame, current: &Frame) -> Vec<[i16; 3]> {
+	match (previous, current) {
+		(Frame::Rgb(previous), Frame::Rgb(current))
+			if previous.dimensions() == current.dimensions() =>
+		{
+			compensate_image(previous, current)
+		}
+		(Frame::Luma(previous), Frame::Luma(current))
+			if previous.dimensions() == current.dimensions() =>
+		{
+			compensate_image(previous, current)
+		}
+		_ => Vec::new(),
+	}
+}
+
+/// Image version of [`compensate`].
+fn compensate_image<P>(
+	previous: &mut ImageBuffer<P, Vec<u8>>,
+	current: &ImageBuffer<P, Vec<u8>>,
+) -> Vec<[i16; 3]>
+where
+	P: Pixel<Subpixel = u8> + 'static,
+{
+	let channels = usize::from(P::CHANNEL_COUNT);
+	let band = previous.as_flat_samples().layout.height_stride * BAND_HEIGHT;
+	previous
+		.par_chunks_mut(band)
+		.zip(current.par_chunks(band))
+		.map(|(previous, current)| {
+			let shift = brightness_shift(previous, current, channels);
+			if shift != [0; 3] {
+				for pix in previous.chunks_exact_mut(channels) {
+					for (value, shift) in pix.iter_mut().zip(shift) {
+						*value = (i16::from(*value) + shift).clamp(0, 255) as u8;
+					}
+				}
+			}
+			shift
+		})
+		.collect()
+}
+
+/// Median change of each channel from the previous to the current samples,
+/// which interleave the given number of channels. As long as less than half of
+/// the samples move, this is the change of the brightness and the moving
+/// objects do not affect it.
+pub fn brightness_shift(previous: &[u8], current: &[u8], channels: usize) -> [i16; 3] {
+	let mut histograms = vec![[0_u32; CHANGES]; channels];
+	for (index, (prev, cur)) in previous.iter().zip(current).enumerate() {
+		let change = usize::from(*cur) + 255 - usize::from(*prev);
+		histograms[index % channels][change] += 1;
+	}
+
+	let mut shift = [0; 3];
+	for (shift, histogram) in shift.iter_mut().zip(histograms) {
+		let total: u32 = histogram.iter().sum();
+		let mut count = 0;
+		if let Some(median) = histogram.iter().position(|bin| {
+			count += bin;
+			count * 2 >= total
+		}) {
+			*shift = median as i16 - 255;
+		}
+	}
+	shift
+}
+
+#[cfg(test)]
+mod tests {
+	use image::{GrayImage, Luma, Rgb, RgbImage};
+
+	use super::*;
+	use crate::camera::{analysis::Threshold, pool::FramePool};
+
+	/// Scene with a dark object at the given position on a gradient, with the
+	/// brightness of each row shifted by the given amount.
+	fn scene(object_x: u32, brightness: impl Fn(u32) -> i16) -> RgbImage {
+		RgbImage::from_fn(64, 48, |x, y| {
+			let value = |base: u32| (base as i16 + brightness(y)).clamp(0, 255) as u8;
+			if (object_x..object_x + 10).contains(&x) && (20..30).contains(&y) {
+				return Rgb([value(20); 3]);
+			}
+			Rgb([value(100 + x), value(80 + y), value(120)])
+		})
+	}
+
+	#[test]
+	fn lights_switching_on_are_no_motion() {
+		let pool = FramePool::new();
+		let threshold = Threshold::Global(16);
+
+		// Without compensation, the whole frame changes.
+		let previous = Frame::Rgb(scene(10, |_| 0));
+		let current = Frame::Rgb(scene(10, |_| 40));
+		let mask = previous.clone().difference(&current, threshold, &pool).unwrap();
+		assert!(mask.motion_fraction(0, 0, 64, 48) > 0.9);
+
+		let mut compensated = previous;
+		assert_eq!(compensate(&mut compensated, &current), vec![[40; 3]; 6]);
+		let mask = compensated.difference(&current, threshold, &pool).unwrap();
+		assert_eq!(mask.motion_fraction(0, 0, 64, 48), 0.0);
+
+		// The moving object is still found, while the lights dim.
+		let mut previous = Frame::Rgb(scene(10, |_| 0));
+		let current = Frame::Rgb(scene(30, |_| -30));
+		assert_eq!(compensate(&mut previous, &current), vec![[-30; 3]; 6]);
+		let mask = previous.difference(&current, threshold, &pool).unwrap();
+		assert_eq!(mask.find_right_movement().map(|point| point.x), Some(39));
+		assert!(mask.motion_fraction(0, 0, 64, 48) < 0.1);
+	}
+
+	#[test]
+	fn flicker_bands_are_no_motion() {
+		let pool = FramePool::new();
+		let threshold = Threshold::Global(16);
+
+		// Flicker brightens and darkens bands of rows of a rolling shutter.
+		let flicker = |y: u32| if (16..32).contains(&y) { -20 } else { 30 };
+		let previous = Frame::Rgb(scene(10, |_| 0));
+		let current = Frame::Rgb(scene(30, flicker));
+		let mask = previous.clone().difference(&current, threshold, &pool).unwrap();
+		assert!(mask.motion_fraction(0, 0, 64, 48) > 0.9);
+
+		let mut compensated = previous;
+		let shifts = compensate(&mut compensated, &current);
+		assert_eq!(shifts, [[30; 3], [30; 3], [-20; 3], [-20; 3], [30; 3], [30; 3]]);
+		let mask = compensated.difference(&current, threshold, &pool).unwrap();
+		assert_eq!(mask.find_right_movement().map(|point| point.x), Some(39));
+		assert!(mask.motion_fraction(0, 0, 64, 48) < 0.1);
+	}
+
+	#[test]
+	fn moving_objects_do_not_shift_brightness() {
+		// The object covers less than half of the rows it is in.
+		let previous = GrayImage::from_fn(32, 32, |x, _y| Luma([x as u8 * 4]));
+		let mut current = previous.clone();
+		for y in 0..10 {
+			for x in 0..12 {
+				current.put_pixel(x, y, Luma([255]));
+			}
+		}
+		assert_eq!(brightness_shift(&previous, &current, 1), [0; 3]);
+
+		let mut previous = Frame::Luma(previous);
+		let unchanged = previous.clone();
+		assert_eq!(compensate(&mut previous, &Frame::Luma(current)), vec![[0; 3]; 4]);
+		assert_eq!(previous, unchanged);
+		let other = Frame::Luma(GrayImage::new(8, 8));
+		assert_eq!(compensate(&mut previous, &other), Vec::<[i16; 3]>::new());
+	}
+}
@@ -0,0 +1,266 @@
+//! Channel between the frame receiver and the frame processor, which drops
+//! frames according to a policy when the processor is too slow.
+
+use std::{
+	collections::VecDeque,
+	fmt,
+	str::FromStr,
+	sync::{Arc, Condvar, Mutex, MutexGuard},
+};
+
+use color_eyre::{
+	eyre::{bail, eyre},
+	Report, Result,
+};
+
+/// Number of frames buffered by [`FramePolicy::Block`].
+const BLOCK_CAPACITY: usize = 2;
+
+/// Policy for frames arriving faster than they are processed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FramePolicy {
+	/// Keep only the latest frame, so the processor always works on the most
+	/// recent image.
+	#[default]
+	Latest,
+	/// Keep up to the given number of frames, dropping the oldest ones. Queues
+	/// keep at least one frame.
+	Queue(usize),
+	/// Block the camera until the processor catches up, dropping nothing.
+	Block,
+}
+
+impl FromStr for FramePolicy {
+	type Err = Report;
+
+	/// Parse `latest`, `block`, `queue` or `queue:<length>`.
+	fn from_str(s: &str) -> Result<Self> {
+		match s.split_once(':') {
+			None if s == "latest" => Ok(Self::Latest),
+			None if s == "block" => Ok(Self::Block),
+			None if s == "queue" => Ok(Self::Queue(4)),
+			Some(("queue", length)) => match length.parse()? {
+				0 => bail!("Queue length must be at least 1"),
+				length => Ok(Self::Queue(length)),
+			},
+			_ => Err(eyre!("Unknown frame policy {s}, expected latest, block or queue:<length>")),
+		}
+	}
+}
+
+impl fmt::Display for FramePolicy {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Latest => f.write_str("latest"),
+			Self::Queue(length) => write!(f, "queue:{length}"),
+			Self::Block => f.write_str("block"),
+		}
+	}
+}
+
+/// Message in the mailbox. Only frames may be dropped.
+pub trait Droppable {
+	/// Whether the message may be dropped to make room for newer ones.
+	fn droppable(&self) -> bool;
+}
+
+/// State shared by both ends of the mailbox.
+#[derive(Debug)]
+struct Shared<T> {
+	state: Mutex<State<T>>,
+	/// Notified when a message is added, removed or an end is dropped.
+	changed: Condvar,
+}
+
+/// Messages and connection state.
+#[derive(Debug)]
+struct State<T> {
+	messages: VecDeque<T>,
+	/// Number of frames dropped since the last call to
+	/// [`MailboxReceiver::take_dropped`].
+	dropped: u64,
+	sender_alive: bool,
+	receiver_alive: bool,
+}
+
+impl<T> Shared<T> {
+	/// Lock the state. A poisoned lock is still used, as the state stays
+	/// consistent between operations.
+	fn lock(&self) -> MutexGuard<'_, State<T>> {
+		self.state.lock().unwrap_or_else(|err| err.into_inner())
+	}
+}
+
+/// Create a mailbox with the given policy.
+pub fn mailbox<T: Droppable>(policy: FramePolicy) -> (MailboxSender<T>, MailboxReceiver<T>) {
+	// Without room for a frame, sending would wait forever for one to drop.
+	let policy = match policy {
+		FramePolicy::Queue(length) => FramePolicy::Queue(length.max(1)),
+		policy => policy,
+	};
+	let shared = Arc::new(Shared {
+		state: Mutex::new(State {
+			messages: VecDeque::new(),
+			dropped: 0,
+			sender_alive: true,
+			receiver_alive: true,
+		}),
+		changed: Condvar::new(),
+	});
+	(MailboxSender { shared: shared.clone(), policy }, MailboxReceiver { shared })
+}
+
+/// Sending end of the mailbox.
+#[derive(Debug)]
+pub struct MailboxSender<T> {
+	shared: Arc<Shared<T>>,
+	policy: FramePolicy,
+}
+
+impl<T: Droppable> MailboxSender<T> {
+	/// Send the message, dropping or waiting according to the policy. Fails
+	/// if the receiver is gone.
+	pub fn send(&self, message: T) -> Result<()> {
+		self.send_recycling(message, drop)
+	}
+
+	/// Like [`Self::send`], but hand the dropped messages to `recycle`, e.g.
+	/// to return the buffers of frames to the pool.
+	pub fn send_recycling(&self, message: T, mut recycle: impl FnMut(T)) -> Result<()> {
+		let mut state = self.shared.lock();
+		let capacity = match self.policy {
+			FramePolicy::Latest => 1,
+			FramePolicy::Queue(length) => length,
+			FramePolicy::Block => {
+				while state.receiver_alive && state.messages.len() >= BLOCK_CAPACITY {
+					state = self.shared.changed.wait(state).unwrap_or_else(|err| err.into_inner());
+				}
+				usize::MAX
+			}
+		};
+		if !state.receiver_alive {
+			bail!("Frame processor stopped");
+		}
+
+		if message.droppable() {
+			// Make room by dropping the oldest frames, keeping other messages.
+			while state.messages.iter().filter(|message| message.droppable()).count() >= capacity {
+				let oldest = state.messages.iter().position(Droppable::droppable);
+				if let Some(dropped) = oldest.and_then(|index| state.messages.remove(index)) {
+					recycle(dropped);
+					state.dropped += 1;
+				}
+			}
+		}
+		state.messages.push_back(message);
+		self.shared.changed.notify_all();
+		Ok(())
+	}
+}
+
+impl<T> Drop for MailboxSender<T> {
+	fn drop(&mut self) {
+		self.shared.lock().sender_alive = false;
+		self.shared.changed.notify_all();
+	}
+}
+
+/// Receiving end of the mailbox.
+#[derive(Debug)]
+pub struct MailboxReceiver<T> {
+	shared: Arc<Shared<T>>,
+}
+
+impl<T> MailboxReceiver<T> {
+	/// Wait for the next message. Returns `None` once the sender is gone and
+	/// all messages are received.
+	pub fn recv(&self) -> Option<T> {
+		let mut state = self.shared.lock();
+		loop {
+			if let Some(message) = state.messages.pop_front() {
+				self.shared.changed.notify_all();
+				return Some(message);
+			}
+			if !state.sender_alive {
+				return None;
+			}
+			state = self.shared.changed.wait(state).unwrap_or_else(|err| err.into_inner());
+		}
+	}
+
+	/// Number of frames dropped since the last call.
+	pub fn take_dropped(&self) -> u64 {
+		std::mem::take(&mut self.shared.lock().dropped)
+	}
+}
+
+impl<T> Drop for MailboxReceiver<T> {
+	fn drop(&mut self) {
+		self.shared.lock().receiver_alive = false;
+		self.shared.changed.notify_all();
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	impl Droppable for (bool, u32) {
+		fn droppable(&self) -> bool {
+			self.0
+		}
+	}
+
+	#[test]
+	fn drop_by_policy() {
+		let (sender, receiver) = mailbox(FramePolicy::Latest);
+		sender.send((true, 1)).unwrap();
+		sender.send((false, 2)).unwrap();
+		let mut recycled = Vec::new();
+		sender.send_recycling((true, 3), |message| recycled.push(message)).unwrap();
+		assert_eq!(recycled, [(true, 1)]);
+		assert_eq!(receiver.take_dropped(), 1);
+		assert_eq!(receiver.recv(), Some((false, 2)));
+		assert_eq!(receiver.recv(), Some((true, 3)));
+
+		let (sender, receiver) = mailbox(FramePolicy::Queue(2));
+		for number in 1..=4 {
+			sender.send((true, number)).unwrap();
+		}
+		drop(sender);
+		assert_eq!(receiver.take_dropped(), 2);
+		assert_eq!(receiver.recv(), Some((true, 3)));
+		assert_eq!(receiver.recv(), Some((true, 4)));
+		assert_eq!(receiver.recv(), None);
+
+		let (sender, receiver) = mailbox(FramePolicy::Block);
+		let handle = std::thread::spawn(move || {
+			for number in 1..=5 {
+				sender.send((true, number)).unwrap();
+			}
+		});
+		let received: Vec<_> = std::iter::from_fn(|| receiver.recv()).collect();
+		handle.join().unwrap();
+		assert_eq!(receiver.take_dropped(), 0);
+		assert_eq!(received, (1..=5).map(|number| (true, number)).collect::<Vec<_>>());
+	}
+
+	#[test]
+	fn empty_queue_keeps_one_frame() {
+		let (sender, receiver) = mailbox(FramePolicy::Queue(0));
+		sender.send((true, 1)).unwrap();
+		sender.send((false, 2)).unwrap();
+		sender.send((true, 3)).unwrap();
+		assert_eq!(receiver.take_dropped(), 1);
+		assert_eq!(receiver.recv(), Some((false, 2)));
+		assert_eq!(receiver.recv(), Some((true, 3)));
+	}
+
+	#[test]
+	fn parse_policy() {
+		assert_eq!("latest".parse::<FramePolicy>().unwrap(), FramePolicy::Latest);
+		assert_eq!("queue:3".parse::<FramePolicy>().unwrap(), FramePolicy::Queue(3));
+		assert!("queue:0".parse::<FramePolicy>().is_err());
+		assert!("newest".parse::<FramePolicy>().is_err());
+	}
+}
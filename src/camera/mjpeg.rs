@@ -0,0 +1,299 @@
+//! Frame source for MJPEG streams via HTTP, as served by IP cameras or phone
+//! apps.
+
+use std::{
+	io::{BufRead, BufReader, Read, Write},
+	net::{TcpStream, ToSocketAddrs},
+	time::Duration,
+};
+
+use color_eyre::{
+	eyre::{bail, ensure, eyre},
+	Result,
+};
+use image::{ImageFormat, RgbImage};
+
+use super::source::FrameSource;
+
+/// Time connecting to the server may take.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Time the server may stall, before the stream is considered lost, so that
+/// the camera handler can reconnect.
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+/// Maximum length of a line of the response headers or part headers.
+const MAX_LINE_LENGTH: usize = 8 * 1024;
+/// Maximum number of headers of the response or of a part.
+const MAX_HEADERS: usize = 64;
+/// Marker code at the start of JPEG data.
+const JPEG_START: u8 = 0xD8;
+/// Marker code at the end of JPEG data.
+const JPEG_END: u8 = 0xD9;
+/// Marker code at the start of the entropy-coded data of a scan.
+const SCAN_START: u8 = 0xDA;
+
+/// MJPEG stream of a `multipart/x-mixed-replace` HTTP response.
+#[derive(Debug)]
+pub struct MjpegSource {
+	reader: BufReader<TcpStream>,
+	/// Boundary between the parts, without leading dashes.
+	boundary: String,
+}
+
+impl MjpegSource {
+	/// Connect to the stream at the URL, e.g. `http://192.168.0.2:8080/video`.
+	pub fn connect(url: &str) -> Result<Self> {
+		let address =
+			url.strip_prefix("http://").ok_or_else(|| eyre!("Only http:// URLs are supported"))?;
+		let (host, path) = match address.find('/') {
+			Some(index) => address.split_at(index),
+			None => (address, "/"),
+		};
+		let authority = if host.contains(':') { host.to_owned() } else { format!("{host}:80") };
+
+		let address = authority
+			.to_socket_addrs()?
+			.next()
+			.ok_or_else(|| eyre!("No address found for {authority}"))?;
+		let mut stream = TcpStream::connect_timeout(&address, CONNECT_TIMEOUT)?;
+		stream.set_read_timeout(Some(READ_TIMEOUT))?;
+		// HTTP/1.0 avoids chunked transfer encoding.
+		write!(stream, "GET {path} HTTP/1.0\r\nHost: {host}\r\n\r\n")?;
+		let mut reader = BufReader::new(stream);
+
+		let status = read_line(&mut reader)?;
+		ensure!(
+			status.split_whitespace().nth(1) == Some("200"),
+			"Unexpected response from {url}: {status}"
+		);
+		let headers = read_headers(&mut reader)?;
+		let content_type = header(&headers, "content-type")
+			.ok_or_else(|| eyre!("Missing content type from {url}"))?;
+		let boundary = content_type
+			.split(';')
+			.find_map(|param| param.trim().strip_prefix("boundary="))
+			.ok_or_else(|| eyre!("No multipart stream at {url}: {content_type}"))?
+			.trim_matches('"')
+			// Some servers already include the dashes in the boundary parameter.
+			.trim_start_matches('-')
+			.to_owned();
+
+		Ok(Self { reader, boundary })
+	}
+}
+
+impl FrameSource for MjpegSource {
+	fn frame(&mut self) -> Result<RgbImage> {
+		// Skip to the start of the next part.
+		loop {
+			let line = read_line(&mut self.reader)?;
+			if line.trim_start_matches('-') == self.boundary {
+				break;
+			}
+		}
+
+		let headers = read_headers(&mut self.reader)?;
+		let jpeg = match header(&headers, "content-length") {
+			Some(length) => {
+				let mut jpeg = vec![0; length.parse()?];
+				self.reader.read_exact(&mut jpeg)?;
+				jpeg
+			}
+			None => read_jpeg(&mut self.reader)?,
+		};
+
+		Ok(image::load_from_memory_with_format(&jpeg, ImageFormat::Jpeg)?.into_rgb8())
+	}
+}
+
+/// Read a line without the line ending, failing at the end of the stream or if
+/// the line is too long. Invalid UTF-8 is replaced.
+fn read_line(reader: &mut impl BufRead) -> Result<String> {
+	let mut line = Vec::new();
+	reader.take(MAX_LINE_LENGTH as u64).read_until(b'\n', &mut line)?;
+	if line.is_empty() {
+		bail!("MJPEG stream ended");
+	}
+	ensure!(line.ends_with(b"\n") || line.len() < MAX_LINE_LENGTH, "MJPEG header line too long");
+	Ok(String::from_utf8_lossy(&line).trim_end().to_owned())
+}
+
+/// Read headers up to the empty line, returning lowercase names and values.
+fn read_headers(reader: &mut impl BufRead) -> Result<Vec<(String, String)>> {
+	let mut headers = Vec::new();
+	loop {
+		let line = read_line(reader)?;
+		if line.is_empty() {
+			return Ok(headers);
+		}
+		ensure!(headers.len() < MAX_HEADERS, "Too many MJPEG headers");
+		if let Some((name, value)) = line.split_once(':') {
+			headers.push((name.trim().to_lowercase(), value.trim().to_owned()));
+		}
+	}
+}
+
+/// Find the value of a header by its lowercase name.
+fn header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+	headers.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+}
+
+/// Read JPEG data up to its end marker, for parts without content length.
+/// Segments are skipped by their length, so that the end marker of an embedded
+/// thumbnail does not end the frame.
+fn read_jpeg(reader: &mut impl BufRead) -> Result<Vec<u8>> {
+	let mut jpeg = Vec::new();
+	ensure!(read_marker(reader, &mut jpeg)? == JPEG_START, "MJPEG part is no JPEG");
+	let mut code = read_marker(reader, &mut jpeg)?;
+	loop {
+		code = match code {
+			JPEG_END => return Ok(jpeg),
+			// Markers without segment.
+			0x01 | 0xD0..=0xD7 => read_marker(reader, &mut jpeg)?,
+			SCAN_START => {
+				read_segment(reader, &mut jpeg)?;
+				read_scan(reader, &mut jpeg)?
+			}
+			_ => {
+				read_segment(reader, &mut jpeg)?;
+				read_marker(reader, &mut jpeg)?
+			}
+		};
+	}
+}
+
+/// Read a segment after its marker into the JPEG data, by its length.
+fn read_segment(reader: &mut impl BufRead, jpeg: &mut Vec<u8>) -> Result<()> {
+	let mut length = [0; 2];
+	reader.read_exact(&mut length)?;
+	jpeg.extend_from_slice(&length);
+	let length = usize::from(u16::from_be_bytes(length));
+	ensure!(length >= 2, "Invalid JPEG segment length");
+	let start = jpeg.len();
+	jpeg.resize(start + length - 2, 0);
+	reader.read_exact(&mut jpeg[start..])?;
+	Ok(())
+}
+
+/// Read the next marker into the JPEG data and return its code.
+fn read_marker(reader: &mut impl BufRead, jpeg: &mut Vec<u8>) -> Result<u8> {
+	let mut byte = [0];
+	reader.read_exact(&mut byte)?;
+	ensure!(byte[0] == 0xFF, "Invalid JPEG marker");
+	jpeg.push(0xFF);
+	read_marker_code(reader, jpeg)
+}
+
+/// Read the code of a marker after its first 0xFF byte into the JPEG data.
+fn read_marker_code(reader: &mut impl BufRead, jpeg: &mut Vec<u8>) -> Result<u8> {
+	let mut byte = [0xFF];
+	// Markers may be padded with any number of 0xFF bytes.
+	while byte[0] == 0xFF {
+		reader.read_exact(&mut byte)?;
+	}
+	jpeg.push(byte[0]);
+	Ok(byte[0])
+}
+
+/// Read the entropy-coded data of a scan into the JPEG data, returning the code
+/// of the marker after it. Within the data, 0xFF bytes are followed by 0x00 or
+/// by a restart marker.
+fn read_scan(reader: &mut impl BufRead, jpeg: &mut Vec<u8>) -> Result<u8> {
+	loop {
+		let read = reader.read_until(0xFF, jpeg)?;
+		ensure!(read > 0 && jpeg.ends_with(&[0xFF]), "MJPEG stream ended");
+		let code = read_marker_code(reader, jpeg)?;
+		if code != 0x00 && !(0xD0..=0xD7).contains(&code) {
+			return Ok(code);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{io::Cursor, net::TcpListener, thread};
+
+	use image::{codecs::jpeg::JpegEncoder, ColorType};
+
+	use super::*;
+
+	/// Encode a single colored frame as JPEG.
+	fn jpeg_frame(color: u8) -> Vec<u8> {
+		let frame = RgbImage::from_pixel(32, 16, image::Rgb([color, color, color]));
+		let mut jpeg = Vec::new();
+		JpegEncoder::new(&mut jpeg).encode(frame.as_raw(), 32, 16, ColorType::Rgb8).unwrap();
+		jpeg
+	}
+
+	#[test]
+	fn receive_frames() {
+		let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+		let url = format!("http://{}/video", listener.local_addr().unwrap());
+		thread::spawn(move || {
+			let (mut stream, _) = listener.accept().unwrap();
+			let mut request = String::new();
+			BufReader::new(stream.try_clone().unwrap()).read_line(&mut request).unwrap();
+			assert_eq!(request, "GET /video HTTP/1.0\r\n");
+
+			write!(
+				stream,
+				"HTTP/1.0 200 OK\r\nContent-Type: multipart/x-mixed-replace;boundary=frame\r\n\r\n"
+			)
+			.unwrap();
+			// One part with and one part without content length.
+			let jpeg = jpeg_frame(0x00);
+			write!(stream, "--frame\r\nContent-Type: image/jpeg\r\n").unwrap();
+			write!(stream, "Content-Length: {}\r\n\r\n", jpeg.len()).unwrap();
+			stream.write_all(&jpeg).unwrap();
+			write!(stream, "\r\n--frame\r\nContent-Type: image/jpeg\r\n\r\n").unwrap();
+			stream.write_all(&jpeg_frame(0xFF)).unwrap();
+			write!(stream, "\r\n").unwrap();
+		});
+
+		let mut source = MjpegSource::connect(&url).unwrap();
+		let frame = source.frame().unwrap();
+		assert_eq!(frame.dimensions(), (32, 16));
+		assert!(frame.get_pixel(5, 5)[0] < 0x10);
+		let frame = source.frame().unwrap();
+		assert!(frame.get_pixel(5, 5)[0] > 0xF0);
+		assert!(source.frame().is_err());
+	}
+
+	#[test]
+	fn embedded_thumbnail_does_not_end_frame() {
+		// The thumbnail is stored in an APP1 segment after the start marker, like
+		// in EXIF data.
+		let thumbnail = RgbImage::from_pixel(8, 8, image::Rgb([0x80, 0x80, 0x80]));
+		let mut exif = b"Exif\0\0".to_vec();
+		JpegEncoder::new(&mut exif).encode(thumbnail.as_raw(), 8, 8, ColorType::Rgb8).unwrap();
+		let frame = jpeg_frame(0xFF);
+		let mut jpeg = vec![0xFF, 0xD8, 0xFF, 0xE1];
+		jpeg.extend_from_slice(&(exif.len() as u16 + 2).to_be_bytes());
+		jpeg.extend_from_slice(&exif);
+		jpeg.extend_from_slice(&frame[2..]);
+
+		let mut stream = jpeg.clone();
+		stream.extend_from_slice(b"\r\n--frame\r\n");
+		let mut reader = Cursor::new(stream);
+		assert_eq!(read_jpeg(&mut reader).unwrap(), jpeg);
+		assert_eq!(read_line(&mut reader).unwrap(), "");
+		assert_eq!(read_line(&mut reader).unwrap(), "--frame");
+		let frame = image::load_from_memory_with_format(&jpeg, ImageFormat::Jpeg).unwrap();
+		assert_eq!(frame.into_rgb8().dimensions(), (32, 16));
+
+		// Streams ending within the frame fail.
+		assert!(read_jpeg(&mut Cursor::new(&jpeg[..jpeg.len() - 2])).is_err());
+	}
+
+	#[test]
+	fn limit_headers() {
+		let mut reader = Cursor::new(b"Content-Type: image/jpeg\r\nX-Name: \xFF\r\n\r\n".to_vec());
+		let headers = read_headers(&mut reader).unwrap();
+		assert_eq!(headers[0], ("content-type".to_owned(), "image/jpeg".to_owned()));
+		assert_eq!(headers[1], ("x-name".to_owned(), "\u{FFFD}".to_owned()));
+
+		let long = format!("X-Long: {}\r\n\r\n", "a".repeat(MAX_LINE_LENGTH));
+		assert!(read_headers(&mut Cursor::new(long)).is_err());
+		let many = "X-Header: value\r\n".repeat(MAX_HEADERS + 1) + "\r\n";
+		assert!(read_headers(&mut Cursor::new(many)).is_err());
+	}
+}
@@ -2,9 +2,34 @@
 //! analyze them and send them to the UI view.
 
 pub mod analysis;
+pub mod calibration;
+pub mod controls;
+pub mod devices;
+pub mod format;
+mod gesture;
+mod illumination;
+mod mailbox;
+mod mjpeg;
+pub mod morphology;
+pub mod pool;
+mod preprocess;
+mod profile;
+mod replay;
+mod simd;
+mod smoothing;
+mod source;
+mod stabilisation;
+pub mod stats;
+mod synthetic;
+#[doc(hidden)]
+pub mod testing;
 
 use std::{
-	sync::mpsc,
+	iter,
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		mpsc, Arc, Mutex,
+	},
 	thread::{self, JoinHandle},
 	time::Instant,
 };
@@ -12,83 +37,566 @@ use std::{
 use color_eyre::Result;
 use druid::{ExtEventSink, Selector, SingleUse, Target};
 use image::RgbImage;
-use nokhwa::Camera;
+
+use self::{
+	analysis::{ColorMode, Frame, Threshold, DEFAULT_BLUR_SIGMA},
+	calibration::{CalibrationStatus, NoiseCalibration},
+	controls::{ControlInfo, ControlSetting, S_CAMERA_CONTROLS},
+	devices::DeviceMonitor,
+	format::{FormatChoice, S_CAMERA_FORMATS},
+	gesture::DwellDetector,
+	mailbox::{Droppable, MailboxReceiver, MailboxSender},
+	morphology::Morphology,
+	pool::{FramePool, PooledFrame},
+	preprocess::Preprocessed,
+	profile::Profile,
+	smoothing::PointSmoother,
+	source::FrameSource,
+	stats::{Metric, StatsCollector},
+};
+pub use self::{
+	mailbox::FramePolicy,
+	preprocess::{PreparedFrame, Preprocessor},
+	source::Source,
+};
+use crate::output::{self, CameraStatus, EventSink, FrameKind, PipelineEvent};
 
 /// Selector name for unprocessed camera frames.
 pub const S_CAMERA_FRAME: &str = "CameraFrame";
 /// Selector name for processed camera frames.
 pub const S_PROCESSED_FRAME: &str = "CameraProcessedFrame";
+/// Selector name for previous processed frames aligned to the current ones.
+pub const S_STABILISED_FRAME: &str = "CameraStabilisedFrame";
 /// Selector name for difference camera frames.
 pub const S_DIFFERENCE_FRAME: &str = "CameraDifferenceFrame";
 /// Selector name for detected point.
 pub const S_CAMERA_POINT: &str = "CameraDetectedPoint";
-/// Selector name for camera FPS.
-pub const S_CAMERA_FPS: &str = "CameraFPS";
+/// Selector name for the size of the unprocessed frames, which detected points
+/// and zones refer to.
+pub const S_FRAME_SIZE: &str = "CameraFrameSize";
+/// Selector name for the camera status.
+pub const S_CAMERA_STATUS: &str = "CameraStatus";
+/// Selector name for the interval since the previous frame, in milliseconds.
+pub const S_FRAME_INTERVAL: &str = "FrameInterval";
+/// Selector name for the statistics of the pipeline.
+pub const S_PIPELINE_STATS: &str = "PipelineStats";
+/// Selector name for the progress of the noise calibration.
+pub const S_CALIBRATION: &str = "Calibration";
+
+/// Request to the camera handler, e.g. from the UI.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CameraRequest {
+	/// Switch to the system camera with the given index.
+	Pick(usize),
+	/// Reopen the current camera with the given format and save it in the
+	/// camera's profile.
+	Format(FormatChoice),
+	/// Reopen the current camera using the profile of the given name.
+	LoadProfile(String),
+	/// Set a control of the current camera and save it in the camera's
+	/// profile.
+	Control(ControlSetting),
+	/// Switch to the given source, e.g. synthetic frames without a camera.
+	Open(Source),
+	/// Scan for cameras now instead of waiting for the next periodic scan.
+	Rescan,
+}
+
+/// Camera request sender
+pub type RequestSender = mpsc::Sender<CameraRequest>;
+/// Camera request receiver
+pub type RequestReceiver = mpsc::Receiver<CameraRequest>;
+
+/// Message from the frame receiver to the frame processor.
+#[derive(Debug)]
+enum Capture {
+	/// Frame retrieved from the source at the given time.
+	Frame(RgbImage, Instant),
+	/// Changed status of the source.
+	Status(CameraStatus),
+}
+
+impl Droppable for Capture {
+	fn droppable(&self) -> bool {
+		matches!(self, Self::Frame(..))
+	}
+}
+
+/// Handle to control the pipeline from other threads.
+#[derive(Debug, Clone)]
+pub struct PipelineControl {
+	paused: Arc<AtomicBool>,
+	threshold: Arc<Mutex<Threshold>>,
+	blur_sigma: Arc<Mutex<f32>>,
+	calibrate: Arc<AtomicBool>,
+	request_sender: RequestSender,
+}
+
+impl PipelineControl {
+	/// Create new pipeline control, which sends camera requests using the
+	/// given sender. Use [`CameraConnector::with_control`] to connect it.
+	pub fn new(request_sender: RequestSender) -> Self {
+		Self {
+			paused: Arc::new(AtomicBool::new(false)),
+			threshold: Arc::default(),
+			blur_sigma: Arc::new(Mutex::new(DEFAULT_BLUR_SIGMA)),
+			calibrate: Arc::new(AtomicBool::new(false)),
+			request_sender,
+		}
+	}
+
+	/// Pause processing of frames.
+	pub fn pause(&self) {
+		self.paused.store(true, Ordering::Relaxed);
+	}
 
-/// Camera picker index receiver
-pub type PickReceiver = mpsc::Receiver<usize>;
+	/// Resume processing of frames.
+	pub fn resume(&self) {
+		self.paused.store(false, Ordering::Relaxed);
+	}
+
+	/// Threshold the differences between frames are currently compared to.
+	pub fn threshold(&self) -> Threshold {
+		*self.threshold.lock().unwrap_or_else(|err| err.into_inner())
+	}
+
+	/// Compare the differences between frames to the given threshold from the
+	/// next frame on.
+	pub fn set_threshold(&self, threshold: Threshold) {
+		*self.threshold.lock().unwrap_or_else(|err| err.into_inner()) = threshold;
+	}
+
+	/// Standard deviation of the Gaussian blur of processed frames.
+	pub fn blur_sigma(&self) -> f32 {
+		*self.blur_sigma.lock().unwrap_or_else(|err| err.into_inner())
+	}
+
+	/// Blur processed frames with the given standard deviation from the next
+	/// frame on.
+	pub fn set_blur_sigma(&self, blur_sigma: f32) {
+		*self.blur_sigma.lock().unwrap_or_else(|err| err.into_inner()) = blur_sigma;
+	}
+
+	/// Measure the noise of the camera over the next frames, while the scene is
+	/// still, and set the threshold and blur strength accordingly.
+	pub fn calibrate(&self) {
+		self.calibrate.store(true, Ordering::Relaxed);
+	}
+
+	/// Switch to the camera with the given index.
+	pub fn switch_camera(&self, index: usize) -> Result<()> {
+		self.request_sender.send(CameraRequest::Pick(index))?;
+		Ok(())
+	}
+
+	/// Apply the profile of the given name to the current camera.
+	pub fn load_profile(&self, name: String) -> Result<()> {
+		self.request_sender.send(CameraRequest::LoadProfile(name))?;
+		Ok(())
+	}
+}
+
+/// Settings of the analysis, which compares the preprocessed frames and
+/// locates the movement.
+#[derive(Debug)]
+struct AnalysisSettings {
+	/// Threshold of the differences, shared with the pipeline control.
+	threshold: Arc<Mutex<Threshold>>,
+	/// Blur strength of the preprocessing, which calibrations change.
+	blur_sigma: Arc<Mutex<f32>>,
+	/// Whether a calibration is requested.
+	calibrate: Arc<AtomicBool>,
+	/// Cleanup of the mask of moving pixels.
+	morphology: Option<Morphology>,
+	/// Whether camera shake is compensated.
+	stabilise: bool,
+	/// Whether changes of the whole frame's brightness are compensated.
+	compensate_illumination: bool,
+	/// Largest fraction of moving pixels, above which the whole frame is
+	/// assumed to change and no movement is located.
+	motion_limit: Option<f32>,
+}
 
 /// Handler to connect to the camera and retrieve images
 pub struct CameraConnector {
 	event_sender: ExtEventSink,
-	pick_receiver: PickReceiver,
+	request_receiver: RequestReceiver,
+	source: Source,
+	sinks: Vec<Box<dyn EventSink>>,
+	paused: Arc<AtomicBool>,
+	threshold: Arc<Mutex<Threshold>>,
+	blur_sigma: Arc<Mutex<f32>>,
+	calibrate: Arc<AtomicBool>,
+	frame_policy: FramePolicy,
+	processing_width: Option<u32>,
+	color_mode: ColorMode,
+	pipelined: bool,
+	morphology: Option<Morphology>,
+	stabilise: bool,
+	compensate_illumination: bool,
+	motion_limit: Option<f32>,
+	pool: FramePool,
 }
 
 impl CameraConnector {
 	/// Create new camera connector with the given information.
-	pub fn new(event_sender: ExtEventSink, cam_pick_receiver: PickReceiver) -> Self {
-		Self { event_sender, pick_receiver: cam_pick_receiver }
+	pub fn new(event_sender: ExtEventSink, request_receiver: RequestReceiver) -> Self {
+		Self {
+			event_sender,
+			request_receiver,
+			source: Source::default(),
+			sinks: Vec::new(),
+			paused: Arc::new(AtomicBool::new(false)),
+			threshold: Arc::default(),
+			blur_sigma: Arc::new(Mutex::new(DEFAULT_BLUR_SIGMA)),
+			calibrate: Arc::new(AtomicBool::new(false)),
+			frame_policy: FramePolicy::default(),
+			processing_width: None,
+			color_mode: ColorMode::default(),
+			pipelined: false,
+			morphology: None,
+			stabilise: false,
+			compensate_illumination: false,
+			motion_limit: None,
+			pool: FramePool::new(),
+		}
+	}
+
+	/// Let the pipeline be controlled by the given control handle.
+	pub fn with_control(mut self, control: &PipelineControl) -> Self {
+		self.paused = control.paused.clone();
+		self.threshold = control.threshold.clone();
+		self.blur_sigma = control.blur_sigma.clone();
+		self.calibrate = control.calibrate.clone();
+		self
+	}
+
+	/// Use the given source of frames until another camera is picked.
+	pub fn with_source(mut self, source: Source) -> Self {
+		self.source = source;
+		self
+	}
+
+	/// Handle frames arriving faster than they are processed with the given
+	/// policy.
+	pub fn with_frame_policy(mut self, frame_policy: FramePolicy) -> Self {
+		self.frame_policy = frame_policy;
+		self
+	}
+
+	/// Run the analysis on frames downscaled to at most the given width, or on
+	/// the full frames for `None`. Detected points are still reported in
+	/// coordinates of the full frames.
+	pub fn with_processing_width(mut self, processing_width: Option<u32>) -> Self {
+		self.processing_width = processing_width;
+		self
+	}
+
+	/// Run the analysis on the color channels of the given mode.
+	pub fn with_color_mode(mut self, color_mode: ColorMode) -> Self {
+		self.color_mode = color_mode;
+		self
+	}
+
+	/// Preprocess the next frame on a separate thread, while the current frame
+	/// is compared to the previous one and the movement is located. Results
+	/// are still emitted in the order of the frames.
+	pub fn with_pipelining(mut self, pipelined: bool) -> Self {
+		self.pipelined = pipelined;
+		self
+	}
+
+	/// Clean up the mask of moving pixels with the given morphological
+	/// operation before locating the movement, or not at all for `None`.
+	pub fn with_morphology(mut self, morphology: Option<Morphology>) -> Self {
+		self.morphology = morphology;
+		self
+	}
+
+	/// Compensate camera shake by aligning the previous frame to the current
+	/// one before comparing them. The aligned frames are sent to the UI.
+	pub fn with_stabilisation(mut self, stabilise: bool) -> Self {
+		self.stabilise = stabilise;
+		self
+	}
+
+	/// Compensate changes of the whole frame's brightness, e.g. from lights
+	/// switching on or flickering, before comparing frames.
+	pub fn with_illumination_compensation(mut self, compensate: bool) -> Self {
+		self.compensate_illumination = compensate;
+		self
+	}
+
+	/// Locate no movement in frames, where more than the given fraction of
+	/// pixels moves, or in all frames for `None`.
+	pub fn with_motion_limit(mut self, motion_limit: Option<f32>) -> Self {
+		self.motion_limit = motion_limit;
+		self
+	}
+
+	/// Add an output, which receives all pipeline events.
+	pub fn with_sink(mut self, sink: impl EventSink + 'static) -> Self {
+		self.sinks.push(Box::new(sink));
+		self
 	}
 
 	/// Spawn and run the camera handler in a new thread.
 	pub fn spawn(self) -> (JoinHandle<()>, JoinHandle<()>) {
-		let (frame_sender, frame_receiver) = mpsc::sync_channel(2);
+		let (frame_sender, frame_receiver) = mailbox::mailbox(self.frame_policy);
 
-		let mut pick_receiver = self.pick_receiver;
+		let mut request_receiver = self.request_receiver;
+		let mut source = self.source;
+		let receiver_event_sender = self.event_sender.clone();
+		let receiver_pool = self.pool.clone();
 		let frame_receiver_handle = thread::spawn(move || {
-			let mut cam_index = 0;
-			while let Err(err) =
-				Self::run_frame_receiver(&mut pick_receiver, cam_index, frame_sender.clone())
-			{
+			let mut monitor = DeviceMonitor::new();
+			let mut status = CameraStatus::Connecting;
+			// Requests for the current source, which arrived while it was not open.
+			let mut queued = Vec::new();
+			while frame_sender.send(Capture::Status(status)).is_ok() {
+				let result = Self::run_frame_receiver(
+					&mut request_receiver,
+					&mut source,
+					&mut queued,
+					&mut monitor,
+					&receiver_event_sender,
+					&frame_sender,
+					&receiver_pool,
+				);
+				let err = match result {
+					Ok(()) => break,
+					Err(err) => err,
+				};
 				eprintln!("Error running camera handler: {err}");
-				match pick_receiver.recv() {
-					Ok(index) => cam_index = index,
-					Err(mpsc::RecvError) => break,
+				let failed = CameraStatus::Error(err.to_string());
+				if frame_sender.send(Capture::Status(failed)).is_err() {
+					break;
 				}
+				match Self::wait_for_source(
+					&mut request_receiver,
+					&mut queued,
+					&mut monitor,
+					&receiver_event_sender,
+					&source,
+				) {
+					Some(next) => source = next,
+					None => break,
+				}
+				status = CameraStatus::Reconnecting;
 			}
 		});
 
 		let mut event_sender = self.event_sender;
+		let mut sinks = self.sinks;
+		let pipelined = self.pipelined;
+		let pool = self.pool;
+		let preprocessor = Preprocessor {
+			paused: self.paused,
+			processing_width: self.processing_width,
+			color_mode: self.color_mode,
+			blur_sigma: self.blur_sigma.clone(),
+			pool: pool.clone(),
+		};
+		let settings = AnalysisSettings {
+			threshold: self.threshold,
+			blur_sigma: self.blur_sigma,
+			calibrate: self.calibrate,
+			morphology: self.morphology,
+			stabilise: self.stabilise,
+			compensate_illumination: self.compensate_illumination,
+			motion_limit: self.motion_limit,
+		};
 		let frame_processor_handle = thread::spawn(move || {
-			Self::run_frame_processor(frame_receiver, &mut event_sender)
-				.expect("running frame processor")
+			let result = Self::process_frames(
+				preprocessor,
+				frame_receiver,
+				pipelined,
+				&settings,
+				&mut event_sender,
+				&mut sinks,
+				&pool,
+			);
+			// Errors only occur when the UI is gone, so there is nobody left to
+			// report them to.
+			if let Err(err) = result {
+				eprintln!("Error running frame processor: {err}");
+			}
 		});
 
 		(frame_receiver_handle, frame_processor_handle)
 	}
 
-	/// Run the frame receiver for this camera handler.
+	/// Open the source and report its formats and controls to the UI.
+	fn open_source(
+		source: &Source,
+		profile_name: Option<&str>,
+		event_sender: &ExtEventSink,
+	) -> Result<Box<dyn FrameSource>> {
+		let mut frames = source.open(profile_name)?;
+		event_sender.submit_command(
+			Selector::new(S_CAMERA_FORMATS),
+			frames.formats()?,
+			Target::Auto,
+		)?;
+		// Not every backend can list controls, the camera is usable anyway.
+		let controls = frames.controls().unwrap_or_else(|err| {
+			eprintln!("Error listing camera controls: {err}");
+			Vec::new()
+		});
+		event_sender.submit_command(
+			Selector::<Vec<ControlInfo>>::new(S_CAMERA_CONTROLS),
+			controls,
+			Target::Auto,
+		)?;
+		Ok(frames)
+	}
+
+	/// Wait until the failed source is available again, e.g. the camera was
+	/// plugged back in, or another camera is picked. Requests changing the
+	/// failed source are queued until it is open again and dropped, when
+	/// another source is picked. Returns `None` when no more requests can
+	/// arrive.
+	fn wait_for_source(
+		request_receiver: &mut RequestReceiver,
+		queued: &mut Vec<CameraRequest>,
+		monitor: &mut DeviceMonitor,
+		event_sender: &ExtEventSink,
+		source: &Source,
+	) -> Option<Source> {
+		loop {
+			match request_receiver.recv_timeout(monitor.until_next_scan()) {
+				Ok(CameraRequest::Pick(index)) => {
+					monitor.prefer(index);
+					queued.clear();
+					return Some(Source::Camera(index));
+				}
+				Ok(CameraRequest::Open(next)) => {
+					queued.clear();
+					return Some(next);
+				}
+				Ok(CameraRequest::Rescan) => {
+					monitor.rescan(event_sender);
+					if let Some(next) = monitor.reconnect_source(source) {
+						return Some(next);
+					}
+				}
+				Ok(request) => queued.push(request),
+				Err(mpsc::RecvTimeoutError::Timeout) => {
+					monitor.scan_if_due(event_sender);
+					if let Some(next) = monitor.reconnect_source(source) {
+						return Some(next);
+					}
+				}
+				Err(mpsc::RecvTimeoutError::Disconnected) => return None,
+			}
+		}
+	}
+
+	/// Load the profile currently in use, change it and save it again. Errors
+	/// are only reported, the camera keeps streaming with the change.
+	fn update_profile(
+		profile_name: Option<&str>,
+		frames: &dyn FrameSource,
+		update: impl FnOnce(&mut Profile),
+	) {
+		let name = profile_name.map(Into::into).or_else(|| frames.profile_name());
+		if let Some(name) = name {
+			let result = Profile::load(&name).and_then(|mut profile| {
+				update(&mut profile);
+				profile.save(&name)
+			});
+			if let Err(err) = result {
+				eprintln!("Error saving camera profile {name}: {err}");
+			}
+		}
+	}
+
+	/// Run the frame receiver for this camera handler. The source is updated
+	/// when another camera is picked. Queued requests are handled first, once
+	/// the source is open. Frames are retrieved into buffers of the pool.
 	fn run_frame_receiver(
-		pick_receiver: &mut PickReceiver,
-		start_index: usize,
-		frame_sender: mpsc::SyncSender<RgbImage>,
+		request_receiver: &mut RequestReceiver,
+		source: &mut Source,
+		queued: &mut Vec<CameraRequest>,
+		monitor: &mut DeviceMonitor,
+		event_sender: &ExtEventSink,
+		frame_sender: &MailboxSender<Capture>,
+		pool: &FramePool,
 	) -> Result<()> {
-		let mut camera = Camera::new(start_index, None)?;
-		camera.open_stream()?;
+		// Profile to use instead of the source's own profile.
+		let mut profile_name: Option<String> = None;
+		monitor.scan_if_due(event_sender);
+		let mut frames = Self::open_source(source, None, event_sender)?;
+		monitor.opened(source, event_sender)?;
+		frame_sender.send(Capture::Status(CameraStatus::Streaming))?;
 
+		let mut queued = std::mem::take(queued).into_iter();
 		loop {
+			monitor.scan_in_background_if_due(event_sender);
+
 			// Retrieve camera frame and send it to the processor
-			let current_frame = camera.frame()?;
-			frame_sender.send(current_frame)?;
-
-			// Check if there is a signal to switch to another camera.
-			match pick_receiver.try_recv() {
-				Ok(index) => {
-					camera.stop_stream()?;
-					camera.set_index(index)?;
-					// Does `camera.set_index` keep the camera format?
-					// camera = Camera::new(index, None)?;
-					camera.open_stream()?;
+			let current_frame = frames.frame_into(pool)?;
+			let capture = Capture::Frame(current_frame, Instant::now());
+			// Frames dropped by the policy return their buffers to the pool.
+			frame_sender.send_recycling(capture, |dropped| {
+				if let Capture::Frame(frame, _) = dropped {
+					pool.recycle_image(frame);
+				}
+			})?;
+
+			// Check if there is a request to switch the camera or its settings.
+			match queued.next().map_or_else(|| request_receiver.try_recv(), Ok) {
+				Ok(request) => {
+					let reopen = match request {
+						CameraRequest::Pick(index) => {
+							*source = Source::Camera(index);
+							monitor.prefer(index);
+							profile_name = None;
+							true
+						}
+						CameraRequest::Format(format) => {
+							Self::update_profile(
+								profile_name.as_deref(),
+								frames.as_ref(),
+								|profile| profile.format = Some(format),
+							);
+							true
+						}
+						CameraRequest::LoadProfile(name) => {
+							profile_name = Some(name);
+							true
+						}
+						CameraRequest::Open(next) => {
+							*source = next;
+							profile_name = None;
+							true
+						}
+						CameraRequest::Rescan => {
+							monitor.rescan_in_background(event_sender);
+							false
+						}
+						CameraRequest::Control(setting) => {
+							// Controls can be changed while streaming. A rejected value
+							// should not stop the stream.
+							match frames.set_control(setting) {
+								Ok(()) => Self::update_profile(
+									profile_name.as_deref(),
+									frames.as_ref(),
+									|profile| profile.set_control(setting),
+								),
+								Err(err) => eprintln!("Error setting camera control: {err}"),
+							}
+							false
+						}
+					};
+					if reopen {
+						// Close the previous source first, it might be the same camera.
+						drop(frames);
+						frame_sender.send(Capture::Status(CameraStatus::Connecting))?;
+						frames = Self::open_source(source, profile_name.as_deref(), event_sender)?;
+						monitor.opened(source, event_sender)?;
+						frame_sender.send(Capture::Status(CameraStatus::Streaming))?;
+					}
 				}
 				Err(mpsc::TryRecvError::Disconnected) => break,
 				_ => {}
@@ -97,67 +605,347 @@ impl CameraConnector {
 		Ok(())
 	}
 
-	/// Run image processor with the given event sender
+	/// Preprocess and analyse the received frames, either on one thread or
+	/// pipelined on two, see [`Self::run_frame_processor`].
+	fn process_frames(
+		preprocessor: Preprocessor,
+		frame_receiver: MailboxReceiver<Capture>,
+		pipelined: bool,
+		settings: &AnalysisSettings,
+		event_sender: &mut ExtEventSink,
+		sinks: &mut [Box<dyn EventSink>],
+		pool: &FramePool,
+	) -> Result<()> {
+		if !pipelined {
+			let frames = iter::from_fn(|| preprocessor.next(&frame_receiver));
+			return Self::run_frame_processor(frames, settings, event_sender, sinks, pool);
+		}
+		// Only one frame waits for the analysis, so frames are still dropped by
+		// the frame policy when the analysis is too slow.
+		let (sender, receiver) = mpsc::sync_channel(1);
+		let preprocessor_handle = thread::spawn(move || preprocessor.run(&frame_receiver, &sender));
+		let result = Self::run_frame_processor(receiver, settings, event_sender, sinks, pool);
+		// The analysis ends when the preprocessor is gone, so a panic of it is
+		// only reported here.
+		if preprocessor_handle.join().is_err() {
+			eprintln!("Error running frame preprocessor: it panicked");
+		}
+		result
+	}
+
+	/// Run image processor on the preprocessed frames with the given event
+	/// sender, outputs and analysis settings.
+	/// Buffers of frames are taken from the pool and returned to it, when the
+	/// UI is done with them.
 	fn run_frame_processor(
-		frame_receiver: mpsc::Receiver<RgbImage>,
+		frames: impl IntoIterator<Item = Preprocessed>,
+		settings: &AnalysisSettings,
 		event_sender: &mut ExtEventSink,
+		sinks: &mut [Box<dyn EventSink>],
+		pool: &FramePool,
 	) -> Result<()> {
-		let mut previous_frame: Option<RgbImage> = None;
+		let mut previous_frame: Option<Frame> = None;
+		let mut previous_blur_sigma = None;
+		let mut dwell_detector = DwellDetector::new();
+		let mut smoother = PointSmoother::new();
+		let mut stats = StatsCollector::new();
+		let mut calibration = None;
 		let mut timer = Instant::now();
-		while let Ok(mut current_frame) = frame_receiver.recv() {
-			// Process the frame to reduce noise and such.
-			analysis::flip_in_place(&mut current_frame);
-			let processed_frame = analysis::process_frame(&current_frame);
+		for preprocessed in frames {
+			let frame = match preprocessed {
+				Preprocessed::Frame(frame) => frame,
+				Preprocessed::Status(status) => {
+					output::dispatch(sinks, PipelineEvent::Status(&status));
+					event_sender.submit_command(
+						Selector::new(S_CAMERA_STATUS),
+						status,
+						Target::Auto,
+					)?;
+					continue;
+				}
+				Preprocessed::Paused => {
+					if let Some(previous) = previous_frame.take() {
+						pool.recycle_frame(previous);
+					}
+					stats.interrupt();
+					timer = Instant::now();
+					continue;
+				}
+			};
+			stats.start_frame();
+			for (metric, time) in frame.times {
+				stats.add(metric, time);
+			}
+			let PreparedFrame {
+				original: current_frame,
+				processed: processed_frame,
+				factor,
+				blur_sigma,
+				captured,
+				dropped,
+				..
+			} = frame;
+			let dimensions = current_frame.dimensions();
+			// Frames blurred with another strength, e.g. before a calibration,
+			// differ everywhere. In pipelined mode, this also catches the frame
+			// preprocessed while the calibration finished.
+			if previous_blur_sigma.replace(blur_sigma) != Some(blur_sigma) {
+				if let Some(previous) = previous_frame.take() {
+					pool.recycle_frame(previous);
+				}
+			}
 
 			// Send original and processed image.
+			output::dispatch(sinks, PipelineEvent::Frame(FrameKind::Original, &current_frame));
+			output::dispatch(sinks, PipelineEvent::Frame(FrameKind::Processed, &processed_frame));
+			event_sender.submit_command(Selector::new(S_FRAME_SIZE), dimensions, Target::Auto)?;
 			event_sender.submit_command(
 				Selector::new(S_CAMERA_FRAME),
-				SingleUse::new(current_frame),
+				SingleUse::new(PooledFrame::new(current_frame, pool.clone())),
 				Target::Auto,
 			)?;
 			event_sender.submit_command(
 				Selector::new(S_PROCESSED_FRAME),
-				SingleUse::new(processed_frame.clone()),
+				SingleUse::new(PooledFrame::new(pool.copy_frame(&processed_frame), pool.clone())),
 				Target::Auto,
 			)?;
+			stats.lap(Metric::Output);
 
 			// Compare to previous frame, send diff image and send position.
 			if let Some(mut previous) = previous_frame {
-				if previous.dimensions() == processed_frame.dimensions() {
-					analysis::frame_difference(&mut previous, &processed_frame);
-					let point = analysis::find_right_movement(&previous);
+				if settings.stabilise {
+					stabilisation::align(&mut previous, &processed_frame, pool);
+					stats.lap(Metric::Stabilise);
+					output::dispatch(sinks, PipelineEvent::Frame(FrameKind::Stabilised, &previous));
+					event_sender.submit_command(
+						Selector::new(S_STABILISED_FRAME),
+						SingleUse::new(PooledFrame::new(pool.copy_frame(&previous), pool.clone())),
+						Target::Auto,
+					)?;
+					stats.lap(Metric::Output);
+				}
+				if settings.compensate_illumination {
+					illumination::compensate(&mut previous, &processed_frame);
+				}
+				let threshold = *settings.threshold.lock().unwrap_or_else(|err| err.into_inner());
+				if let Some(mut mask) = previous.difference(&processed_frame, threshold, pool) {
+					stats.lap(Metric::Difference);
+					if let Some(morphology) = &settings.morphology {
+						mask = mask.clean(morphology, pool);
+						stats.lap(Metric::Cleanup);
+					}
+					// Motion almost everywhere is a change of the whole scene, e.g.
+					// of the lighting, rather than a movement to follow.
+					let (width, height) = mask.dimensions();
+					let whole_frame = settings
+						.motion_limit
+						.is_some_and(|limit| mask.motion_fraction(0, 0, width, height) > limit);
+					let point = if whole_frame {
+						None
+					} else {
+						mask.find_right_movement()
+							.map(|point| analysis::upscale_point(point, factor))
+					};
+					stats.lap(Metric::Locate);
+					let difference = mask.into_frame();
+					output::dispatch(
+						sinks,
+						PipelineEvent::Difference { frame: &difference, whole_frame },
+					);
 
 					event_sender.submit_command(
 						Selector::new(S_DIFFERENCE_FRAME),
-						SingleUse::new(previous),
+						SingleUse::new(PooledFrame::new(difference, pool.clone())),
 						Target::Auto,
 					)?;
 					if let Some(detected_point) = point {
+						let position = (detected_point.x, detected_point.y);
+						output::dispatch(sinks, PipelineEvent::Point { position, dimensions });
+						let smoothed = smoother.update(position);
+						output::dispatch(
+							sinks,
+							PipelineEvent::SmoothedPoint { position: smoothed, dimensions },
+						);
 						event_sender.submit_command(
 							Selector::new(S_CAMERA_POINT),
 							(detected_point.x, detected_point.y),
 							Target::Auto,
 						)?;
 					}
+
+					let point = point.map(|point| (point.x, point.y));
+					if let Some(gesture) =
+						dwell_detector.update(point, dimensions.0, Instant::now())
+					{
+						output::dispatch(sinks, PipelineEvent::Gesture(gesture));
+					}
+					stats.lap(Metric::Output);
 				}
 			}
+			// All outputs handled the frame, e.g. moved the pointer.
+			output::dispatch(sinks, PipelineEvent::Latency(captured.elapsed()));
+			Self::update_calibration(&mut calibration, &processed_frame, settings, event_sender)?;
 			previous_frame = Some(processed_frame);
 
 			// Send FPS
 			let elapsed = timer.elapsed().as_secs_f32();
 			timer = Instant::now();
 			let frame_rate = 1.0 / elapsed;
-			event_sender.submit_command(Selector::new(S_CAMERA_FPS), frame_rate, Target::Auto)?;
+			output::dispatch(sinks, PipelineEvent::Fps(frame_rate));
+			event_sender.submit_command(
+				Selector::new(S_FRAME_INTERVAL),
+				elapsed * 1000.0,
+				Target::Auto,
+			)?;
+			stats.lap(Metric::Output);
+
+			// Send statistics over the recent frames.
+			stats.finish_frame(captured, dropped);
+			if let Some(report) = stats.report() {
+				output::dispatch(sinks, PipelineEvent::Stats(&report));
+				event_sender.submit_command(
+					Selector::new(S_PIPELINE_STATS),
+					Arc::new(report),
+					Target::Auto,
+				)?;
+			}
 		}
 		Ok(())
 	}
+
+	/// Start a requested calibration or add the processed frame to the running
+	/// one. Once the noise is measured, the derived threshold and blur strength
+	/// are applied.
+	fn update_calibration(
+		calibration: &mut Option<NoiseCalibration>,
+		processed_frame: &Frame,
+		settings: &AnalysisSettings,
+		event_sender: &mut ExtEventSink,
+	) -> Result<()> {
+		if settings.calibrate.swap(false, Ordering::Relaxed) {
+			let blur_sigma = *settings.blur_sigma.lock().unwrap_or_else(|err| err.into_inner());
+			*calibration = Some(NoiseCalibration::new(blur_sigma, Instant::now()));
+			event_sender.submit_command(
+				Selector::new(S_CALIBRATION),
+				CalibrationStatus::Measuring,
+				Target::Auto,
+			)?;
+		}
+		let Some(measurement) = calibration else {
+			return Ok(());
+		};
+		measurement.add(processed_frame);
+		if !measurement.is_done(Instant::now()) {
+			return Ok(());
+		}
+
+		let status = match measurement.result() {
+			Some(result) => {
+				*settings.threshold.lock().unwrap_or_else(|err| err.into_inner()) =
+					result.threshold;
+				*settings.blur_sigma.lock().unwrap_or_else(|err| err.into_inner()) =
+					result.blur_sigma;
+				CalibrationStatus::Done(result)
+			}
+			None => CalibrationStatus::Failed,
+		};
+		*calibration = None;
+		event_sender.submit_command(Selector::new(S_CALIBRATION), status, Target::Auto)?;
+		Ok(())
+	}
 }
 
 impl std::fmt::Debug for CameraConnector {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 		f.debug_struct("CameraConnector")
 			.field("event_sender", &"<object>")
-			.field("pick_receiver", &"<object>")
+			.field("request_receiver", &"<object>")
+			.field("source", &self.source)
+			.field("sinks", &self.sinks.len())
+			.field("paused", &self.paused)
+			.field("frame_policy", &self.frame_policy)
+			.field("processing_width", &self.processing_width)
+			.field("color_mode", &self.color_mode)
+			.field("threshold", &self.threshold)
+			.field("blur_sigma", &self.blur_sigma)
+			.field("morphology", &self.morphology)
+			.field("stabilise", &self.stabilise)
+			.field("compensate_illumination", &self.compensate_illumination)
+			.field("motion_limit", &self.motion_limit)
+			.field("pipelined", &self.pipelined)
 			.finish()
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use druid::{widget::Label, AppLauncher, WindowDesc};
+	use image::Rgb;
+
+	use super::*;
+
+	/// Output recording the detected points.
+	struct PointRecorder(mpsc::Sender<(u32, u32)>);
+
+	impl EventSink for PointRecorder {
+		fn handle(&mut self, event: PipelineEvent<'_>) -> Result<()> {
+			if let PipelineEvent::Point { position, .. } = event {
+				self.0.send(position)?;
+			}
+			Ok(())
+		}
+	}
+
+	#[test]
+	fn pipelined_points_stay_in_order() {
+		// The UI is never launched, its events are only queued.
+		let launcher = AppLauncher::with_window(WindowDesc::new(Label::<()>::new("")));
+		let mut event_sender = launcher.get_external_handle();
+
+		let (frame_sender, frame_receiver) = mailbox::mailbox(FramePolicy::Block);
+		let camera = thread::spawn(move || {
+			// The square moves to the left, so to the right in the mirrored frames.
+			for step in 0..8 {
+				let left = 200 - step * 20;
+				let frame = RgbImage::from_fn(320, 240, |x, y| {
+					if (left..left + 20).contains(&x) && (100..120).contains(&y) {
+						Rgb([0xFF; 3])
+					} else {
+						Rgb([0x20; 3])
+					}
+				});
+				frame_sender.send(Capture::Frame(frame, Instant::now())).unwrap();
+			}
+		});
+
+		let pool = FramePool::new();
+		let preprocessor = Preprocessor::new(None, ColorMode::Luma, pool.clone());
+		let settings = AnalysisSettings {
+			threshold: Arc::default(),
+			blur_sigma: preprocessor.blur_sigma.clone(),
+			calibrate: Arc::new(AtomicBool::new(false)),
+			morphology: None,
+			stabilise: false,
+			compensate_illumination: false,
+			motion_limit: None,
+		};
+		let (point_sender, point_receiver) = mpsc::channel();
+		let mut sinks: Vec<Box<dyn EventSink>> = vec![Box::new(PointRecorder(point_sender))];
+		CameraConnector::process_frames(
+			preprocessor,
+			frame_receiver,
+			true,
+			&settings,
+			&mut event_sender,
+			&mut sinks,
+			&pool,
+		)
+		.unwrap();
+		camera.join().unwrap();
+
+		drop(sinks);
+		let points: Vec<(u32, u32)> = point_receiver.iter().collect();
+		assert_eq!(points.len(), 7, "{points:?}");
+		assert!(points.windows(2).all(|pair| pair[0].0 < pair[1].0), "{points:?}");
+	}
+}
@@ -0,0 +1,285 @@
+//! Morphological operations on the binary motion mask, to remove speckles of
+//! noise and close gaps in moving objects.
+
+use std::{fmt, str::FromStr};
+
+use color_eyre::{
+	eyre::{bail, eyre},
+	Report, Result,
+};
+use image::GrayImage;
+use rayon::prelude::*;
+
+use super::pool::FramePool;
+
+/// Largest supported radius of the structuring element.
+pub const MAX_RADIUS: u8 = 254;
+
+/// Morphological operation on the mask.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+	/// Shrink moving regions, removing those smaller than the element.
+	Erode,
+	/// Grow moving regions, filling gaps smaller than the element.
+	Dilate,
+	/// Erode, then dilate, which removes speckles but keeps larger regions.
+	Open,
+	/// Dilate, then erode, which fills gaps but keeps the regions' size.
+	Close,
+}
+
+/// Shape of the structuring element.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Element {
+	/// Square with sides of twice the radius plus one.
+	Square,
+	/// Horizontal and vertical line through the center.
+	Cross,
+	/// Pixels within the radius of the center.
+	Disc,
+}
+
+impl Element {
+	/// Half width of the element's row at each vertical offset from `-radius`
+	/// to `radius`.
+	fn half_widths(self, radius: u8) -> Vec<u8> {
+		let radius = i32::from(radius);
+		(-radius..=radius)
+			.map(|dy| match self {
+				Self::Square => radius as u8,
+				Self::Cross if dy == 0 => radius as u8,
+				Self::Cross => 0,
+				Self::Disc => f64::from(radius * radius - dy * dy).sqrt().floor() as u8,
+			})
+			.collect()
+	}
+}
+
+/// Morphological cleanup of the motion mask.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Morphology {
+	/// Operation to apply.
+	pub operation: Operation,
+	/// Shape of the structuring element.
+	pub element: Element,
+	/// Radius of the structuring element, at most [`MAX_RADIUS`].
+	pub radius: u8,
+}
+
+impl Morphology {
+	/// Apply the operation to the mask, where non-zero pixels are set. The
+	/// result is `0xFF` for set pixels and zero otherwise. Pixels outside of
+	/// the mask neither erode nor dilate it. The buffers are taken from and
+	/// returned to the pool.
+	pub fn apply(&self, mask: GrayImage, pool: &FramePool) -> GrayImage {
+		let half_widths = self.element.half_widths(self.radius.min(MAX_RADIUS));
+		let steps: &[bool] = match self.operation {
+			Operation::Erode => &[false],
+			Operation::Dilate => &[true],
+			Operation::Open => &[false, true],
+			Operation::Close => &[true, false],
+		};
+		steps.iter().fold(mask, |mask, dilate| {
+			let result = erode(&mask, &half_widths, *dilate, pool);
+			pool.recycle_image(mask);
+			result
+		})
+	}
+}
+
+impl FromStr for Morphology {
+	type Err = Report;
+
+	/// Parse `<operation>:<element>:<radius>`, e.g. `open:disc:2`.
+	fn from_str(s: &str) -> Result<Self> {
+		let mut parts = s.split(':');
+		let (Some(operation), Some(element), Some(radius), None) =
+			(parts.next(), parts.next(), parts.next(), parts.next())
+		else {
+			bail!("Expected <operation>:<element>:<radius>, got {s}");
+		};
+		let operation = match operation {
+			"erode" => Operation::Erode,
+			"dilate" => Operation::Dilate,
+			"open" => Operation::Open,
+			"close" => Operation::Close,
+			_ => bail!("Unknown operation {operation}, expected erode, dilate, open or close"),
+		};
+		let element = match element {
+			"square" => Element::Square,
+			"cross" => Element::Cross,
+			"disc" => Element::Disc,
+			_ => bail!("Unknown structuring element {element}, expected square, cross or disc"),
+		};
+		let radius = radius.parse()?;
+		if radius > MAX_RADIUS {
+			return Err(eyre!("Radius must be at most {MAX_RADIUS}"));
+		}
+		Ok(Self { operation, element, radius })
+	}
+}
+
+impl fmt::Display for Morphology {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let operation = match self.operation {
+			Operation::Erode => "erode",
+			Operation::Dilate => "dilate",
+			Operation::Open => "open",
+			Operation::Close => "close",
+		};
+		let element = match self.element {
+			Element::Square => "square",
+			Element::Cross => "cross",
+			Element::Disc => "disc",
+		};
+		write!(f, "{operation}:{element}:{}", self.radius)
+	}
+}
+
+/// Erode the mask with the element of the given half widths. Dilating is
+/// eroding the inverted mask, as the elements are symmetric, so with `dilate`
+/// the mask is inverted before and after.
+///
+/// Each row of the element fits at a pixel, if the run of set pixels through
+/// it reaches far enough to both sides. So the reach of each pixel is computed
+/// once, and each output row combines the reach of the rows around it.
+fn erode(mask: &GrayImage, half_widths: &[u8], dilate: bool, pool: &FramePool) -> GrayImage {
+	let (width, height) = mask.dimensions();
+	let width = width as usize;
+	let radius = half_widths.len() / 2;
+
+	// Length of the run of set pixels to the nearer end, including the pixel
+	// itself. Outside of the mask counts as set.
+	let mut reach: GrayImage = pool.image(mask.width(), height);
+	reach.par_chunks_exact_mut(width).zip(mask.par_chunks_exact(width)).for_each(
+		|(reach_row, row)| {
+			let mut run = u8::MAX;
+			for (reach, value) in reach_row.iter_mut().zip(row) {
+				run = if (*value != 0) != dilate { run.saturating_add(1) } else { 0 };
+				*reach = run;
+			}
+			let mut run = u8::MAX;
+			for (reach, value) in reach_row.iter_mut().zip(row).rev() {
+				run = if (*value != 0) != dilate { run.saturating_add(1) } else { 0 };
+				*reach = (*reach).min(run);
+			}
+		},
+	);
+
+	let mut eroded: GrayImage = pool.image(mask.width(), height);
+	eroded.par_chunks_exact_mut(width).enumerate().for_each(|(y, out_row)| {
+		out_row.fill(0xFF);
+		for (offset, half_width) in half_widths.iter().enumerate() {
+			// Rows outside of the mask count as set.
+			let Some(source_y) = (y + offset).checked_sub(radius).filter(|y| *y < height as usize)
+			else {
+				continue;
+			};
+			let reach_row = &reach.as_raw()[source_y * width..(source_y + 1) * width];
+			for (out, reach) in out_row.iter_mut().zip(reach_row) {
+				if reach <= half_width {
+					*out = 0x00;
+				}
+			}
+		}
+		if dilate {
+			for out in out_row {
+				*out = !*out;
+			}
+		}
+	});
+	pool.recycle_image(reach);
+	eroded
+}
+
+#[cfg(test)]
+mod tests {
+	use image::Luma;
+
+	use super::*;
+	use crate::camera::testing::Lcg;
+
+	/// Erode or dilate with the element directly, by checking every pixel of
+	/// the element.
+	fn reference(mask: &GrayImage, element: Element, radius: u8, dilate: bool) -> GrayImage {
+		let radius = i64::from(radius);
+		let (width, height) = mask.dimensions();
+		GrayImage::from_fn(width, height, |x, y| {
+			let mut covered =
+				(-radius..=radius).flat_map(|dy| (-radius..=radius).map(move |dx| (dx, dy)));
+			let in_element = |(dx, dy): &(i64, i64)| match element {
+				Element::Square => true,
+				Element::Cross => *dx == 0 || *dy == 0,
+				Element::Disc => dx * dx + dy * dy <= radius * radius,
+			};
+			let is_set = |(dx, dy): (i64, i64)| {
+				let (x, y) = (i64::from(x) + dx, i64::from(y) + dy);
+				if x < 0 || y < 0 || x >= i64::from(width) || y >= i64::from(height) {
+					// Outside of the mask neither erodes nor dilates.
+					return !dilate;
+				}
+				mask.get_pixel(x as u32, y as u32).0[0] != 0
+			};
+			let set = if dilate {
+				covered.any(|offset| in_element(&offset) && is_set(offset))
+			} else {
+				covered.all(|offset| !in_element(&offset) || is_set(offset))
+			};
+			Luma([if set { 0xFF } else { 0x00 }])
+		})
+	}
+
+	#[test]
+	fn like_reference() {
+		let mut lcg = Lcg::new();
+
+		let pool = FramePool::new();
+		for (width, height) in [(1, 1), (7, 3), (40, 30)] {
+			let mask = GrayImage::from_fn(width, height, |_x, _y| {
+				Luma([if lcg.next_u8() < 160 { 0xFF } else { 0x00 }])
+			});
+			for element in [Element::Square, Element::Cross, Element::Disc] {
+				for radius in 0..=3 {
+					let eroded = reference(&mask, element, radius, false);
+					let dilated = reference(&mask, element, radius, true);
+					let morphology = |operation| {
+						Morphology { operation, element, radius }.apply(mask.clone(), &pool)
+					};
+					assert_eq!(morphology(Operation::Erode), eroded);
+					assert_eq!(morphology(Operation::Dilate), dilated);
+					assert_eq!(
+						morphology(Operation::Open),
+						reference(&eroded, element, radius, true)
+					);
+					assert_eq!(
+						morphology(Operation::Close),
+						reference(&dilated, element, radius, false)
+					);
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn opening_removes_speckles() {
+		let mut mask = GrayImage::new(32, 32);
+		// Speckle of noise and a moving object.
+		mask.put_pixel(3, 3, Luma([0xFF]));
+		for y in 10..20 {
+			for x in 12..22 {
+				mask.put_pixel(x, y, Luma([0xFF]));
+			}
+		}
+		let opening = Morphology { operation: Operation::Open, element: Element::Disc, radius: 2 };
+		let opened = opening.apply(mask.clone(), &FramePool::new());
+		assert_eq!(opened.get_pixel(3, 3).0, [0x00]);
+		// Corners of the square are rounded off by the disc.
+		assert_eq!(opened.get_pixel(21, 15).0, [0xFF]);
+		assert_eq!(opened.get_pixel(21, 10).0, [0x00]);
+
+		assert_eq!("open:disc:2".parse::<Morphology>().unwrap(), opening);
+		assert_eq!(opening.to_string(), "open:disc:2");
+		assert!("open:disc".parse::<Morphology>().is_err());
+		assert!("open:ring:2".parse::<Morphology>().is_err());
+	}
+}
@@ -0,0 +1,139 @@
+//! Pool of recycled frame buffers, so that the pipeline does not allocate new
+//! buffers for every frame.
+
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use image::{ImageBuffer, Pixel};
+
+use super::analysis::Frame;
+
+/// Maximum number of buffers kept in the pool. Further returned buffers are
+/// freed.
+const MAX_BUFFERS: usize = 16;
+
+/// Pool of sample buffers for frames, shared by the pipeline and the UI.
+/// Clones refer to the same pool.
+#[derive(Debug, Clone, Default)]
+pub struct FramePool {
+	buffers: Arc<Mutex<Vec<Vec<u8>>>>,
+}
+
+impl FramePool {
+	/// Create new empty pool.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Lock the buffers. A poisoned lock is still used, as the buffers stay
+	/// valid.
+	fn lock(&self) -> MutexGuard<'_, Vec<Vec<u8>>> {
+		self.buffers.lock().unwrap_or_else(|err| err.into_inner())
+	}
+
+	/// Take a buffer of the given length from the pool, or allocate a new one
+	/// if no buffer is large enough. The contents are unspecified.
+	pub fn take(&self, len: usize) -> Vec<u8> {
+		let recycled = {
+			// Use the smallest buffer that fits, so larger frames find theirs.
+			let mut buffers = self.lock();
+			let index = buffers
+				.iter()
+				.enumerate()
+				.filter(|(_, buffer)| buffer.capacity() >= len)
+				.min_by_key(|(_, buffer)| buffer.capacity())
+				.map(|(index, _)| index);
+			index.map(|index| buffers.swap_remove(index))
+		};
+		match recycled {
+			Some(mut buffer) => {
+				buffer.resize(len, 0);
+				buffer
+			}
+			None => vec![0; len],
+		}
+	}
+
+	/// Take an image of the given size from the pool. The contents are
+	/// unspecified.
+	pub fn image<P>(&self, width: u32, height: u32) -> ImageBuffer<P, Vec<u8>>
+	where
+		P: Pixel<Subpixel = u8> + 'static,
+	{
+		let len = width as usize * height as usize * usize::from(P::CHANNEL_COUNT);
+		ImageBuffer::from_raw(width, height, self.take(len)).expect("buffer fits the image")
+	}
+
+	/// Return a buffer to the pool for reuse.
+	pub fn recycle(&self, buffer: Vec<u8>) {
+		if buffer.capacity() == 0 {
+			return;
+		}
+		let mut buffers = self.lock();
+		if buffers.len() < MAX_BUFFERS {
+			buffers.push(buffer);
+		}
+	}
+
+	/// Return the buffer of an image to the pool for reuse.
+	pub fn recycle_image<P>(&self, image: ImageBuffer<P, Vec<u8>>)
+	where
+		P: Pixel<Subpixel = u8> + 'static,
+	{
+		self.recycle(image.into_raw());
+	}
+
+	/// Return the buffer of a frame to the pool for reuse.
+	pub fn recycle_frame(&self, frame: Frame) {
+		match frame {
+			Frame::Rgb(image) => self.recycle_image(image),
+			Frame::Luma(image) => self.recycle_image(image),
+		}
+	}
+
+	/// Copy the frame into a buffer of the pool.
+	pub fn copy_frame(&self, frame: &Frame) -> Frame {
+		match frame {
+			Frame::Rgb(image) => Frame::Rgb(self.copy_image(image)),
+			Frame::Luma(image) => Frame::Luma(self.copy_image(image)),
+		}
+	}
+
+	/// Copy the image into a buffer of the pool.
+	fn copy_image<P>(&self, image: &ImageBuffer<P, Vec<u8>>) -> ImageBuffer<P, Vec<u8>>
+	where
+		P: Pixel<Subpixel = u8> + 'static,
+	{
+		let mut copy = self.image(image.width(), image.height());
+		copy.copy_from_slice(image.as_raw());
+		copy
+	}
+}
+
+/// Frame, which returns its buffer to the pool when dropped, e.g. after the UI
+/// displayed it.
+#[derive(Debug)]
+pub struct PooledFrame {
+	/// Always set until dropped.
+	frame: Option<Frame>,
+	pool: FramePool,
+}
+
+impl PooledFrame {
+	/// Wrap the frame to return its buffer to the given pool.
+	pub fn new(frame: Frame, pool: FramePool) -> Self {
+		Self { frame: Some(frame), pool }
+	}
+
+	/// The wrapped frame.
+	pub fn frame(&self) -> &Frame {
+		self.frame.as_ref().expect("frame is set until dropped")
+	}
+}
+
+impl Drop for PooledFrame {
+	fn drop(&mut self) {
+		if let Some(frame) = self.frame.take() {
+			self.pool.recycle_frame(frame);
+		}
+	}
+}
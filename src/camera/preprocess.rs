@@ -0,0 +1,195 @@
+//! First stage of the frame processor, which mirrors frames and processes them
+//! for the analysis. Can run on its own thread, so that the next frame is
+//! preprocessed while the current one is analysed.
+
+use std::{
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		mpsc::SyncSender,
+		Arc, Mutex,
+	},
+	time::{Duration, Instant},
+};
+
+use image::RgbImage;
+
+use super::{
+	analysis::{self, ColorMode, Frame},
+	mailbox::MailboxReceiver,
+	pool::FramePool,
+	stats::Metric,
+	Capture,
+};
+use crate::output::CameraStatus;
+
+/// Message from the preprocessing stage to the analysis.
+#[derive(Debug)]
+pub(super) enum Preprocessed {
+	/// Frame prepared for the analysis.
+	Frame(PreparedFrame),
+	/// Changed status of the source.
+	Status(CameraStatus),
+	/// A frame arrived while paused and was dropped.
+	Paused,
+}
+
+/// Frame after the preprocessing stage.
+#[derive(Debug)]
+pub struct PreparedFrame {
+	/// Mirrored frame as captured.
+	pub original: Frame,
+	/// Frame in the color mode and resolution of the analysis, with reduced
+	/// noise.
+	pub processed: Frame,
+	/// Factor the processed frame is downscaled by.
+	pub factor: u32,
+	/// Standard deviation of the Gaussian blur the frame was processed with.
+	pub blur_sigma: f32,
+	/// Time the frame was captured.
+	pub captured: Instant,
+	/// Number of frames dropped before this one.
+	pub dropped: u64,
+	/// Times of the preprocessing stages.
+	pub times: [(Metric, Duration); 3],
+}
+
+/// Preprocessing stage with its settings.
+#[derive(Debug)]
+pub struct Preprocessor {
+	/// Whether the pipeline is paused, so frames are dropped.
+	pub(super) paused: Arc<AtomicBool>,
+	/// Maximum width to run the analysis on.
+	pub(super) processing_width: Option<u32>,
+	/// Color channels to run the analysis on.
+	pub(super) color_mode: ColorMode,
+	/// Standard deviation of the Gaussian blur, shared with the pipeline
+	/// control.
+	pub(super) blur_sigma: Arc<Mutex<f32>>,
+	/// Pool to take frame buffers from.
+	pub(super) pool: FramePool,
+}
+
+impl Preprocessor {
+	/// Create new preprocessor with the default blur, which is not paused.
+	pub fn new(processing_width: Option<u32>, color_mode: ColorMode, pool: FramePool) -> Self {
+		Self {
+			paused: Arc::new(AtomicBool::new(false)),
+			processing_width,
+			color_mode,
+			blur_sigma: Arc::new(Mutex::new(analysis::DEFAULT_BLUR_SIGMA)),
+			pool,
+		}
+	}
+
+	/// Receive the next message and preprocess it. Returns `None` when the
+	/// frame receiver is gone.
+	pub(super) fn next(&self, frame_receiver: &MailboxReceiver<Capture>) -> Option<Preprocessed> {
+		let (frame, captured) = match frame_receiver.recv()? {
+			Capture::Frame(frame, captured) => (frame, captured),
+			Capture::Status(status) => return Some(Preprocessed::Status(status)),
+		};
+		if self.paused.load(Ordering::Relaxed) {
+			self.pool.recycle_image(frame);
+			return Some(Preprocessed::Paused);
+		}
+		let dropped = frame_receiver.take_dropped();
+		Some(Preprocessed::Frame(self.prepare(frame, captured, dropped)))
+	}
+
+	/// Preprocess the captured frame: mirror it, then convert it to the
+	/// analysis' color mode and resolution and process it to reduce noise and
+	/// such.
+	pub fn prepare(&self, mut frame: RgbImage, captured: Instant, dropped: u64) -> PreparedFrame {
+		let start = Instant::now();
+		analysis::flip_in_place(&mut frame);
+		let flipped = Instant::now();
+		let factor = self
+			.processing_width
+			.map_or(1, |max_width| analysis::downscale_factor(frame.width(), max_width));
+		let converted = analysis::prepare_frame(&frame, self.color_mode, factor, &self.pool);
+		let original = Frame::Rgb(frame);
+		let prepared = Instant::now();
+		let blur_sigma = *self.blur_sigma.lock().unwrap_or_else(|err| err.into_inner());
+		let processed = converted.as_ref().unwrap_or(&original).process(blur_sigma, &self.pool);
+		if let Some(converted) = converted {
+			self.pool.recycle_frame(converted);
+		}
+
+		PreparedFrame {
+			original,
+			processed,
+			factor,
+			blur_sigma,
+			captured,
+			dropped,
+			times: [
+				(Metric::Flip, flipped - start),
+				(Metric::Convert, prepared - flipped),
+				(Metric::Process, prepared.elapsed()),
+			],
+		}
+	}
+
+	/// Preprocess all frames and send them to the analysis, until either end
+	/// is gone. The channel keeps the frames in order.
+	pub(super) fn run(
+		&self,
+		frame_receiver: &MailboxReceiver<Capture>,
+		sender: &SyncSender<Preprocessed>,
+	) {
+		while let Some(preprocessed) = self.next(frame_receiver) {
+			if sender.send(preprocessed).is_err() {
+				break;
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{sync::mpsc, thread};
+
+	use super::*;
+	use crate::camera::{mailbox, FramePolicy};
+
+	#[test]
+	fn pipelined_frames_stay_in_order() {
+		let (frame_sender, frame_receiver) = mailbox::mailbox(FramePolicy::Block);
+		let start = Instant::now();
+		let captured: Vec<Instant> =
+			(0..8).map(|index| start + Duration::from_millis(index)).collect();
+		let sent = captured.clone();
+		let camera = thread::spawn(move || {
+			frame_sender.send(Capture::Status(CameraStatus::Streaming)).unwrap();
+			for (index, captured) in sent.into_iter().enumerate() {
+				let frame = RgbImage::from_pixel(32, 16, image::Rgb([index as u8 * 0x20; 3]));
+				frame_sender.send(Capture::Frame(frame, captured)).unwrap();
+			}
+		});
+
+		let preprocessor = Preprocessor {
+			paused: Arc::new(AtomicBool::new(false)),
+			processing_width: Some(16),
+			color_mode: ColorMode::Luma,
+			blur_sigma: Arc::new(Mutex::new(analysis::DEFAULT_BLUR_SIGMA)),
+			pool: FramePool::new(),
+		};
+		let (sender, receiver) = mpsc::sync_channel(1);
+		let stage = thread::spawn(move || preprocessor.run(&frame_receiver, &sender));
+
+		assert!(matches!(receiver.recv().unwrap(), Preprocessed::Status(CameraStatus::Streaming)));
+		for expected in captured {
+			match receiver.recv().unwrap() {
+				Preprocessed::Frame(frame) => {
+					assert_eq!(frame.captured, expected);
+					assert_eq!(frame.factor, 2);
+					assert_eq!(frame.processed.dimensions(), (16, 8));
+				}
+				other => panic!("Expected frame, got {other:?}"),
+			}
+		}
+		camera.join().unwrap();
+		stage.join().unwrap();
+		assert!(receiver.recv().is_err());
+	}
+}
@@ -0,0 +1,58 @@
+//! Profiles persisting the settings per camera.
+
+use std::{fs, io, path::PathBuf};
+
+use color_eyre::{eyre::eyre, Result};
+use serde::{Deserialize, Serialize};
+
+use super::{controls::ControlSetting, format::FormatChoice};
+
+/// Settings of a camera, stored as JSON in the config directory.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+	/// Format to open the camera with.
+	pub format: Option<FormatChoice>,
+	/// Controls to set after opening the camera, in order.
+	pub controls: Vec<ControlSetting>,
+}
+
+impl Profile {
+	/// Load the profile of the given name, or the default if there is none
+	/// yet.
+	pub fn load(name: &str) -> Result<Self> {
+		match fs::read(Self::path(name)?) {
+			Ok(json) => Ok(serde_json::from_slice(&json)?),
+			Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+			Err(err) => Err(err.into()),
+		}
+	}
+
+	/// Save the profile under the given name.
+	pub fn save(&self, name: &str) -> Result<()> {
+		let path = Self::path(name)?;
+		if let Some(dir) = path.parent() {
+			fs::create_dir_all(dir)?;
+		}
+		fs::write(path, serde_json::to_vec_pretty(self)?)?;
+		Ok(())
+	}
+
+	/// Remember the given control setting, replacing a previous setting of
+	/// the same control.
+	pub fn set_control(&mut self, setting: ControlSetting) {
+		match self.controls.iter_mut().find(|control| control.kind == setting.kind) {
+			Some(control) => *control = setting,
+			None => self.controls.push(setting),
+		}
+	}
+
+	/// Path of the profile with the given name, which is usually the camera
+	/// name.
+	fn path(name: &str) -> Result<PathBuf> {
+		let file_name: String =
+			name.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect();
+		let dir = dirs::config_dir().ok_or_else(|| eyre!("No config directory available"))?;
+		Ok(dir.join("webcam-mouse").join("profiles").join(file_name + ".json"))
+	}
+}
@@ -0,0 +1,70 @@
+//! Frame source replaying a directory of images, e.g. for testing the
+//! tracking without a camera.
+
+use std::{
+	fs,
+	path::{Path, PathBuf},
+};
+
+use color_eyre::{eyre::ensure, Result};
+use image::RgbImage;
+
+use super::source::{FrameClock, FrameSource};
+
+/// Images of a directory, replayed in the order of their file names.
+#[derive(Debug)]
+pub struct ReplaySource {
+	/// Paths of the images.
+	paths: Vec<PathBuf>,
+	/// Index of the next image, starting again at the first one after the
+	/// last.
+	next: usize,
+	clock: FrameClock,
+}
+
+impl ReplaySource {
+	/// Open the images in the given directory. Files which are no images are
+	/// skipped.
+	pub fn open(dir: &Path) -> Result<Self> {
+		let mut paths = Vec::new();
+		for entry in fs::read_dir(dir)? {
+			let path = entry?.path();
+			if path.is_file() && image::ImageFormat::from_path(&path).is_ok() {
+				paths.push(path);
+			}
+		}
+		ensure!(!paths.is_empty(), "No images found in {}", dir.display());
+		paths.sort();
+
+		Ok(Self { paths, next: 0, clock: FrameClock::new() })
+	}
+}
+
+impl FrameSource for ReplaySource {
+	fn frame(&mut self) -> Result<RgbImage> {
+		self.clock.wait();
+		let path = &self.paths[self.next];
+		self.next = (self.next + 1) % self.paths.len();
+		Ok(image::open(path)?.into_rgb8())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn replay_images_in_order() {
+		let dir = std::env::temp_dir().join(format!("webcam-mouse-replay-{}", std::process::id()));
+		fs::create_dir_all(&dir).unwrap();
+		RgbImage::from_pixel(4, 3, image::Rgb([1, 2, 3])).save(dir.join("b.png")).unwrap();
+		RgbImage::from_pixel(2, 2, image::Rgb([4, 5, 6])).save(dir.join("a.png")).unwrap();
+		fs::write(dir.join("notes.txt"), "not an image").unwrap();
+
+		let mut source = ReplaySource::open(&dir).unwrap();
+		let dimensions: Vec<_> = (0..3).map(|_| source.frame().unwrap().dimensions()).collect();
+		fs::remove_dir_all(&dir).unwrap();
+
+		assert_eq!(dimensions, vec![(2, 2), (4, 3), (2, 2)]);
+	}
+}
@@ -0,0 +1,168 @@
+//! Vectorised kernels of the image analysis, selected at runtime by the
+//! features of the CPU with a scalar fallback.
+
+/// Turn the previous row into the thresholded absolute difference to the
+/// current row. The thresholds of the channels repeat along the row, so the
+/// row length must be a multiple of their number. Differences below their
+/// threshold become zero. Returns whether any value is left, i.e. the row has
+/// motion.
+pub fn difference_row(previous: &mut [u8], current: &[u8], thresholds: &[u8]) -> bool {
+	debug_assert_eq!(previous.len(), current.len());
+	debug_assert_eq!(previous.len() % thresholds.len(), 0);
+
+	#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+	{
+		if is_x86_feature_detected!("avx2") {
+			// SAFETY: The CPU supports AVX2.
+			return unsafe { x86::difference_row_avx2(previous, current, thresholds) };
+		}
+		if is_x86_feature_detected!("sse2") {
+			// SAFETY: The CPU supports SSE2.
+			return unsafe { x86::difference_row_sse2(previous, current, thresholds) };
+		}
+	}
+
+	difference_row_scalar(previous, current, thresholds)
+}
+
+/// Scalar version of [`difference_row`].
+pub fn difference_row_scalar(previous: &mut [u8], current: &[u8], thresholds: &[u8]) -> bool {
+	let mut motion = 0;
+	for ((prev, cur), threshold) in previous.iter_mut().zip(current).zip(thresholds.iter().cycle())
+	{
+		let diff = prev.abs_diff(*cur);
+		*prev = if diff >= *threshold { diff } else { 0 };
+		motion |= *prev;
+	}
+	motion != 0
+}
+
+/// Thresholds of the channels repeated to fill three vectors of `LEN` bytes,
+/// so that each block of three vectors starts with the first channel, for one
+/// and three channels alike.
+fn threshold_pattern<const LEN: usize>(thresholds: &[u8]) -> [[u8; LEN]; 3] {
+	let mut pattern = [[0; LEN]; 3];
+	for (index, value) in pattern.iter_mut().flatten().enumerate() {
+		*value = thresholds[index % thresholds.len()];
+	}
+	pattern
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod x86 {
+	//! Kernels using SSE2 and AVX2.
+
+	#[cfg(target_arch = "x86")]
+	use std::arch::x86::*;
+	#[cfg(target_arch = "x86_64")]
+	use std::arch::x86_64::*;
+
+	use super::{difference_row_scalar, threshold_pattern};
+
+	/// [`super::difference_row`] on 32 bytes at once.
+	///
+	/// # Safety
+	/// The CPU must support AVX2.
+	#[target_feature(enable = "avx2")]
+	pub unsafe fn difference_row_avx2(
+		previous: &mut [u8],
+		current: &[u8],
+		thresholds: &[u8],
+	) -> bool {
+		let len = previous.len().min(current.len());
+		let blocks = len / 96 * 96;
+		let pattern = threshold_pattern::<32>(thresholds);
+		let thresholds_vec = pattern.map(|part| _mm256_loadu_si256(part.as_ptr().cast()));
+		let mut motion = _mm256_setzero_si256();
+		for offset in (0..blocks).step_by(32) {
+			let prev_ptr = previous.as_mut_ptr().add(offset).cast::<__m256i>();
+			let prev = _mm256_loadu_si256(prev_ptr);
+			let cur = _mm256_loadu_si256(current.as_ptr().add(offset).cast());
+			// Saturating subtraction is zero in one direction, so combining both
+			// directions gives the absolute difference.
+			let diff = _mm256_or_si256(_mm256_subs_epu8(prev, cur), _mm256_subs_epu8(cur, prev));
+			// The difference is at least the threshold, if it is the maximum.
+			let threshold = thresholds_vec[offset / 32 % 3];
+			let kept = _mm256_cmpeq_epi8(_mm256_max_epu8(diff, threshold), diff);
+			let diff = _mm256_and_si256(diff, kept);
+			_mm256_storeu_si256(prev_ptr, diff);
+			motion = _mm256_or_si256(motion, diff);
+		}
+		let rest =
+			difference_row_scalar(&mut previous[blocks..len], &current[blocks..len], thresholds);
+		_mm256_testz_si256(motion, motion) == 0 || rest
+	}
+
+	/// [`super::difference_row`] on 16 bytes at once.
+	///
+	/// # Safety
+	/// The CPU must support SSE2.
+	#[target_feature(enable = "sse2")]
+	pub unsafe fn difference_row_sse2(
+		previous: &mut [u8],
+		current: &[u8],
+		thresholds: &[u8],
+	) -> bool {
+		let len = previous.len().min(current.len());
+		let blocks = len / 48 * 48;
+		let pattern = threshold_pattern::<16>(thresholds);
+		let thresholds_vec = pattern.map(|part| _mm_loadu_si128(part.as_ptr().cast()));
+		let mut motion = _mm_setzero_si128();
+		for offset in (0..blocks).step_by(16) {
+			let prev_ptr = previous.as_mut_ptr().add(offset).cast::<__m128i>();
+			let prev = _mm_loadu_si128(prev_ptr);
+			let cur = _mm_loadu_si128(current.as_ptr().add(offset).cast());
+			let diff = _mm_or_si128(_mm_subs_epu8(prev, cur), _mm_subs_epu8(cur, prev));
+			let threshold = thresholds_vec[offset / 16 % 3];
+			let kept = _mm_cmpeq_epi8(_mm_max_epu8(diff, threshold), diff);
+			let diff = _mm_and_si128(diff, kept);
+			_mm_storeu_si128(prev_ptr, diff);
+			motion = _mm_or_si128(motion, diff);
+		}
+		let rest =
+			difference_row_scalar(&mut previous[blocks..len], &current[blocks..len], thresholds);
+		let zero = _mm_cmpeq_epi8(motion, _mm_setzero_si128());
+		_mm_movemask_epi8(zero) != 0xFFFF || rest
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::camera::testing::Lcg;
+
+	#[test]
+	fn difference_like_scalar() {
+		let mut lcg = Lcg::new();
+		let mut random = || lcg.next_u8();
+
+		for thresholds in [&[32][..], &[0], &[255], &[8, 32, 64]] {
+			for len in [0, 3, 15, 48, 93, 96, 99, 300, 1920 * 3] {
+				let previous: Vec<u8> = (0..len).map(|_| random()).collect();
+				let current: Vec<u8> = (0..len).map(|_| random()).collect();
+				let mut expected = previous.clone();
+				let expected_motion = difference_row_scalar(&mut expected, &current, thresholds);
+				let mut actual = previous.clone();
+				assert_eq!(difference_row(&mut actual, &current, thresholds), expected_motion);
+				assert_eq!(actual, expected);
+				#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+				if is_x86_feature_detected!("sse2") {
+					let mut actual = previous.clone();
+					// SAFETY: The CPU supports SSE2.
+					let motion =
+						unsafe { x86::difference_row_sse2(&mut actual, &current, thresholds) };
+					assert_eq!(motion, expected_motion);
+					assert_eq!(actual, expected);
+				}
+			}
+		}
+
+		// Differences below the threshold of their channel are noise.
+		let current = [0x80; 6];
+		let mut previous = [0x80 + 7, 0x80 + 31, 0x80 - 63, 0x80 + 8, 0x80, 0x80 - 64];
+		assert!(difference_row(&mut previous, &current, &[8, 32, 64]));
+		assert_eq!(previous, [0, 0, 0, 8, 0, 64]);
+		let mut still: Vec<u8> = current.iter().map(|value| value ^ 0x07).collect();
+		assert!(!difference_row(&mut still, &current, &[8]));
+	}
+}
@@ -0,0 +1,30 @@
+//! Smoothing of the detected point.
+
+/// Weight of a new point in the exponential moving average.
+const SMOOTHING_FACTOR: f32 = 0.3;
+
+/// Exponential moving average of the detected points.
+#[derive(Debug, Default)]
+pub struct PointSmoother {
+	point: Option<(f32, f32)>,
+}
+
+impl PointSmoother {
+	/// Create new point smoother.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Add the next detected point and return the smoothed point.
+	pub fn update(&mut self, point: (u32, u32)) -> (f32, f32) {
+		let point = (point.0 as f32, point.1 as f32);
+		let smoothed = match self.point {
+			Some((x, y)) => {
+				(x + SMOOTHING_FACTOR * (point.0 - x), y + SMOOTHING_FACTOR * (point.1 - y))
+			}
+			None => point,
+		};
+		self.point = Some(smoothed);
+		smoothed
+	}
+}
@@ -0,0 +1,182 @@
+//! Sources of frames for the pipeline.
+
+use std::{
+	path::PathBuf,
+	thread,
+	time::{Duration, Instant},
+};
+
+use color_eyre::{eyre::bail, Result};
+use image::RgbImage;
+use nokhwa::{Camera, KnownCameraControlFlag};
+
+use super::{
+	controls::{ControlInfo, ControlSetting},
+	format::{CameraFormats, FormatChoice},
+	mjpeg::MjpegSource,
+	pool::FramePool,
+	profile::Profile,
+	replay::ReplaySource,
+	synthetic::SyntheticSource,
+};
+
+/// Interval between frames of sources which are not paced by hardware.
+const FRAME_INTERVAL: Duration = Duration::from_millis(33);
+
+/// Source of frames.
+pub trait FrameSource {
+	/// Retrieve the next frame, blocking until it is available.
+	fn frame(&mut self) -> Result<RgbImage>;
+
+	/// Retrieve the next frame like [`Self::frame`], writing it into a buffer of
+	/// the pool if the source supports that.
+	fn frame_into(&mut self, _pool: &FramePool) -> Result<RgbImage> {
+		self.frame()
+	}
+
+	/// Name of the source's own profile, if it supports profiles.
+	fn profile_name(&self) -> Option<String> {
+		None
+	}
+
+	/// Formats supported by the source, if it supports choosing one.
+	fn formats(&mut self) -> Result<CameraFormats> {
+		Ok(CameraFormats::default())
+	}
+
+	/// Controls supported by the source, like exposure or focus.
+	fn controls(&mut self) -> Result<Vec<ControlInfo>> {
+		Ok(Vec::new())
+	}
+
+	/// Set a control of the source.
+	fn set_control(&mut self, setting: ControlSetting) -> Result<()> {
+		bail!("Source does not support setting {}", setting.kind);
+	}
+}
+
+impl FrameSource for Camera {
+	fn frame(&mut self) -> Result<RgbImage> {
+		Ok(Camera::frame(self)?)
+	}
+
+	fn frame_into(&mut self, pool: &FramePool) -> Result<RgbImage> {
+		let resolution = self.resolution();
+		let mut image = pool.image(resolution.width(), resolution.height());
+		self.write_frame_to_buffer(&mut image)?;
+		Ok(image)
+	}
+
+	fn profile_name(&self) -> Option<String> {
+		Some(self.info().human_name())
+	}
+
+	fn formats(&mut self) -> Result<CameraFormats> {
+		let mut available = Vec::new();
+		for frame_format in self.compatible_fourcc()? {
+			for (resolution, frame_rates) in self.compatible_list_by_resolution(frame_format)? {
+				available.extend(frame_rates.into_iter().map(|frame_rate| FormatChoice {
+					pixel_format: frame_format.into(),
+					resolution: (resolution.width(), resolution.height()),
+					frame_rate,
+				}));
+			}
+		}
+		available.sort_unstable();
+		available.dedup();
+
+		Ok(CameraFormats { available, current: Some(self.camera_format().into()) })
+	}
+
+	fn controls(&mut self) -> Result<Vec<ControlInfo>> {
+		Ok(self.camera_controls()?.iter().map(ControlInfo::from).collect())
+	}
+
+	fn set_control(&mut self, setting: ControlSetting) -> Result<()> {
+		let mut control = self.camera_control(setting.kind.into())?;
+		if setting.automatic {
+			control.set_flag(KnownCameraControlFlag::Automatic);
+		} else {
+			control.set_flag(KnownCameraControlFlag::Manual);
+			control.set_value(setting.value)?;
+		}
+		self.set_camera_control(control)?;
+		Ok(())
+	}
+}
+
+/// Description of a frame source to open.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+	/// System camera with the given index.
+	Camera(usize),
+	/// MJPEG stream via HTTP at the given URL, e.g. from an IP camera.
+	Mjpeg(String),
+	/// Images in the given directory, replayed in a loop.
+	Replay(PathBuf),
+	/// Synthetic frames with a moving square.
+	Synthetic,
+}
+
+impl Default for Source {
+	fn default() -> Self {
+		Self::Camera(0)
+	}
+}
+
+impl Source {
+	/// Open the source to start retrieving frames. Cameras are set up
+	/// according to the profile of the given name or their own profile.
+	pub fn open(&self, profile_name: Option<&str>) -> Result<Box<dyn FrameSource>> {
+		match self {
+			Self::Camera(index) => {
+				let mut camera = Camera::new(*index, None)?;
+				let name = profile_name.map_or_else(|| camera.info().human_name(), Into::into);
+				// A broken profile should not make the camera unusable.
+				let profile = Profile::load(&name).unwrap_or_else(|err| {
+					eprintln!("Error loading profile {name}: {err}");
+					Profile::default()
+				});
+				if let Some(format) = profile.format {
+					if let Err(err) = camera.set_camera_format(format.into()) {
+						eprintln!("Error setting camera format {format:?}: {err}");
+					}
+				}
+
+				camera.open_stream()?;
+				for setting in profile.controls {
+					if let Err(err) = FrameSource::set_control(&mut camera, setting) {
+						eprintln!("Error setting camera control {}: {err}", setting.kind);
+					}
+				}
+				Ok(Box::new(camera))
+			}
+			Self::Mjpeg(url) => Ok(Box::new(MjpegSource::connect(url)?)),
+			Self::Replay(dir) => Ok(Box::new(ReplaySource::open(dir)?)),
+			Self::Synthetic => Ok(Box::new(SyntheticSource::new())),
+		}
+	}
+}
+
+/// Clock pacing sources that could produce frames as fast as they are
+/// requested.
+#[derive(Debug)]
+pub(super) struct FrameClock {
+	/// Time of the last frame.
+	last: Option<Instant>,
+}
+
+impl FrameClock {
+	/// Create new clock, which does not wait for the first frame.
+	pub(super) fn new() -> Self {
+		Self { last: None }
+	}
+
+	/// Wait until the next frame is due.
+	pub(super) fn wait(&mut self) {
+		if let Some(last) = self.last {
+			thread::sleep(FRAME_INTERVAL.saturating_sub(last.elapsed()));
+		}
+		self.last = Some(Instant::now());
+	}
+}
@@ -0,0 +1,229 @@
+//! Compensation of camera shake, e.g. of a wobbling laptop lid, by estimating
+//! the translation of the whole frame and aligning the previous frame to the
+//! current one.
+
+use image::{GrayImage, ImageBuffer, Pixel};
+use rayon::prelude::*;
+
+use super::{
+	analysis::{self, Frame},
+	pool::FramePool,
+};
+
+/// Largest estimated shift in either direction, as fraction of the width.
+const MAX_SHIFT_FRACTION: f32 = 1.0 / 16.0;
+/// Width the coarsest level of the search is at least downscaled to.
+const MIN_LEVEL_WIDTH: u32 = 32;
+/// Largest factor the coarsest level of the search is downscaled by.
+const MAX_LEVEL_FACTOR: u32 = 16;
+
+/// Shift the previous frame, so that it is aligned with the current one.
+/// Pixels shifted in from outside of the previous frame are taken from the
+/// current one, so they do not differ. Returns the estimated translation of
+/// the scene from the previous frame to the current one, which is zero for
+/// frames of different color mode or size. The buffers are taken from and
+/// returned to the pool.
+pub fn align(previous: &mut Frame, current: &Frame, pool: &FramePool) -> (i32, i32) {
+	if previous.dimensions() != current.dimensions() {
+		return (0, 0);
+	}
+	match (previous, current) {
+		(Frame::Rgb(previous), Frame::Rgb(current)) => {
+			let (width, height) = current.dimensions();
+			let mut previous_luma = pool.image(width, height);
+			let mut current_luma = pool.image(width, height);
+			analysis::to_luma_into(previous, &mut previous_luma);
+			analysis::to_luma_into(current, &mut current_luma);
+			let shift = estimate_shift(&previous_luma, &current_luma, pool);
+			pool.recycle_image(previous_luma);
+			pool.recycle_image(current_luma);
+			shift_image(previous, current, shift, pool);
+			shift
+		}
+		(Frame::Luma(previous), Frame::Luma(current)) => {
+			let shift = estimate_shift(previous, current, pool);
+			shift_image(previous, current, shift, pool);
+			shift
+		}
+		_ => (0, 0),
+	}
+}
+
+/// Estimate the translation of the scene from the previous image to the
+/// current one, which matches them best. The search starts on a coarse
+/// downscaled level over all shifts and refines the best one on each finer
+/// level, so that larger shifts stay cheap.
+pub fn estimate_shift(previous: &GrayImage, current: &GrayImage, pool: &FramePool) -> (i32, i32) {
+	let width = current.width();
+	let max_shift = (width as f32 * MAX_SHIFT_FRACTION).ceil() as i32;
+	let mut factor = 1;
+	while factor < MAX_LEVEL_FACTOR && width / (factor * 2) >= MIN_LEVEL_WIDTH {
+		factor *= 2;
+	}
+
+	let mut shift = (0, 0);
+	let mut radius = (max_shift + factor as i32 - 1) / factor as i32;
+	while factor >= 1 {
+		shift = if factor == 1 {
+			best_shift(previous, current, shift, radius)
+		} else {
+			let (level_width, level_height) = (width / factor, current.height() / factor);
+			let mut previous_level = pool.image(level_width, level_height);
+			let mut current_level = pool.image(level_width, level_height);
+			analysis::downscale_into(previous, factor, &mut previous_level);
+			analysis::downscale_into(current, factor, &mut current_level);
+			let shift = best_shift(&previous_level, &current_level, shift, radius);
+			pool.recycle_image(previous_level);
+			pool.recycle_image(current_level);
+			shift
+		};
+		// The next level has twice the resolution, so the shift is refined by
+		// at most a pixel.
+		shift = (shift.0 * 2, shift.1 * 2);
+		radius = 1;
+		factor /= 2;
+	}
+	(shift.0 / 2, shift.1 / 2)
+}
+
+/// Shift with the least mean absolute difference within the radius around the
+/// given one. Ties are broken by the smaller shift, so that still scenes are
+/// not shifted.
+fn best_shift(
+	previous: &GrayImage,
+	current: &GrayImage,
+	center: (i32, i32),
+	radius: i32,
+) -> (i32, i32) {
+	(-radius..=radius)
+		.flat_map(|dy| (-radius..=radius).map(move |dx| (center.0 + dx, center.1 + dy)))
+		.filter_map(|shift| Some((shift, difference(previous, current, shift)?)))
+		.min_by(|(shift, cost), (other_shift, other_cost)| {
+			let size = |(dx, dy): &(i32, i32)| dx * dx + dy * dy;
+			cost.total_cmp(other_cost).then(size(shift).cmp(&size(other_shift)))
+		})
+		.map_or(center, |(shift, _)| shift)
+}
+
+/// Mean absolute difference of the overlap of the current image and the
+/// previous one shifted by the given translation. `None` if they do not
+/// overlap.
+fn difference(previous: &GrayImage, current: &GrayImage, (dx, dy): (i32, i32)) -> Option<f32> {
+	let (width, height) = (current.width() as i32, current.height() as i32);
+	let (x_start, x_end) = (dx.max(0), (width + dx).min(width));
+	let (y_start, y_end) = (dy.max(0), (height + dy).min(height));
+	if x_start >= x_end || y_start >= y_end {
+		return None;
+	}
+
+	let sum: u64 = (y_start..y_end)
+		.into_par_iter()
+		.map(|y| {
+			let len = (x_end - x_start) as usize;
+			let current_start = (y * width + x_start) as usize;
+			let previous_start = ((y - dy) * width + x_start - dx) as usize;
+			current.as_raw()[current_start..][..len]
+				.iter()
+				.zip(&previous.as_raw()[previous_start..][..len])
+				.map(|(cur, prev)| u64::from(cur.abs_diff(*prev)))
+				.sum::<u64>()
+		})
+		.sum();
+	Some(sum as f32 / ((x_end - x_start) * (y_end - y_start)) as f32)
+}
+
+/// Shift the previous image by the translation, filling in pixels from
+/// outside of it with the current image.
+fn shift_image<P>(
+	previous: &mut ImageBuffer<P, Vec<u8>>,
+	current: &ImageBuffer<P, Vec<u8>>,
+	(dx, dy): (i32, i32),
+	pool: &FramePool,
+) where
+	P: Pixel<Subpixel = u8> + 'static,
+{
+	if (dx, dy) == (0, 0) {
+		return;
+	}
+	let (width, height) = current.dimensions();
+	let pix_size = usize::from(P::CHANNEL_COUNT);
+	let stride = width as usize * pix_size;
+	let source = previous.as_raw();
+	let mut shifted: ImageBuffer<P, Vec<u8>> = pool.image(width, height);
+	shifted
+		.par_chunks_exact_mut(stride)
+		.zip(current.par_chunks_exact(stride))
+		.enumerate()
+		.for_each(|(y, (out_row, current_row))| {
+			out_row.copy_from_slice(current_row);
+			let source_y = y as i32 - dy;
+			let x_start = dx.clamp(0, width as i32) as usize * pix_size;
+			let x_end = (width as i32 + dx).clamp(0, width as i32) as usize * pix_size;
+			if source_y < 0 || source_y >= height as i32 || x_start >= x_end {
+				return;
+			}
+			let source_row = &source[source_y as usize * stride..][..stride];
+			let source_start = (x_start as i32 - dx * pix_size as i32) as usize;
+			out_row[x_start..x_end]
+				.copy_from_slice(&source_row[source_start..source_start + x_end - x_start]);
+		});
+	let unshifted = std::mem::replace(previous, shifted);
+	pool.recycle_image(unshifted);
+}
+
+#[cfg(test)]
+mod tests {
+	use image::{Luma, Rgb, RgbImage};
+
+	use super::*;
+	use crate::camera::analysis::Threshold;
+
+	/// Textured scene seen through a camera shifted by the given offset.
+	fn scene(offset: (i32, i32)) -> RgbImage {
+		RgbImage::from_fn(160, 120, |x, y| {
+			let (x, y) = (x as i32 + offset.0, y as i32 + offset.1);
+			let value = |scale: i32| ((x * scale / 7 + y * 3) ^ (y * scale / 5 - x)) as u8;
+			Rgb([value(11), value(13), value(17)])
+		})
+	}
+
+	#[test]
+	fn shaking_camera_is_aligned() {
+		let pool = FramePool::new();
+		let threshold = Threshold::Global(16);
+		for offset in [(0, 0), (3, -2), (-7, 5), (9, 9), (1, 0)] {
+			// The scene moves in the opposite direction of the camera.
+			let previous = Frame::Rgb(scene((0, 0)));
+			let current = Frame::Rgb(scene(offset));
+			let mut aligned = previous.clone();
+			assert_eq!(align(&mut aligned, &current, &pool), (-offset.0, -offset.1));
+			let mask = aligned.difference(&current, threshold, &pool).unwrap();
+			assert_eq!(mask.motion_fraction(0, 0, 160, 120), 0.0, "{offset:?}");
+			if offset != (0, 0) {
+				let mask = previous.difference(&current, threshold, &pool).unwrap();
+				assert!(mask.motion_fraction(0, 0, 160, 120) > 0.25, "{offset:?}");
+			}
+		}
+	}
+
+	#[test]
+	fn moving_objects_are_not_aligned() {
+		let pool = FramePool::new();
+		let background = |x: u32, y: u32| Luma([((x * 7) ^ (y * 13)) as u8]);
+		let previous = GrayImage::from_fn(128, 96, background);
+		let current = GrayImage::from_fn(128, 96, |x, y| {
+			if (40..60).contains(&x) && (30..50).contains(&y) {
+				Luma([255])
+			} else {
+				background(x, y)
+			}
+		});
+		assert_eq!(estimate_shift(&previous, &current, &pool), (0, 0));
+
+		let mut previous = Frame::Luma(previous);
+		let unchanged = previous.clone();
+		assert_eq!(align(&mut previous, &Frame::Luma(current), &pool), (0, 0));
+		assert_eq!(previous, unchanged);
+		assert_eq!(align(&mut previous, &Frame::Luma(GrayImage::new(8, 8)), &pool), (0, 0));
+	}
+}
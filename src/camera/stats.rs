@@ -0,0 +1,282 @@
+//! Timing statistics of the pipeline stages.
+
+use std::{
+	collections::VecDeque,
+	fmt,
+	time::{Duration, Instant},
+};
+
+use serde::Serialize;
+
+/// Number of frames the statistics are computed over.
+const WINDOW: usize = 120;
+/// Interval between reports of the statistics.
+const REPORT_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Measured time of the pipeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+	/// Mirroring the frame.
+	Flip,
+	/// Converting the frame to the color mode and resolution of the analysis.
+	Convert,
+	/// Reducing noise of the frame.
+	Process,
+	/// Aligning the previous frame to the current one.
+	Stabilise,
+	/// Compensating brightness changes and computing the difference to the
+	/// previous frame.
+	Difference,
+	/// Cleaning up the mask of moving pixels.
+	Cleanup,
+	/// Locating the movement in the difference.
+	Locate,
+	/// Handing frames and results to the UI and outputs.
+	Output,
+	/// Time from capturing the frame until all outputs handled it.
+	Latency,
+	/// Time between processed frames.
+	Interval,
+}
+
+impl Metric {
+	/// All metrics, in order of the pipeline.
+	pub const ALL: [Self; 10] = [
+		Self::Flip,
+		Self::Convert,
+		Self::Process,
+		Self::Stabilise,
+		Self::Difference,
+		Self::Cleanup,
+		Self::Locate,
+		Self::Output,
+		Self::Latency,
+		Self::Interval,
+	];
+
+	/// Name of the metric as used in external formats.
+	pub fn name(self) -> &'static str {
+		match self {
+			Self::Flip => "flip",
+			Self::Convert => "convert",
+			Self::Process => "process",
+			Self::Stabilise => "stabilise",
+			Self::Difference => "difference",
+			Self::Cleanup => "cleanup",
+			Self::Locate => "locate",
+			Self::Output => "output",
+			Self::Latency => "latency",
+			Self::Interval => "interval",
+		}
+	}
+}
+
+impl fmt::Display for Metric {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		fmt::Debug::fmt(self, f)
+	}
+}
+
+/// Summary of a metric over the recent frames, in milliseconds.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct MetricSummary {
+	/// Summarized metric.
+	pub metric: Metric,
+	/// Mean.
+	pub average: f32,
+	/// Median.
+	pub median: f32,
+	/// 95th percentile.
+	pub p95: f32,
+	/// Maximum.
+	pub max: f32,
+}
+
+/// Statistics of the pipeline over the recent frames.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct PipelineStats {
+	/// Summaries of all metrics with samples.
+	pub metrics: Vec<MetricSummary>,
+	/// Frames dropped since the pipeline started, because they arrived faster
+	/// than they were processed.
+	pub dropped_frames: u64,
+}
+
+impl PipelineStats {
+	/// Summary of the given metric, if it has samples.
+	pub fn get(&self, metric: Metric) -> Option<&MetricSummary> {
+		self.metrics.iter().find(|summary| summary.metric == metric)
+	}
+}
+
+/// Trailing moving average over the given number of values.
+pub fn moving_average(values: &[f32], window: usize) -> Vec<f32> {
+	let mut sum = 0.0;
+	values
+		.iter()
+		.enumerate()
+		.map(|(index, value)| {
+			sum += value;
+			if index >= window {
+				sum -= values[index - window];
+			}
+			sum / (index + 1).min(window) as f32
+		})
+		.collect()
+}
+
+/// Count the values in the given number of equally wide bins from zero to
+/// the maximum. Larger values are counted in the last bin.
+pub fn histogram(values: &[f32], bins: usize, max: f32) -> Vec<usize> {
+	let mut counts = vec![0; bins];
+	if bins == 0 {
+		return counts;
+	}
+	for value in values {
+		let bin = (value / max * bins as f32).max(0.0) as usize;
+		counts[bin.min(bins - 1)] += 1;
+	}
+	counts
+}
+
+/// Collector of the stage timings of each frame.
+#[derive(Debug)]
+pub(super) struct StatsCollector {
+	/// Recent samples of each metric in milliseconds, indexed like
+	/// [`Metric::ALL`].
+	samples: Vec<VecDeque<f32>>,
+	/// Times of the current frame, indexed like [`Metric::ALL`].
+	current: [Option<Duration>; Metric::ALL.len()],
+	/// Start of the current stage.
+	lap: Instant,
+	/// End of the previous frame.
+	previous_frame: Option<Instant>,
+	dropped_frames: u64,
+	last_report: Instant,
+}
+
+impl StatsCollector {
+	/// Create new collector without samples.
+	pub(super) fn new() -> Self {
+		let now = Instant::now();
+		Self {
+			samples: vec![VecDeque::with_capacity(WINDOW); Metric::ALL.len()],
+			current: [None; Metric::ALL.len()],
+			lap: now,
+			previous_frame: None,
+			dropped_frames: 0,
+			last_report: now,
+		}
+	}
+
+	/// Start timing a new frame.
+	pub(super) fn start_frame(&mut self) {
+		self.current = [None; Metric::ALL.len()];
+		self.lap = Instant::now();
+	}
+
+	/// Add the time since the previous lap to the metric of the stage that
+	/// just finished.
+	pub(super) fn lap(&mut self, metric: Metric) {
+		let now = Instant::now();
+		let time = self.current[metric as usize].get_or_insert(Duration::ZERO);
+		*time += now - self.lap;
+		self.lap = now;
+	}
+
+	/// Add the time of a stage, which was measured elsewhere, e.g. on another
+	/// thread.
+	pub(super) fn add(&mut self, metric: Metric, time: Duration) {
+		*self.current[metric as usize].get_or_insert(Duration::ZERO) += time;
+	}
+
+	/// Finish the frame captured at the given time, counting the frames
+	/// dropped before it.
+	pub(super) fn finish_frame(&mut self, captured: Instant, dropped_frames: u64) {
+		let now = Instant::now();
+		self.current[Metric::Latency as usize] = Some(now - captured);
+		if let Some(previous) = self.previous_frame {
+			self.current[Metric::Interval as usize] = Some(now - previous);
+		}
+		self.previous_frame = Some(now);
+		self.dropped_frames += dropped_frames;
+
+		for (samples, time) in self.samples.iter_mut().zip(self.current) {
+			if let Some(time) = time {
+				if samples.len() == WINDOW {
+					samples.pop_front();
+				}
+				samples.push_back(time.as_secs_f32() * 1000.0);
+			}
+		}
+	}
+
+	/// Forget the previous frame, e.g. while the pipeline is paused, so the
+	/// pause does not count as frame interval.
+	pub(super) fn interrupt(&mut self) {
+		self.previous_frame = None;
+	}
+
+	/// Statistics over the recent frames, if they are due to be reported.
+	pub(super) fn report(&mut self) -> Option<PipelineStats> {
+		if self.last_report.elapsed() < REPORT_INTERVAL {
+			return None;
+		}
+		self.last_report = Instant::now();
+		Some(self.stats())
+	}
+
+	/// Statistics over the recent frames.
+	pub(super) fn stats(&self) -> PipelineStats {
+		let metrics = Metric::ALL
+			.iter()
+			.zip(&self.samples)
+			.filter_map(|(metric, samples)| summarize(*metric, samples.iter().copied()))
+			.collect();
+		PipelineStats { metrics, dropped_frames: self.dropped_frames }
+	}
+}
+
+/// Summarize the samples of a metric, if there are any.
+pub fn summarize(metric: Metric, samples: impl IntoIterator<Item = f32>) -> Option<MetricSummary> {
+	let mut sorted: Vec<f32> = samples.into_iter().collect();
+	if sorted.is_empty() {
+		return None;
+	}
+	sorted.sort_unstable_by(f32::total_cmp);
+	// Nearest rank percentile, i.e. index ceil(n * p / 100) - 1.
+	let percentile = |p: usize| sorted[(sorted.len() * p).saturating_sub(1) / 100];
+	Some(MetricSummary {
+		metric,
+		average: sorted.iter().sum::<f32>() / sorted.len() as f32,
+		median: percentile(50),
+		p95: percentile(95),
+		max: sorted[sorted.len() - 1],
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn summarize_samples() {
+		let samples = (1..=100).rev().map(|ms| ms as f32);
+		let summary = summarize(Metric::Process, samples).unwrap();
+		assert_eq!(summary.average, 50.5);
+		assert_eq!(summary.median, 50.0);
+		assert_eq!(summary.p95, 95.0);
+		assert_eq!(summary.max, 100.0);
+
+		assert_eq!(summarize(Metric::Process, []), None);
+	}
+
+	#[test]
+	fn interval_chart_values() {
+		assert_eq!(moving_average(&[2.0, 4.0, 6.0, 8.0], 2), vec![2.0, 3.0, 5.0, 7.0]);
+		let intervals = [33.0, 34.0, 66.0, 5.0, 120.0];
+		assert_eq!(histogram(&intervals, 4, 80.0), vec![1, 2, 0, 2]);
+		assert_eq!(histogram(&intervals, 0, 80.0), Vec::<usize>::new());
+	}
+}
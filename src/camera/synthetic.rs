@@ -0,0 +1,70 @@
+//! Synthetic frame source, which moves a bright square over a dark
+//! background. Allows using the app without any camera.
+
+use color_eyre::Result;
+use image::{Rgb, RgbImage};
+
+use super::{
+	pool::FramePool,
+	source::{FrameClock, FrameSource},
+};
+
+/// Width of the synthetic frames.
+const WIDTH: u32 = 640;
+/// Height of the synthetic frames.
+const HEIGHT: u32 = 480;
+/// Side length of the moving square.
+const SQUARE_SIZE: u32 = 40;
+/// Number of frames for the square to move back and forth once.
+const PERIOD: u32 = 120;
+
+/// Source of synthetic frames with a moving square.
+#[derive(Debug)]
+pub struct SyntheticSource {
+	/// Number of the next frame.
+	frame_number: u32,
+	clock: FrameClock,
+}
+
+impl SyntheticSource {
+	/// Create new synthetic source.
+	pub fn new() -> Self {
+		Self { frame_number: 0, clock: FrameClock::new() }
+	}
+
+	/// Wait for the next frame and draw it into the image.
+	fn draw(&mut self, image: &mut RgbImage) {
+		self.clock.wait();
+		// Move on a circle, so that the movement is visible in both axes.
+		let angle = self.frame_number as f32 / PERIOD as f32 * std::f32::consts::TAU;
+		self.frame_number = (self.frame_number + 1) % PERIOD;
+		let center_x = (WIDTH / 2) as f32 + angle.cos() * (WIDTH / 3) as f32;
+		let center_y = (HEIGHT / 2) as f32 + angle.sin() * (HEIGHT / 3) as f32;
+		let left = center_x as u32 - SQUARE_SIZE / 2;
+		let top = center_y as u32 - SQUARE_SIZE / 2;
+
+		for (x, y, pixel) in image.enumerate_pixels_mut() {
+			*pixel = if (left..left + SQUARE_SIZE).contains(&x)
+				&& (top..top + SQUARE_SIZE).contains(&y)
+			{
+				Rgb([0xFF, 0xFF, 0xFF])
+			} else {
+				Rgb([0x20, 0x20, 0x20])
+			};
+		}
+	}
+}
+
+impl FrameSource for SyntheticSource {
+	fn frame(&mut self) -> Result<RgbImage> {
+		let mut image = RgbImage::new(WIDTH, HEIGHT);
+		self.draw(&mut image);
+		Ok(image)
+	}
+
+	fn frame_into(&mut self, pool: &FramePool) -> Result<RgbImage> {
+		let mut image = pool.image(WIDTH, HEIGHT);
+		self.draw(&mut image);
+		Ok(image)
+	}
+}
@@ -0,0 +1,39 @@
+//! Helpers shared by the unit tests, integration tests and benchmarks, which
+//! are not part of the API.
+
+pub use super::mailbox::{mailbox, Droppable, MailboxReceiver, MailboxSender};
+
+/// Simple linear congruential generator, so failures are reproducible.
+#[derive(Debug, Clone)]
+pub struct Lcg {
+	state: u32,
+}
+
+impl Lcg {
+	/// Generator with the seed most tests use.
+	pub fn new() -> Self {
+		Self::with_seed(0x2545_F491)
+	}
+
+	/// Generator with the given seed, for independent sequences.
+	pub fn with_seed(seed: u32) -> Self {
+		Self { state: seed }
+	}
+
+	/// Next pseudo-random value, using the better high bits of the state.
+	pub fn next_u16(&mut self) -> u16 {
+		self.state = self.state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+		(self.state >> 16) as u16
+	}
+
+	/// Next pseudo-random byte.
+	pub fn next_u8(&mut self) -> u8 {
+		self.next_u16() as u8
+	}
+}
+
+impl Default for Lcg {
+	fn default() -> Self {
+		Self::new()
+	}
+}
@@ -9,10 +9,18 @@
 	dead_code
 )]
 
+mod args;
 mod camera;
+pub mod output;
 mod widgets;
 
+#[doc(hidden)]
+pub use self::camera::testing;
 pub use self::{
-	camera::{analysis, CameraConnector},
+	args::Args,
+	camera::{
+		analysis, morphology, pool::FramePool, CameraConnector, CameraRequest, FramePolicy,
+		PipelineControl, PreparedFrame, Preprocessor, Source,
+	},
 	widgets::{root_widget, RootUIState},
 };
@@ -1,21 +1,83 @@
 use std::sync::mpsc;
 
+use clap::Parser;
+#[cfg(not(target_os = "linux"))]
+use color_eyre::eyre::bail;
 use color_eyre::Result;
 use druid::{AppLauncher, LocalizedString, WindowDesc};
-use webcam_mouse::{root_widget, CameraConnector, RootUIState};
+#[cfg(unix)]
+use webcam_mouse::output::IpcServer;
+#[cfg(target_os = "linux")]
+use webcam_mouse::output::{JoystickOutput, ZoneTrigger};
+use webcam_mouse::{
+	output::{zones::ZoneReceiver, OscAddresses, OscOutput, PreviewServer, StatsCsvOutput},
+	root_widget, Args, CameraConnector, PipelineControl, RootUIState, Source,
+};
 
 fn main() -> Result<()> {
 	color_eyre::install()?;
+	let args = Args::parse();
 
-	let (pick_sender, pick_receiver) = mpsc::channel();
+	let (request_sender, request_receiver) = mpsc::channel();
+	let (zone_sender, zone_receiver) = mpsc::channel();
 
-	let window = WindowDesc::new(root_widget(pick_sender))
+	let control = PipelineControl::new(request_sender.clone());
+	control.set_threshold(args.threshold);
+
+	let root = root_widget(request_sender, zone_sender, control.clone(), args.stabilise);
+	let window = WindowDesc::new(root)
 		.title(LocalizedString::new("Window-Title").with_placeholder("Webcam Mouse"))
 		.window_size((1100.0, 550.0));
 	let launcher = AppLauncher::with_window(window);
 	let event_sender = launcher.get_external_handle();
 
-	let camera_handler = CameraConnector::new(event_sender, pick_receiver);
+	let mut camera_handler = CameraConnector::new(event_sender, request_receiver)
+		.with_control(&control)
+		.with_frame_policy(args.frame_policy)
+		.with_processing_width(args.processing_width)
+		.with_color_mode(args.color_mode)
+		.with_morphology(args.morphology)
+		.with_stabilisation(args.stabilise)
+		.with_illumination_compensation(args.compensate_illumination)
+		.with_motion_limit(args.motion_limit)
+		.with_pipelining(args.pipelined);
+	if let Some(url) = &args.mjpeg_url {
+		camera_handler = camera_handler.with_source(Source::Mjpeg(url.clone()));
+	} else if let Some(dir) = &args.replay {
+		camera_handler = camera_handler.with_source(Source::Replay(dir.clone()));
+	} else if args.synthetic {
+		camera_handler = camera_handler.with_source(Source::Synthetic);
+	}
+	let camera_handler = add_platform_outputs(camera_handler, &args, zone_receiver)?;
+	let camera_handler = match args.osc_port {
+		Some(port) => {
+			let addresses = OscAddresses {
+				point: args.osc_point_address.clone(),
+				area: args.osc_area_address.clone(),
+				gesture: args.osc_gesture_address.clone(),
+				fps: args.osc_fps_address.clone(),
+			};
+			camera_handler.with_sink(OscOutput::new(port, addresses)?)
+		}
+		None => camera_handler,
+	};
+	let camera_handler = match args.preview_port {
+		Some(port) => camera_handler.with_sink(PreviewServer::bind(port, args.stabilise)?),
+		None => camera_handler,
+	};
+	let camera_handler = match &args.stats_csv {
+		Some(path) => camera_handler.with_sink(StatsCsvOutput::create(path)?),
+		None => camera_handler,
+	};
+	#[cfg(unix)]
+	let camera_handler = match &args.ipc_socket {
+		Some(path) => camera_handler.with_sink(IpcServer::bind(path, control)?),
+		None => camera_handler,
+	};
+	#[cfg(not(unix))]
+	if args.ipc_socket.is_some() {
+		bail!("The IPC server is only supported on Unix systems");
+	}
 	let cam_handles = camera_handler.spawn();
 
 	launcher.log_to_console().launch(RootUIState::default()).expect("running app");
@@ -24,3 +86,32 @@ fn main() -> Result<()> {
 	cam_handles.1.join().expect("joining camera frame processor");
 	Ok(())
 }
+
+/// Add the outputs, which are only available on Linux, to the camera handler.
+#[cfg(target_os = "linux")]
+fn add_platform_outputs(
+	mut camera_handler: CameraConnector,
+	args: &Args,
+	zone_receiver: ZoneReceiver,
+) -> Result<CameraConnector> {
+	camera_handler = camera_handler.with_sink(ZoneTrigger::new(zone_receiver));
+	if args.joystick {
+		let neutral = (args.joystick_neutral_x, args.joystick_neutral_y);
+		let joystick = JoystickOutput::new(neutral, args.joystick_dead_zone)?;
+		camera_handler = camera_handler.with_sink(joystick);
+	}
+	Ok(camera_handler)
+}
+
+/// Add the outputs, which are only available on Linux, to the camera handler.
+#[cfg(not(target_os = "linux"))]
+fn add_platform_outputs(
+	camera_handler: CameraConnector,
+	args: &Args,
+	_zone_receiver: ZoneReceiver,
+) -> Result<CameraConnector> {
+	if args.joystick {
+		bail!("The virtual joystick is only supported on Linux");
+	}
+	Ok(camera_handler)
+}
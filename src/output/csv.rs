@@ -0,0 +1,97 @@
+//! Export of the pipeline statistics as CSV.
+
+use std::{
+	fs::File,
+	io::{BufWriter, Write},
+	path::Path,
+	time::Instant,
+};
+
+use color_eyre::Result;
+
+use super::{EventSink, PipelineEvent};
+use crate::camera::stats::Metric;
+
+/// Output appending a row to a CSV file for every statistics report.
+#[derive(Debug)]
+pub struct StatsCsvOutput {
+	writer: BufWriter<File>,
+	/// Start of the export, which the time column is relative to.
+	start: Instant,
+}
+
+impl StatsCsvOutput {
+	/// Create the CSV file at the path and write its header.
+	pub fn create(path: &Path) -> Result<Self> {
+		let mut writer = BufWriter::new(File::create(path)?);
+		write!(writer, "time_s,dropped_frames")?;
+		for metric in Metric::ALL {
+			let name = metric.name();
+			write!(writer, ",{name}_avg_ms,{name}_median_ms,{name}_p95_ms,{name}_max_ms")?;
+		}
+		writeln!(writer)?;
+		writer.flush()?;
+		Ok(Self { writer, start: Instant::now() })
+	}
+}
+
+impl EventSink for StatsCsvOutput {
+	fn handle(&mut self, event: PipelineEvent<'_>) -> Result<()> {
+		if let PipelineEvent::Stats(stats) = event {
+			let time = self.start.elapsed().as_secs_f32();
+			write!(self.writer, "{time:.3},{}", stats.dropped_frames)?;
+			for metric in Metric::ALL {
+				match stats.get(metric) {
+					Some(summary) => write!(
+						self.writer,
+						",{:.3},{:.3},{:.3},{:.3}",
+						summary.average, summary.median, summary.p95, summary.max
+					)?,
+					// Metrics without samples stay empty, e.g. before the
+					// second frame.
+					None => write!(self.writer, ",,,,")?,
+				}
+			}
+			writeln!(self.writer)?;
+			// Keep the file usable while the app is running.
+			self.writer.flush()?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::camera::stats::{MetricSummary, PipelineStats};
+
+	#[test]
+	fn write_rows() {
+		let path =
+			std::env::temp_dir().join(format!("webcam-mouse-stats-{}.csv", std::process::id()));
+		let mut output = StatsCsvOutput::create(&path).unwrap();
+		let stats = PipelineStats {
+			metrics: vec![MetricSummary {
+				metric: Metric::Process,
+				average: 4.0,
+				median: 3.5,
+				p95: 6.25,
+				max: 7.0,
+			}],
+			dropped_frames: 3,
+		};
+		output.handle(PipelineEvent::Stats(&stats)).unwrap();
+		output.handle(PipelineEvent::Fps(30.0)).unwrap();
+
+		let csv = std::fs::read_to_string(&path).unwrap();
+		std::fs::remove_file(&path).unwrap();
+		let lines: Vec<&str> = csv.lines().collect();
+		assert_eq!(lines.len(), 2);
+		let header: Vec<&str> = lines[0].split(',').collect();
+		let row: Vec<&str> = lines[1].split(',').collect();
+		assert_eq!(header.len(), row.len());
+		assert_eq!(row[1], "3");
+		assert_eq!(&row[10..14], ["4.000", "3.500", "6.250", "7.000"]);
+		assert_eq!(row[2], "");
+	}
+}
@@ -0,0 +1,176 @@
+//! Local IPC server, which publishes pipeline events as newline-delimited JSON
+//! over a Unix domain socket and accepts commands from its clients.
+
+use std::{
+	fs,
+	io::{BufRead, BufReader, Write},
+	os::unix::{
+		fs::FileTypeExt,
+		net::{UnixListener, UnixStream},
+	},
+	path::{Path, PathBuf},
+	sync::{Arc, Mutex},
+	thread,
+	time::Duration,
+};
+
+use color_eyre::{eyre::bail, Result};
+use serde::Deserialize;
+
+use super::{json::JsonEvent, EventSink, PipelineEvent};
+use crate::camera::PipelineControl;
+
+/// Time writing to a client may block before the client is disconnected.
+const WRITE_TIMEOUT: Duration = Duration::from_millis(50);
+
+/// Command as received from the clients.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+enum IpcCommand {
+	/// Pause processing.
+	Pause,
+	/// Resume processing.
+	Resume,
+	/// Switch to the camera with the given index.
+	SwitchCamera { index: usize },
+	/// Load the profile of the given name.
+	LoadProfile { name: String },
+	/// Calibrate the threshold and blur strength to the camera's noise.
+	Calibrate,
+}
+
+/// Connected clients.
+type Clients = Arc<Mutex<Vec<Arc<Mutex<UnixStream>>>>>;
+
+/// Sink publishing the events to all clients connected to the socket.
+#[derive(Debug)]
+pub struct IpcServer {
+	path: PathBuf,
+	clients: Clients,
+}
+
+impl IpcServer {
+	/// Bind the server to the socket at the given path and start accepting
+	/// clients. Commands are applied using the pipeline control.
+	pub fn bind(path: impl AsRef<Path>, control: PipelineControl) -> Result<Self> {
+		let path = path.as_ref().to_path_buf();
+		// Remove the socket of a previous run, but nothing else.
+		if let Ok(metadata) = fs::symlink_metadata(&path) {
+			if !metadata.file_type().is_socket() {
+				bail!("{} exists and is not a socket", path.display());
+			}
+			fs::remove_file(&path)?;
+		}
+
+		let listener = UnixListener::bind(&path)?;
+		let clients = Clients::default();
+		let accepted_clients = clients.clone();
+		thread::spawn(move || {
+			for stream in listener.incoming() {
+				let result = stream
+					.map_err(Into::into)
+					.and_then(|stream| Self::accept(stream, &accepted_clients, control.clone()));
+				if let Err(err) = result {
+					eprintln!("Error accepting IPC client: {err}");
+				}
+			}
+		});
+
+		Ok(Self { path, clients })
+	}
+
+	/// Register the client and handle its commands in a new thread.
+	fn accept(stream: UnixStream, clients: &Clients, control: PipelineControl) -> Result<()> {
+		stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+		let reader = BufReader::new(stream.try_clone()?);
+		let client = Arc::new(Mutex::new(stream));
+		clients.lock().unwrap_or_else(|err| err.into_inner()).push(client.clone());
+
+		thread::spawn(move || {
+			for line in reader.lines() {
+				let line = match line {
+					Ok(line) if line.trim().is_empty() => continue,
+					Ok(line) => line,
+					Err(_) => break,
+				};
+				if let Err(err) = Self::run_command(&line, &control) {
+					let reply = JsonEvent::Error { message: err.to_string() };
+					let mut client = client.lock().unwrap_or_else(|err| err.into_inner());
+					if write_event(&mut client, &reply).is_err() {
+						break;
+					}
+				}
+			}
+		});
+		Ok(())
+	}
+
+	/// Parse and run a command.
+	fn run_command(line: &str, control: &PipelineControl) -> Result<()> {
+		match serde_json::from_str(line)? {
+			IpcCommand::Pause => control.pause(),
+			IpcCommand::Resume => control.resume(),
+			IpcCommand::SwitchCamera { index } => control.switch_camera(index)?,
+			IpcCommand::LoadProfile { name } => control.load_profile(name)?,
+			IpcCommand::Calibrate => control.calibrate(),
+		}
+		Ok(())
+	}
+}
+
+impl EventSink for IpcServer {
+	fn handle(&mut self, event: PipelineEvent<'_>) -> Result<()> {
+		let event = match JsonEvent::from_event(event) {
+			Some(event) => event,
+			None => return Ok(()),
+		};
+
+		// Disconnect clients, which cannot keep up or are gone.
+		self.clients.lock().unwrap_or_else(|err| err.into_inner()).retain(|client| {
+			write_event(&mut client.lock().unwrap_or_else(|err| err.into_inner()), &event).is_ok()
+		});
+		Ok(())
+	}
+}
+
+impl Drop for IpcServer {
+	fn drop(&mut self) {
+		fs::remove_file(&self.path).ok();
+	}
+}
+
+/// Write the event as single line of JSON.
+fn write_event(stream: &mut UnixStream, event: &JsonEvent) -> Result<()> {
+	let mut line = serde_json::to_vec(event)?;
+	line.push(b'\n');
+	stream.write_all(&line)?;
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::mpsc;
+
+	use super::*;
+	use crate::camera::CameraRequest;
+
+	#[test]
+	fn events_and_commands() {
+		let path = std::env::temp_dir().join(format!("webcam-mouse-{}.sock", std::process::id()));
+		let (request_sender, request_receiver) = mpsc::channel();
+		let mut server = IpcServer::bind(&path, PipelineControl::new(request_sender)).unwrap();
+
+		let mut client = UnixStream::connect(&path).unwrap();
+		client.write_all(b"{\"command\":\"switch_camera\",\"index\":2}\n").unwrap();
+		assert_eq!(
+			request_receiver.recv_timeout(Duration::from_secs(5)),
+			Ok(CameraRequest::Pick(2))
+		);
+
+		server.handle(PipelineEvent::Fps(30.0)).unwrap();
+		let mut reader = BufReader::new(client);
+		let mut line = String::new();
+		reader.read_line(&mut line).unwrap();
+		assert_eq!(line, "{\"event\":\"fps\",\"fps\":30.0}\n");
+	}
+}
@@ -0,0 +1,110 @@
+//! Virtual joystick output of the detected point.
+
+use std::time::{Duration, Instant};
+
+use color_eyre::Result;
+use evdev::Key;
+
+use super::{EventSink, Gesture, PipelineEvent, VirtualInput};
+
+/// Maximum absolute value of the joystick axes.
+pub const AXIS_MAX: i32 = 32767;
+/// Time without detected points, after which the joystick returns to the
+/// center. Single frames without a point do not make it jump back.
+const RECENTER_TIMEOUT: Duration = Duration::from_millis(300);
+
+/// Sink moving a virtual joystick according to the detected point. Gestures
+/// are mapped to buttons.
+#[derive(Debug)]
+pub struct JoystickOutput {
+	device: VirtualInput,
+	/// Neutral position as fraction of the frame size.
+	neutral: (f32, f32),
+	/// Radius around the neutral position mapped to the axes' center, as
+	/// fraction of the axis range.
+	dead_zone: f32,
+	/// Time of the last point, while the axes are not centered.
+	last_point: Option<Instant>,
+}
+
+impl JoystickOutput {
+	/// Create the virtual joystick. The neutral position is given as fraction
+	/// of the frame size and the dead zone as fraction of the axis range.
+	pub fn new(neutral: (f32, f32), dead_zone: f32) -> Result<Self> {
+		let device = VirtualInput::joystick(AXIS_MAX)?;
+		Ok(Self { device, neutral, dead_zone: dead_zone.clamp(0.0, 0.99), last_point: None })
+	}
+
+	/// Move the axes back to the center.
+	fn recenter(&mut self) -> Result<()> {
+		self.device.move_axes(0, 0)?;
+		self.last_point = None;
+		Ok(())
+	}
+}
+
+impl EventSink for JoystickOutput {
+	fn handle(&mut self, event: PipelineEvent<'_>) -> Result<()> {
+		match event {
+			PipelineEvent::Point { position, dimensions } => {
+				let (x, y) = axis_values(position, dimensions, self.neutral, self.dead_zone);
+				self.device.move_axes(x, y)?;
+				self.last_point = Some(Instant::now());
+			}
+			// Each processed frame ends with its frame rate, so the timeout is
+			// checked once per frame.
+			PipelineEvent::Fps(_)
+				if self.last_point.is_some_and(|time| time.elapsed() >= RECENTER_TIMEOUT) =>
+			{
+				self.recenter()?;
+			}
+			// No frames follow until the camera streams again.
+			PipelineEvent::Status(_) if self.last_point.is_some() => self.recenter()?,
+			PipelineEvent::Gesture(Gesture::Dwell) => self.device.click(Key::BTN_TRIGGER)?,
+			_ => {}
+		}
+		Ok(())
+	}
+}
+
+/// Compute the axis values for a point in a frame of the given dimensions.
+/// Neutral position and dead zone are given as fractions like for
+/// [`JoystickOutput::new`].
+fn axis_values(
+	position: (u32, u32),
+	dimensions: (u32, u32),
+	neutral: (f32, f32),
+	dead_zone: f32,
+) -> (i32, i32) {
+	// Offset to the neutral position, scaled so that the frame edges are at +-1.
+	let offset = |pos: u32, size: u32, neutral: f32| {
+		let pos = pos as f32 / size.max(1) as f32;
+		let range = if pos < neutral { neutral } else { 1.0 - neutral };
+		((pos - neutral) / range.max(f32::EPSILON)).clamp(-1.0, 1.0)
+	};
+	let x = offset(position.0, dimensions.0, neutral.0);
+	let y = offset(position.1, dimensions.1, neutral.1);
+
+	// Radial dead zone, rescaling the rest to keep the full range.
+	let length = x.hypot(y);
+	if length <= dead_zone {
+		return (0, 0);
+	}
+	let scale = ((length - dead_zone) / (1.0 - dead_zone)).min(1.0) / length;
+	((x * scale * AXIS_MAX as f32) as i32, (y * scale * AXIS_MAX as f32) as i32)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn dead_zone() {
+		let neutral = (0.5, 0.5);
+		assert_eq!(axis_values((55, 50), (100, 100), neutral, 0.2), (0, 0));
+		assert_eq!(axis_values((100, 50), (100, 100), neutral, 0.2), (AXIS_MAX, 0));
+		assert_eq!(axis_values((50, 0), (100, 100), neutral, 0.2), (0, -AXIS_MAX));
+		let (x, y) = axis_values((80, 50), (100, 100), neutral, 0.2);
+		assert!(x > 0 && x < AXIS_MAX && y == 0);
+	}
+}
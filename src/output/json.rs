@@ -0,0 +1,62 @@
+//! JSON representation of pipeline events for external consumers.
+
+use serde::Serialize;
+
+use super::{CameraStatus, Gesture, PipelineEvent};
+use crate::camera::stats::PipelineStats;
+
+/// Pipeline event as sent to external consumers, without frames.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub(crate) enum JsonEvent {
+	/// Detected point.
+	Point { x: u32, y: u32, width: u32, height: u32 },
+	/// Smoothed detected point.
+	SmoothedPoint { x: f32, y: f32, width: u32, height: u32 },
+	/// Detected gesture.
+	Gesture { gesture: Gesture },
+	/// Frame rate of the processor.
+	Fps { fps: f32 },
+	/// Latency from capture to the outputs handling the frame, in milliseconds.
+	Latency { ms: f32 },
+	/// Statistics of the pipeline over the recent frames.
+	Stats {
+		#[serde(flatten)]
+		stats: PipelineStats,
+	},
+	/// Changed camera status, with the error message if it failed.
+	Status {
+		status: &'static str,
+		#[serde(skip_serializing_if = "Option::is_none")]
+		message: Option<String>,
+	},
+	/// Failed request of the consumer.
+	Error { message: String },
+}
+
+impl JsonEvent {
+	/// Convert the pipeline event, if it is not a frame.
+	pub(crate) fn from_event(event: PipelineEvent<'_>) -> Option<Self> {
+		let event = match event {
+			PipelineEvent::Point { position: (x, y), dimensions: (width, height) } => {
+				Self::Point { x, y, width, height }
+			}
+			PipelineEvent::SmoothedPoint { position: (x, y), dimensions: (width, height) } => {
+				Self::SmoothedPoint { x, y, width, height }
+			}
+			PipelineEvent::Gesture(gesture) => Self::Gesture { gesture },
+			PipelineEvent::Fps(fps) => Self::Fps { fps },
+			PipelineEvent::Latency(latency) => Self::Latency { ms: latency.as_secs_f32() * 1000.0 },
+			PipelineEvent::Stats(stats) => Self::Stats { stats: stats.clone() },
+			PipelineEvent::Status(status) => {
+				let message = match status {
+					CameraStatus::Error(message) => Some(message.clone()),
+					_ => None,
+				};
+				Self::Status { status: status.name(), message }
+			}
+			PipelineEvent::Frame(..) | PipelineEvent::Difference { .. } => return None,
+		};
+		Some(event)
+	}
+}
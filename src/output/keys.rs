@@ -0,0 +1,105 @@
+//! Zone trigger mode, which emits key chords on movement inside motion zones.
+
+use color_eyre::{eyre::eyre, Result};
+use evdev::Key;
+
+use super::{
+	virtual_input::parse_chord,
+	zones::{Zone, ZoneReceiver},
+	EventSink, FrameKind, PipelineEvent, VirtualInput,
+};
+
+/// Fraction of moving pixels inside a zone to trigger it.
+const ACTIVATION_FRACTION: f32 = 0.05;
+/// Fraction of moving pixels inside a triggered zone, below which it can be
+/// triggered again.
+const RELEASE_FRACTION: f32 = 0.01;
+
+/// Sink emitting key chords when there is movement in one of the zones.
+#[derive(Debug)]
+pub struct ZoneTrigger {
+	zone_receiver: ZoneReceiver,
+	zones: Vec<ZoneState>,
+	/// Width of the unprocessed frames, which the zones refer to.
+	frame_width: u32,
+	/// Virtual keyboard, only created once it is needed.
+	input: Option<VirtualInput>,
+}
+
+/// Zone with its parsed chord and trigger state.
+#[derive(Debug)]
+struct ZoneState {
+	zone: Zone,
+	chord: Vec<Key>,
+	triggered: bool,
+}
+
+impl ZoneTrigger {
+	/// Create new zone trigger receiving its zones from the UI.
+	pub fn new(zone_receiver: ZoneReceiver) -> Self {
+		Self { zone_receiver, zones: Vec::new(), frame_width: 0, input: None }
+	}
+
+	/// Apply the latest zone update, if there is any. Zones with invalid
+	/// chords are skipped and reported.
+	fn update_zones(&mut self) -> Result<()> {
+		let latest = self.zone_receiver.try_iter().last();
+		if let Some(zones) = latest {
+			let mut invalid = Vec::new();
+			self.zones = zones
+				.into_iter()
+				.filter_map(|zone| match parse_chord(&zone.chord) {
+					Ok(chord) => Some(ZoneState { zone, chord, triggered: false }),
+					Err(err) => {
+						invalid.push(err.to_string());
+						None
+					}
+				})
+				.collect();
+			if !invalid.is_empty() {
+				return Err(eyre!("Skipped zones: {}", invalid.join(", ")));
+			}
+		}
+		Ok(())
+	}
+}
+
+impl EventSink for ZoneTrigger {
+	fn handle(&mut self, event: PipelineEvent<'_>) -> Result<()> {
+		self.update_zones()?;
+
+		if let PipelineEvent::Frame(FrameKind::Original, frame) = event {
+			self.frame_width = frame.dimensions().0;
+		}
+		// Changes of the whole frame would trigger all zones at once.
+		if let PipelineEvent::Difference { frame: diff_img, whole_frame: false } = event {
+			// The difference image is smaller if the analysis runs downscaled.
+			let scale = match self.frame_width {
+				0 => 1.0,
+				frame_width => diff_img.dimensions().0 as f32 / frame_width as f32,
+			};
+			let scaled = |value: u32| (value as f32 * scale).round() as u32;
+			for state in &mut self.zones {
+				let Zone { x, y, width, height, .. } = state.zone;
+				let fraction = diff_img.motion_fraction(
+					scaled(x),
+					scaled(y),
+					scaled(width).max(1),
+					scaled(height).max(1),
+				);
+				if !state.triggered && fraction >= ACTIVATION_FRACTION {
+					state.triggered = true;
+					if self.input.is_none() {
+						self.input = Some(VirtualInput::keyboard()?);
+					}
+					if let Some(input) = &mut self.input {
+						input.press_chord(&state.chord)?;
+					}
+				} else if state.triggered && fraction < RELEASE_FRACTION {
+					state.triggered = false;
+				}
+			}
+		}
+		Ok(())
+	}
+}
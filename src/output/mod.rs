@@ -0,0 +1,156 @@
+//! Outputs of the tracking pipeline to consumers other than the UI, e.g.
+//! virtual input devices.
+
+mod csv;
+#[cfg(unix)]
+mod ipc;
+#[cfg(target_os = "linux")]
+mod joystick;
+mod json;
+#[cfg(target_os = "linux")]
+mod keys;
+mod osc;
+mod preview;
+#[cfg(target_os = "linux")]
+mod virtual_input;
+pub mod zones;
+
+use std::{fmt, time::Duration};
+
+use color_eyre::Result;
+use druid::Data;
+use serde::Serialize;
+
+#[cfg(unix)]
+pub use self::ipc::IpcServer;
+pub use self::{
+	csv::StatsCsvOutput,
+	osc::{OscAddresses, OscOutput},
+	preview::PreviewServer,
+};
+#[cfg(target_os = "linux")]
+pub use self::{joystick::JoystickOutput, keys::ZoneTrigger, virtual_input::VirtualInput};
+use crate::camera::{analysis::Frame, stats::PipelineStats};
+
+/// Event emitted by the frame processor to all registered [`EventSink`]s.
+#[derive(Debug, Clone, Copy)]
+pub enum PipelineEvent<'a> {
+	/// Frame of the given kind, except differences.
+	Frame(FrameKind, &'a Frame),
+	/// Difference of the processed frame to the previous one.
+	Difference {
+		/// Difference image, see [`FrameKind::Difference`].
+		frame: &'a Frame,
+		/// Whether more than the motion limit changed, e.g. the lighting, so
+		/// no movement is located.
+		whole_frame: bool,
+	},
+	/// Detected point of movement within a frame of the given dimensions.
+	Point {
+		/// Position of the point in frame pixels.
+		position: (u32, u32),
+		/// Width and height of the frame.
+		dimensions: (u32, u32),
+	},
+	/// Exponentially smoothed detected point.
+	SmoothedPoint {
+		/// Position of the point in frame pixels.
+		position: (f32, f32),
+		/// Width and height of the frame.
+		dimensions: (u32, u32),
+	},
+	/// Detected gesture.
+	Gesture(Gesture),
+	/// Current frame rate of the processor.
+	Fps(f32),
+	/// Changed status of the camera.
+	Status(&'a CameraStatus),
+	/// Time from capturing the frame to the outputs handling it, sent for
+	/// every frame.
+	Latency(Duration),
+	/// Statistics of the pipeline over the recent frames.
+	Stats(&'a PipelineStats),
+}
+
+/// Kinds of frames produced by the pipeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameKind {
+	/// Mirrored camera frame.
+	Original,
+	/// Frame processed to reduce noise.
+	Processed,
+	/// Previous processed frame aligned to the current one.
+	Stabilised,
+	/// Difference of the processed frame to the previous one.
+	Difference,
+}
+
+/// Gestures detected on the stream of points.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Gesture {
+	/// The point rested at one position for a while.
+	Dwell,
+}
+
+impl Gesture {
+	/// Name of the gesture as used in external formats.
+	pub fn name(self) -> &'static str {
+		match self {
+			Self::Dwell => "dwell",
+		}
+	}
+}
+
+/// Status of the camera connection.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Data)]
+pub enum CameraStatus {
+	/// Opening the camera.
+	#[default]
+	Connecting,
+	/// Frames are being received.
+	Streaming,
+	/// The camera failed with the given error.
+	Error(String),
+	/// Trying to open the camera again after an error.
+	Reconnecting,
+}
+
+impl CameraStatus {
+	/// Name of the status as used in external formats.
+	pub fn name(&self) -> &'static str {
+		match self {
+			Self::Connecting => "connecting",
+			Self::Streaming => "streaming",
+			Self::Error(_) => "error",
+			Self::Reconnecting => "reconnecting",
+		}
+	}
+}
+
+impl fmt::Display for CameraStatus {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Connecting => f.write_str("Connecting"),
+			Self::Streaming => f.write_str("Streaming"),
+			Self::Error(message) => write!(f, "Error: {message}"),
+			Self::Reconnecting => f.write_str("Reconnecting"),
+		}
+	}
+}
+
+/// Consumer of pipeline events.
+pub trait EventSink: Send {
+	/// Handle the next event of the pipeline.
+	fn handle(&mut self, event: PipelineEvent<'_>) -> Result<()>;
+}
+
+/// Hand the event to all sinks. Errors of single sinks are only reported, so
+/// that one broken output does not stop the pipeline.
+pub(crate) fn dispatch(sinks: &mut [Box<dyn EventSink>], event: PipelineEvent<'_>) {
+	for sink in sinks {
+		if let Err(err) = sink.handle(event) {
+			eprintln!("Error in pipeline output: {err}");
+		}
+	}
+}
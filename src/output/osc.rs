@@ -0,0 +1,176 @@
+//! OSC output of the tracking results via UDP to a local port, e.g. for
+//! TouchDesigner, Max or Pure Data.
+
+use std::{
+	io::ErrorKind,
+	net::{Ipv4Addr, UdpSocket},
+};
+
+use color_eyre::{eyre::ensure, Result};
+
+use super::{EventSink, PipelineEvent};
+
+/// OSC addresses of the sent messages.
+#[derive(Debug, Clone)]
+pub struct OscAddresses {
+	/// Address for the normalised point, with x and y as floats.
+	pub point: String,
+	/// Address for the area of movement, as float fraction of the frame.
+	pub area: String,
+	/// Address for gestures, with the gesture name as string.
+	pub gesture: String,
+	/// Address for the frame rate, as float.
+	pub fps: String,
+}
+
+impl Default for OscAddresses {
+	fn default() -> Self {
+		Self {
+			point: "/webcam/point".to_owned(),
+			area: "/webcam/area".to_owned(),
+			gesture: "/webcam/gesture".to_owned(),
+			fps: "/webcam/fps".to_owned(),
+		}
+	}
+}
+
+/// Argument of an OSC message.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum OscArg<'a> {
+	/// 32 bit float.
+	Float(f32),
+	/// String.
+	Str(&'a str),
+}
+
+/// Sink sending the pipeline events as OSC messages.
+#[derive(Debug)]
+pub struct OscOutput {
+	socket: UdpSocket,
+	addresses: OscAddresses,
+}
+
+impl OscOutput {
+	/// Create OSC output sending to the given port on localhost.
+	pub fn new(port: u16, addresses: OscAddresses) -> Result<Self> {
+		for address in [&addresses.point, &addresses.area, &addresses.gesture, &addresses.fps] {
+			ensure!(address.starts_with('/'), "OSC address `{address}` must start with `/`");
+		}
+
+		let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0))?;
+		socket.connect((Ipv4Addr::LOCALHOST, port))?;
+		Ok(Self { socket, addresses })
+	}
+
+	/// Send a message with the given arguments. Messages are dropped, while
+	/// no application listens on the port.
+	fn send(&self, address: &str, args: &[OscArg<'_>]) -> Result<()> {
+		match self.socket.send(&encode_message(address, args)) {
+			// The connected socket reports, that an earlier message was refused.
+			Err(err) if err.kind() == ErrorKind::ConnectionRefused => Ok(()),
+			result => {
+				result?;
+				Ok(())
+			}
+		}
+	}
+}
+
+impl EventSink for OscOutput {
+	fn handle(&mut self, event: PipelineEvent<'_>) -> Result<()> {
+		match event {
+			PipelineEvent::Point { position, dimensions } => {
+				let x = position.0 as f32 / dimensions.0.max(1) as f32;
+				let y = position.1 as f32 / dimensions.1.max(1) as f32;
+				self.send(&self.addresses.point, &[OscArg::Float(x), OscArg::Float(y)])
+			}
+			PipelineEvent::Difference { frame: diff_img, whole_frame: false } => {
+				let (width, height) = diff_img.dimensions();
+				let area = diff_img.motion_fraction(0, 0, width, height);
+				self.send(&self.addresses.area, &[OscArg::Float(area)])
+			}
+			PipelineEvent::Gesture(gesture) => {
+				self.send(&self.addresses.gesture, &[OscArg::Str(gesture.name())])
+			}
+			PipelineEvent::Fps(fps) => self.send(&self.addresses.fps, &[OscArg::Float(fps)]),
+			PipelineEvent::Frame(..)
+			| PipelineEvent::Difference { whole_frame: true, .. }
+			| PipelineEvent::SmoothedPoint { .. }
+			| PipelineEvent::Status(_)
+			| PipelineEvent::Latency(_)
+			| PipelineEvent::Stats(_) => Ok(()),
+		}
+	}
+}
+
+/// Encode an OSC message.
+fn encode_message(address: &str, args: &[OscArg<'_>]) -> Vec<u8> {
+	let mut message = Vec::new();
+	push_padded_str(&mut message, address);
+
+	let mut type_tags = String::from(",");
+	type_tags.extend(args.iter().map(|arg| match arg {
+		OscArg::Float(_) => 'f',
+		OscArg::Str(_) => 's',
+	}));
+	push_padded_str(&mut message, &type_tags);
+
+	for arg in args {
+		match arg {
+			OscArg::Float(value) => message.extend_from_slice(&value.to_be_bytes()),
+			OscArg::Str(value) => push_padded_str(&mut message, value),
+		}
+	}
+	message
+}
+
+/// Append a null terminated string padded to a multiple of 4 bytes.
+fn push_padded_str(buffer: &mut Vec<u8>, value: &str) {
+	buffer.extend_from_slice(value.as_bytes());
+	let padding = 4 - value.len() % 4;
+	buffer.resize(buffer.len() + padding, 0);
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+
+	use image::{GrayImage, Luma};
+
+	use super::*;
+	use crate::camera::analysis::Frame;
+
+	#[test]
+	fn send_to_listener() {
+		let listener = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+		listener.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+		let port = listener.local_addr().unwrap().port();
+		let mut output = OscOutput::new(port, OscAddresses::default()).unwrap();
+
+		output
+			.handle(PipelineEvent::Point { position: (160, 60), dimensions: (320, 240) })
+			.unwrap();
+		let mut buffer = [0; 64];
+		let len = listener.recv(&mut buffer).unwrap();
+		let mut expected = b"/webcam/point\0\0\0,ff\0".to_vec();
+		expected.extend_from_slice(&0.5_f32.to_be_bytes());
+		expected.extend_from_slice(&0.25_f32.to_be_bytes());
+		assert_eq!(&buffer[..len], expected);
+
+		// Changes of the whole frame are no motion area.
+		let frame = Frame::Luma(GrayImage::from_pixel(16, 8, Luma([0xFF])));
+		output.handle(PipelineEvent::Difference { frame: &frame, whole_frame: true }).unwrap();
+		output.handle(PipelineEvent::Gesture(crate::output::Gesture::Dwell)).unwrap();
+		let len = listener.recv(&mut buffer).unwrap();
+		assert_eq!(&buffer[..len], b"/webcam/gesture\0,s\0\0dwell\0\0\0");
+	}
+
+	#[test]
+	fn send_without_listener() {
+		let port = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap().local_addr().unwrap().port();
+		let mut output = OscOutput::new(port, OscAddresses::default()).unwrap();
+		for _ in 0..3 {
+			output.handle(PipelineEvent::Gesture(crate::output::Gesture::Dwell)).unwrap();
+		}
+	}
+}
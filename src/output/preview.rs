@@ -0,0 +1,432 @@
+//! Embedded HTTP server on localhost for previewing the pipeline in a browser.
+//! Serves the frames as MJPEG streams and the events via WebSocket.
+
+use std::{
+	collections::HashMap,
+	io::{BufRead, BufReader, Read, Write},
+	net::{Ipv4Addr, TcpListener, TcpStream},
+	sync::{
+		atomic::{AtomicUsize, Ordering},
+		Arc, Condvar, Mutex,
+	},
+	thread,
+	time::Duration,
+};
+
+use color_eyre::{eyre::eyre, Result};
+use image::{codecs::jpeg::JpegEncoder, ColorType};
+use tungstenite::{handshake::derive_accept_key, protocol::Role, Message, WebSocket};
+
+use super::{json::JsonEvent, EventSink, FrameKind, PipelineEvent};
+use crate::camera::{analysis::Frame, pool::FramePool};
+
+/// Time writing to a client may block before the client is disconnected.
+const WRITE_TIMEOUT: Duration = Duration::from_millis(500);
+/// Quality of the JPEG frames.
+const JPEG_QUALITY: u8 = 80;
+/// Boundary between the frames of the MJPEG streams.
+const BOUNDARY: &str = "webcam-mouse-frame";
+/// Page showing all streams and events. The images of the streams replace
+/// `{images}`, see [`index_page`].
+const INDEX_PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head><title>Webcam Mouse Preview</title></head>
+<body>
+{images}
+<pre id="event"></pre>
+<script>
+const socket = new WebSocket(`ws://${location.host}/events`);
+socket.onmessage = (message) => document.getElementById("event").textContent = message.data;
+</script>
+</body>
+</html>
+"#;
+
+/// Latest frame of a stream, encoded as JPEG.
+#[derive(Debug, Default)]
+struct FrameStream {
+	/// Number of connected clients. Frames are only encoded if there are any.
+	clients: AtomicUsize,
+	/// Latest frame, which is not encoded yet.
+	pending: Mutex<Option<Frame>>,
+	/// Pool of the buffers of pending frames.
+	pool: FramePool,
+	/// Notification about pending frames.
+	submitted: Condvar,
+	/// Sequence number and data of the latest frame.
+	latest: Mutex<(u64, Arc<Vec<u8>>)>,
+	/// Notification about new frames.
+	updated: Condvar,
+}
+
+impl FrameStream {
+	/// Hand a new frame to the encoder thread. A pending frame, which is not
+	/// encoded yet, is replaced, so that a slow encoder never delays the
+	/// pipeline.
+	fn submit(&self, frame: &Frame) {
+		let copy = self.pool.copy_frame(frame);
+		let replaced = self.pending.lock().unwrap_or_else(|err| err.into_inner()).replace(copy);
+		self.submitted.notify_one();
+		if let Some(replaced) = replaced {
+			self.pool.recycle_frame(replaced);
+		}
+	}
+
+	/// Encode the pending frames forever.
+	fn run_encoder(&self) {
+		loop {
+			let pending = self.pending.lock().unwrap_or_else(|err| err.into_inner());
+			let mut pending = self
+				.submitted
+				.wait_while(pending, |pending| pending.is_none())
+				.unwrap_or_else(|err| err.into_inner());
+			let Some(frame) = pending.take() else {
+				continue;
+			};
+			drop(pending);
+			if let Err(err) = self.publish(&frame) {
+				eprintln!("Error encoding preview frame: {err}");
+			}
+			self.pool.recycle_frame(frame);
+		}
+	}
+
+	/// Encode and publish a new frame.
+	fn publish(&self, frame: &Frame) -> Result<()> {
+		let (data, color_type) = match frame {
+			Frame::Rgb(image) => (image.as_raw(), ColorType::Rgb8),
+			Frame::Luma(image) => (image.as_raw(), ColorType::L8),
+		};
+		let (width, height) = frame.dimensions();
+		let mut jpeg = Vec::new();
+		JpegEncoder::new_with_quality(&mut jpeg, JPEG_QUALITY)
+			.encode(data, width, height, color_type)?;
+
+		let mut latest = self.latest.lock().unwrap_or_else(|err| err.into_inner());
+		*latest = (latest.0 + 1, Arc::new(jpeg));
+		self.updated.notify_all();
+		Ok(())
+	}
+
+	/// Wait for a frame newer than the given sequence number.
+	fn next_frame(&self, seen: u64) -> (u64, Arc<Vec<u8>>) {
+		let latest = self.latest.lock().unwrap_or_else(|err| err.into_inner());
+		let latest = self.updated.wait_while(latest, |latest| latest.0 == seen);
+		latest.unwrap_or_else(|err| err.into_inner()).clone()
+	}
+}
+
+/// Shared state of the server and its connection threads.
+#[derive(Debug, Default)]
+struct PreviewState {
+	/// Whether the pipeline stabilises frames, so there is a stabilised stream.
+	stabilise: bool,
+	original: FrameStream,
+	processed: FrameStream,
+	stabilised: FrameStream,
+	difference: FrameStream,
+	event_clients: Mutex<Vec<WebSocket<TcpStream>>>,
+}
+
+impl PreviewState {
+	/// Get the stream of the frame kind.
+	fn stream(&self, kind: FrameKind) -> &FrameStream {
+		match kind {
+			FrameKind::Original => &self.original,
+			FrameKind::Processed => &self.processed,
+			FrameKind::Stabilised => &self.stabilised,
+			FrameKind::Difference => &self.difference,
+		}
+	}
+}
+
+/// Sink serving the frames and events to browsers.
+#[derive(Debug)]
+pub struct PreviewServer {
+	state: Arc<PreviewState>,
+}
+
+impl PreviewServer {
+	/// Bind the server to the given port on localhost and start accepting
+	/// clients. The stabilised frames are only served, if the pipeline
+	/// stabilises frames.
+	pub fn bind(port: u16, stabilise: bool) -> Result<Self> {
+		Ok(Self::start(TcpListener::bind((Ipv4Addr::LOCALHOST, port))?, stabilise))
+	}
+
+	/// Start accepting clients of the listener and encoding their frames.
+	fn start(listener: TcpListener, stabilise: bool) -> Self {
+		let state = Arc::new(PreviewState { stabilise, ..PreviewState::default() });
+
+		// Encode the frames on their own threads, not to slow down the
+		// pipeline.
+		let kinds = [
+			FrameKind::Original,
+			FrameKind::Processed,
+			FrameKind::Stabilised,
+			FrameKind::Difference,
+		];
+		for kind in kinds.into_iter().filter(|kind| stabilise || *kind != FrameKind::Stabilised) {
+			let state = state.clone();
+			thread::spawn(move || state.stream(kind).run_encoder());
+		}
+
+		let server_state = state.clone();
+		thread::spawn(move || {
+			for stream in listener.incoming() {
+				let state = server_state.clone();
+				thread::spawn(move || {
+					if let Err(err) = stream.map_err(Into::into).and_then(|s| serve(s, &state)) {
+						eprintln!("Error serving preview client: {err}");
+					}
+				});
+			}
+		});
+
+		Self { state }
+	}
+}
+
+impl EventSink for PreviewServer {
+	fn handle(&mut self, event: PipelineEvent<'_>) -> Result<()> {
+		let frame = match event {
+			PipelineEvent::Frame(kind, frame) => Some((kind, frame)),
+			PipelineEvent::Difference { frame, .. } => Some((FrameKind::Difference, frame)),
+			_ => None,
+		};
+		if let Some((kind, frame)) = frame {
+			let stream = self.state.stream(kind);
+			if stream.clients.load(Ordering::Relaxed) > 0 {
+				stream.submit(frame);
+			}
+		} else if let Some(event) = JsonEvent::from_event(event) {
+			let message = serde_json::to_string(&event)?;
+			// Disconnect clients, which cannot keep up or are gone.
+			self.state
+				.event_clients
+				.lock()
+				.unwrap_or_else(|err| err.into_inner())
+				.retain_mut(|client| client.write_message(Message::Text(message.clone())).is_ok());
+		}
+		Ok(())
+	}
+}
+
+/// Serve a client's request.
+fn serve(stream: TcpStream, state: &PreviewState) -> Result<()> {
+	stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+	let (path, headers) = read_request(&stream)?;
+	let mut stream = stream;
+
+	let kind = match path.as_str() {
+		"/" => {
+			let page = index_page(state.stabilise);
+			return write_response(&mut stream, "200 OK", "text/html", page.as_bytes());
+		}
+		"/events" => return accept_websocket(stream, &headers, state),
+		"/original.mjpg" => FrameKind::Original,
+		"/processed.mjpg" => FrameKind::Processed,
+		"/stabilised.mjpg" if state.stabilise => FrameKind::Stabilised,
+		"/difference.mjpg" => FrameKind::Difference,
+		_ => return write_response(&mut stream, "404 Not Found", "text/plain", b"Not found"),
+	};
+
+	let frames = state.stream(kind);
+	frames.clients.fetch_add(1, Ordering::Relaxed);
+	let result = stream_frames(&mut stream, frames);
+	frames.clients.fetch_sub(1, Ordering::Relaxed);
+	result
+}
+
+/// Index page with the images of the streams, the stabilised one only with
+/// stabilisation.
+fn index_page(stabilise: bool) -> String {
+	let mut paths = vec!["/original.mjpg", "/processed.mjpg"];
+	if stabilise {
+		paths.push("/stabilised.mjpg");
+	}
+	paths.push("/difference.mjpg");
+	let images: Vec<String> = paths.iter().map(|path| format!(r#"<img src="{path}">"#)).collect();
+	INDEX_PAGE.replace("{images}", &images.join(" "))
+}
+
+/// Read the request line and headers, returning the path and the headers with
+/// lowercase names.
+fn read_request(stream: impl Read) -> Result<(String, HashMap<String, String>)> {
+	let mut reader = BufReader::new(stream);
+	let mut request_line = String::new();
+	reader.read_line(&mut request_line)?;
+	let path = request_line
+		.split_whitespace()
+		.nth(1)
+		.ok_or_else(|| eyre!("Invalid request line: {request_line}"))?;
+	// Ignore query parameters.
+	let path = path.split('?').next().unwrap_or(path).to_owned();
+
+	let mut headers = HashMap::new();
+	loop {
+		let mut line = String::new();
+		if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+			break;
+		}
+		if let Some((name, value)) = line.split_once(':') {
+			headers.insert(name.trim().to_lowercase(), value.trim().to_owned());
+		}
+	}
+	Ok((path, headers))
+}
+
+/// Write a complete response.
+fn write_response(
+	stream: &mut TcpStream,
+	status: &str,
+	content_type: &str,
+	body: &[u8],
+) -> Result<()> {
+	write!(stream, "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\n")?;
+	write!(stream, "Content-Length: {}\r\nConnection: close\r\n\r\n", body.len())?;
+	stream.write_all(body)?;
+	Ok(())
+}
+
+/// Send all new frames of the stream as MJPEG until the client disconnects.
+fn stream_frames(stream: &mut TcpStream, frames: &FrameStream) -> Result<()> {
+	write!(stream, "HTTP/1.1 200 OK\r\n")?;
+	write!(stream, "Content-Type: multipart/x-mixed-replace; boundary={BOUNDARY}\r\n")?;
+	write!(stream, "Cache-Control: no-cache\r\nConnection: close\r\n\r\n")?;
+
+	let mut seen = 0;
+	loop {
+		let (sequence, jpeg) = frames.next_frame(seen);
+		seen = sequence;
+		write!(
+			stream,
+			"--{BOUNDARY}\r\nContent-Type: image/jpeg\r\nContent-Length: {}\r\n\r\n",
+			jpeg.len()
+		)?;
+		stream.write_all(&jpeg)?;
+		stream.write_all(b"\r\n")?;
+	}
+}
+
+/// Complete the WebSocket handshake and register the client for events.
+fn accept_websocket(
+	mut stream: TcpStream,
+	headers: &HashMap<String, String>,
+	state: &PreviewState,
+) -> Result<()> {
+	let key = match headers.get("sec-websocket-key") {
+		Some(key) => key,
+		None => {
+			return write_response(&mut stream, "400 Bad Request", "text/plain", b"WebSocket only");
+		}
+	};
+	let accept_key = derive_accept_key(key.as_bytes());
+	write!(stream, "HTTP/1.1 101 Switching Protocols\r\n")?;
+	write!(stream, "Upgrade: websocket\r\nConnection: Upgrade\r\n")?;
+	write!(stream, "Sec-WebSocket-Accept: {accept_key}\r\n\r\n")?;
+
+	let socket = WebSocket::from_raw_socket(stream, Role::Server, None);
+	state.event_clients.lock().unwrap_or_else(|err| err.into_inner()).push(socket);
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use image::GrayImage;
+
+	use super::*;
+
+	/// Start a server on a free port and send the request to it.
+	fn connect(request: &str) -> (PreviewServer, TcpStream) {
+		let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+		let address = listener.local_addr().unwrap();
+		let server = PreviewServer::start(listener, false);
+		let mut client = TcpStream::connect(address).unwrap();
+		client.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+		client.write_all(request.as_bytes()).unwrap();
+		(server, client)
+	}
+
+	/// Read a head up to the empty line, leaving the body in the stream.
+	fn read_head(stream: &mut TcpStream) -> String {
+		let mut head = Vec::new();
+		while !head.ends_with(b"\r\n\r\n") {
+			let mut byte = [0];
+			stream.read_exact(&mut byte).unwrap();
+			head.push(byte[0]);
+		}
+		String::from_utf8(head).unwrap()
+	}
+
+	#[test]
+	fn request_path_and_headers() {
+		let request =
+			b"GET /events?debug HTTP/1.1\r\nHost: localhost\r\nSec-WebSocket-Key: abc\r\n\r\n";
+		let (path, headers) = read_request(&request[..]).unwrap();
+		assert_eq!(path, "/events");
+		assert_eq!(headers["host"], "localhost");
+		assert_eq!(headers["sec-websocket-key"], "abc");
+		assert!(read_request(&b"\r\n"[..]).is_err());
+	}
+
+	#[test]
+	fn stabilised_stream_only_with_stabilisation() {
+		assert!(index_page(true).contains(r#"<img src="/stabilised.mjpg">"#));
+		assert!(!index_page(false).contains("stabilised"));
+		assert!(index_page(false).contains(r#"<img src="/difference.mjpg">"#));
+
+		let (_server, mut client) = connect("GET /stabilised.mjpg HTTP/1.1\r\n\r\n");
+		assert!(read_head(&mut client).starts_with("HTTP/1.1 404 Not Found\r\n"));
+	}
+
+	#[test]
+	fn websocket_events() {
+		// Example handshake of RFC 6455.
+		let (mut server, mut client) = connect(
+			"GET /events HTTP/1.1\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+			 Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n",
+		);
+		let head = read_head(&mut client);
+		assert!(head.starts_with("HTTP/1.1 101 Switching Protocols\r\n"));
+		assert!(head.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"));
+
+		// The client is registered after the handshake is sent.
+		while server.state.event_clients.lock().unwrap().is_empty() {
+			thread::sleep(Duration::from_millis(1));
+		}
+		server.handle(PipelineEvent::Fps(30.0)).unwrap();
+		let mut socket = WebSocket::from_raw_socket(client, Role::Client, None);
+		assert_eq!(
+			socket.read_message().unwrap(),
+			Message::Text(r#"{"event":"fps","fps":30.0}"#.to_owned())
+		);
+	}
+
+	#[test]
+	fn mjpeg_stream() {
+		let (mut server, mut client) = connect("GET /difference.mjpg HTTP/1.1\r\n\r\n");
+		let head = read_head(&mut client);
+		let content_type = "multipart/x-mixed-replace; boundary=webcam-mouse-frame";
+		assert!(head.contains(&format!("Content-Type: {content_type}\r\n")));
+
+		// Only streams with clients are encoded.
+		let frame = Frame::Luma(GrayImage::new(16, 8));
+		server.handle(PipelineEvent::Frame(FrameKind::Processed, &frame)).unwrap();
+		assert!(server.state.processed.pending.lock().unwrap().is_none());
+
+		server.handle(PipelineEvent::Difference { frame: &frame, whole_frame: false }).unwrap();
+		let part = read_head(&mut client);
+		assert!(part.starts_with("--webcam-mouse-frame\r\nContent-Type: image/jpeg\r\n"));
+		let len: usize = part
+			.lines()
+			.find_map(|line| line.strip_prefix("Content-Length: "))
+			.unwrap()
+			.parse()
+			.unwrap();
+		let mut jpeg = vec![0; len + 2];
+		client.read_exact(&mut jpeg).unwrap();
+		assert!(jpeg.starts_with(&[0xff, 0xd8]));
+		assert!(jpeg.ends_with(b"\r\n"));
+	}
+}
@@ -0,0 +1,105 @@
+//! Virtual input devices using Linux' uinput.
+
+use color_eyre::{eyre::eyre, Result};
+use evdev::{
+	uinput::{VirtualDevice, VirtualDeviceBuilder},
+	AbsInfo, AbsoluteAxisType, AttributeSet, EventType, InputEvent, Key, UinputAbsSetup,
+};
+
+/// Name of the virtual devices as shown to the system.
+const DEVICE_NAME: &str = "Webcam Mouse";
+
+/// Virtual input device to emit input events into the system.
+pub struct VirtualInput {
+	device: VirtualDevice,
+}
+
+impl VirtualInput {
+	/// Create a virtual keyboard supporting all regular keys.
+	pub fn keyboard() -> Result<Self> {
+		let keys: AttributeSet<Key> =
+			(Key::KEY_ESC.code()..=Key::KEY_MICMUTE.code()).map(Key::new).collect();
+		let device = VirtualDeviceBuilder::new()?.name(DEVICE_NAME).with_keys(&keys)?.build()?;
+		Ok(Self { device })
+	}
+
+	/// Create a virtual joystick with X and Y axes in the range `-axis_max` to
+	/// `axis_max` and a trigger button.
+	pub fn joystick(axis_max: i32) -> Result<Self> {
+		let buttons: AttributeSet<Key> = [Key::BTN_TRIGGER].into_iter().collect();
+		let axis = AbsInfo::new(0, -axis_max, axis_max, 0, 0, 0);
+		let device = VirtualDeviceBuilder::new()?
+			.name(DEVICE_NAME)
+			.with_keys(&buttons)?
+			.with_absolute_axis(&UinputAbsSetup::new(AbsoluteAxisType::ABS_X, axis))?
+			.with_absolute_axis(&UinputAbsSetup::new(AbsoluteAxisType::ABS_Y, axis))?
+			.build()?;
+		Ok(Self { device })
+	}
+
+	/// Set the absolute X and Y axes.
+	pub fn move_axes(&mut self, x: i32, y: i32) -> Result<()> {
+		self.device.emit(&[
+			InputEvent::new(EventType::ABSOLUTE, AbsoluteAxisType::ABS_X.0, x),
+			InputEvent::new(EventType::ABSOLUTE, AbsoluteAxisType::ABS_Y.0, y),
+		])?;
+		Ok(())
+	}
+
+	/// Press and release a single key or button.
+	pub fn click(&mut self, key: Key) -> Result<()> {
+		self.press_chord(&[key])
+	}
+
+	/// Press all keys of the chord in order and release them in reverse order.
+	pub fn press_chord(&mut self, chord: &[Key]) -> Result<()> {
+		let press: Vec<_> =
+			chord.iter().map(|key| InputEvent::new(EventType::KEY, key.code(), 1)).collect();
+		self.device.emit(&press)?;
+		let release: Vec<_> =
+			chord.iter().rev().map(|key| InputEvent::new(EventType::KEY, key.code(), 0)).collect();
+		self.device.emit(&release)?;
+		Ok(())
+	}
+}
+
+impl std::fmt::Debug for VirtualInput {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("VirtualInput").field("device", &"<object>").finish()
+	}
+}
+
+/// Parse a key chord like `ctrl+shift+a` or `KEY_LEFTCTRL+KEY_A` into its keys.
+pub fn parse_chord(chord: &str) -> Result<Vec<Key>> {
+	chord
+		.split('+')
+		.map(|part| {
+			let name = match part.trim().to_uppercase().as_str() {
+				"CTRL" => "LEFTCTRL".to_owned(),
+				"SHIFT" => "LEFTSHIFT".to_owned(),
+				"ALT" => "LEFTALT".to_owned(),
+				"SUPER" | "META" => "LEFTMETA".to_owned(),
+				name => name.to_owned(),
+			};
+			let name = if name.starts_with("KEY_") || name.starts_with("BTN_") {
+				name
+			} else {
+				format!("KEY_{name}")
+			};
+			name.parse().map_err(|_| eyre!("Unknown key `{}` in chord `{chord}`", part.trim()))
+		})
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parse_key_chords() {
+		assert_eq!(parse_chord("ctrl+Space").unwrap(), [Key::KEY_LEFTCTRL, Key::KEY_SPACE]);
+		assert_eq!(parse_chord("KEY_A").unwrap(), [Key::KEY_A]);
+		assert!(parse_chord("ctrl+").is_err());
+		assert!(parse_chord("nokey").is_err());
+	}
+}
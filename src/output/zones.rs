@@ -0,0 +1,39 @@
+//! Motion zones, regions of the camera frame that trigger key chords when
+//! there is movement inside.
+
+use std::sync::mpsc;
+
+use druid::Data;
+
+/// Channel sender for updating the active zones.
+pub type ZoneSender = mpsc::Sender<Vec<Zone>>;
+/// Channel receiver for updating the active zones.
+pub type ZoneReceiver = mpsc::Receiver<Vec<Zone>>;
+
+/// Rectangular region of the camera frame and the key chord it emits.
+#[derive(Debug, Clone, PartialEq, Eq, Data)]
+pub struct Zone {
+	/// Left edge in frame pixels.
+	pub x: u32,
+	/// Top edge in frame pixels.
+	pub y: u32,
+	/// Width in frame pixels.
+	pub width: u32,
+	/// Height in frame pixels.
+	pub height: u32,
+	/// Key chord to emit, e.g. `ctrl+space`.
+	pub chord: String,
+}
+
+impl Zone {
+	/// Create a zone spanning the rectangle between the two corners.
+	pub fn from_corners(a: (u32, u32), b: (u32, u32), chord: String) -> Self {
+		Self {
+			x: a.0.min(b.0),
+			y: a.1.min(b.1),
+			width: a.0.abs_diff(b.0),
+			height: a.1.abs_diff(b.1),
+			chord,
+		}
+	}
+}
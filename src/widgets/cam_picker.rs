@@ -1,47 +1,85 @@
 //! Combobox for picking the camera.
 
-use std::sync::mpsc;
+use std::sync::Arc;
 
-use druid::{widget::Controller, Widget, WidgetExt};
+use druid::{
+	widget::{Controller, ViewSwitcher},
+	Data, Lens, Selector, Widget, WidgetExt,
+};
 use druid_widget_nursery::DropdownSelect;
-use nokhwa::{query_devices, CaptureAPIBackend};
 
-/// Channel sender for camera picker update
-pub(crate) type PickerSender = mpsc::Sender<usize>;
+use super::status;
+use crate::camera::{
+	devices::{CameraDevice, CameraDevices, S_CAMERA_DEVICES},
+	CameraRequest, RequestSender,
+};
 
-/// Create the widget for the camera picker
-pub fn widget(update_sender: PickerSender) -> impl Widget<usize> {
-	let mut cameras: Vec<_> = query_devices(CaptureAPIBackend::Auto).expect("listing cameras");
-	cameras.sort_by_key(|info| info.index());
-	let dropdown_cams: Vec<_> = cameras
-		.into_iter()
-		.map(|info| (format!("{}: {}", info.index(), info.human_name()), info.index()))
-		.collect();
+/// State of the camera picker.
+#[derive(Debug, Clone, Default, Data, Lens)]
+pub struct CameraPickerState {
+	/// Cameras currently available.
+	devices: Arc<Vec<CameraDevice>>,
+	/// Index of the picked camera.
+	selected: usize,
+}
+
+impl CameraPickerState {
+	/// Whether any system camera is available.
+	pub fn has_cameras(&self) -> bool {
+		!self.devices.is_empty()
+	}
+}
 
-	DropdownSelect::new(dropdown_cams).controller(SelectionController::new(update_sender))
+/// Create the widget for the camera picker. The dropdown is rebuilt whenever
+/// cameras are plugged in or out.
+pub fn widget(update_sender: RequestSender) -> impl Widget<CameraPickerState> {
+	ViewSwitcher::new(
+		|data: &CameraPickerState, _env| data.devices.clone(),
+		|devices, _data, _env| {
+			let dropdown_cams: Vec<_> = devices
+				.iter()
+				.map(|device| (format!("{}: {}", device.index, device.name), device.index))
+				.collect();
+			Box::new(DropdownSelect::new(dropdown_cams).lens(CameraPickerState::selected))
+		},
+	)
+	.controller(SelectionController::new(update_sender))
 }
 
-/// Controller for changing camera when the selection is changed
+/// Controller for receiving the available cameras and changing camera when
+/// the selection is changed
 struct SelectionController {
-	sender: PickerSender,
+	sender: RequestSender,
+	/// Camera the handler reported to use, which does not need to be picked.
+	reported: Option<usize>,
 }
 
 impl SelectionController {
 	/// Create new SelectionController
-	pub fn new(sender: PickerSender) -> Self {
-		Self { sender }
+	pub fn new(sender: RequestSender) -> Self {
+		Self { sender, reported: None }
 	}
 }
 
-impl<W: Widget<usize>> Controller<usize, W> for SelectionController {
+impl<W: Widget<CameraPickerState>> Controller<CameraPickerState, W> for SelectionController {
 	fn event(
 		&mut self,
 		child: &mut W,
 		ctx: &mut druid::EventCtx,
 		event: &druid::Event,
-		data: &mut usize,
+		data: &mut CameraPickerState,
 		env: &druid::Env,
 	) {
+		if let druid::Event::Command(command) = event {
+			if let Some(devices) = command.get(Selector::<CameraDevices>::new(S_CAMERA_DEVICES)) {
+				data.devices = Arc::new(devices.available.clone());
+				if let Some(current) = devices.current {
+					self.reported = Some(current);
+					data.selected = current;
+				}
+			}
+		}
+
 		child.event(ctx, event, data, env)
 	}
 
@@ -50,7 +88,7 @@ impl<W: Widget<usize>> Controller<usize, W> for SelectionController {
 		child: &mut W,
 		ctx: &mut druid::LifeCycleCtx,
 		event: &druid::LifeCycle,
-		data: &usize,
+		data: &CameraPickerState,
 		env: &druid::Env,
 	) {
 		child.lifecycle(ctx, event, data, env)
@@ -60,12 +98,15 @@ impl<W: Widget<usize>> Controller<usize, W> for SelectionController {
 		&mut self,
 		child: &mut W,
 		ctx: &mut druid::UpdateCtx,
-		old_data: &usize,
-		data: &usize,
+		old_data: &CameraPickerState,
+		data: &CameraPickerState,
 		env: &druid::Env,
 	) {
-		if *old_data != *data {
-			self.sender.send(*data).expect("sending picked camera");
+		if old_data.selected != data.selected && self.reported != Some(data.selected) {
+			self.reported = Some(data.selected);
+			if let Err(err) = self.sender.send(CameraRequest::Pick(data.selected)) {
+				ctx.submit_command(status::request_failed(err));
+			}
 		}
 
 		child.update(ctx, old_data, data, env)
@@ -0,0 +1,183 @@
+//! Panel for adjusting the camera controls, like exposure or focus.
+
+use std::sync::Arc;
+
+use druid::{
+	widget::{
+		Checkbox, Controller, CrossAxisAlignment, Either, Flex, Label, List, SizedBox, Slider,
+	},
+	Data, Env, Event, Lens, Selector, Widget, WidgetExt,
+};
+
+use super::status;
+use crate::camera::{
+	controls::{ControlInfo, ControlKind, ControlSetting, S_CAMERA_CONTROLS},
+	CameraRequest, RequestSender,
+};
+
+/// State of the camera controls panel.
+#[derive(Debug, Clone, Default, Data, Lens)]
+pub struct ControlsState {
+	/// Controls supported by the camera.
+	controls: Arc<Vec<ControlState>>,
+}
+
+/// State of a single camera control. The slider position is kept relative
+/// to the control's range, as sliders cannot change their range.
+#[derive(Debug, Clone, Data, Lens)]
+struct ControlState {
+	/// Control kind.
+	kind: ControlKind,
+	/// Minimum value.
+	minimum: i32,
+	/// Maximum value.
+	maximum: i32,
+	/// Step between values.
+	step: i32,
+	/// Slider position between 0 and 1.
+	position: f64,
+	/// Whether the camera adjusts the control automatically.
+	automatic: bool,
+	/// Whether the camera can adjust the control automatically.
+	automatic_supported: bool,
+}
+
+impl ControlState {
+	/// Value of the control at the current slider position, rounded to the
+	/// control's step.
+	fn value(&self) -> i32 {
+		let step = self.step.max(1);
+		let steps = (self.position * f64::from(self.maximum - self.minimum) / f64::from(step))
+			.round() as i32;
+		(self.minimum + steps * step).clamp(self.minimum, self.maximum)
+	}
+
+	/// Setting to request from the camera.
+	fn setting(&self) -> ControlSetting {
+		ControlSetting { kind: self.kind, value: self.value(), automatic: self.automatic }
+	}
+}
+
+impl From<&ControlInfo> for ControlState {
+	fn from(info: &ControlInfo) -> Self {
+		let range = info.maximum - info.minimum;
+		let position = if range > 0 {
+			f64::from(info.setting.value - info.minimum) / f64::from(range)
+		} else {
+			0.0
+		};
+		Self {
+			kind: info.setting.kind,
+			minimum: info.minimum,
+			maximum: info.maximum,
+			step: info.step,
+			position,
+			automatic: info.setting.automatic,
+			automatic_supported: info.automatic_supported,
+		}
+	}
+}
+
+/// Create the widget for the camera controls panel.
+pub fn widget(update_sender: RequestSender) -> impl Widget<ControlsState> {
+	Flex::column()
+		.cross_axis_alignment(CrossAxisAlignment::Start)
+		.with_child(Label::new("Camera controls:"))
+		.with_child(List::new(control_row).lens(ControlsState::controls))
+		.controller(ControlsController::new(update_sender))
+}
+
+/// Build the widgets for a single control. Controls, which the camera can
+/// adjust automatically, can be locked by disabling the adjustment.
+fn control_row() -> impl Widget<ControlState> {
+	let label = Label::dynamic(|data: &ControlState, _env| {
+		if data.automatic {
+			format!("{}: auto", data.kind)
+		} else {
+			format!("{}: {}", data.kind, data.value())
+		}
+	});
+	let slider = Slider::new()
+		.disabled_if(|data: &ControlState, _env| data.automatic)
+		.lens(ControlState::position);
+	let automatic = Either::new(
+		|data: &ControlState, _env| data.automatic_supported,
+		Checkbox::new("Auto").lens(ControlState::automatic),
+		SizedBox::empty(),
+	);
+
+	Flex::column()
+		.cross_axis_alignment(CrossAxisAlignment::Start)
+		.with_child(label)
+		.with_child(Flex::row().with_child(slider).with_child(automatic))
+}
+
+/// Controller for receiving the camera's controls and requesting changed
+/// settings.
+struct ControlsController {
+	sender: RequestSender,
+	/// Controls as reported by the camera, which do not need to be requested.
+	reported: Arc<Vec<ControlState>>,
+}
+
+impl ControlsController {
+	/// Create new ControlsController
+	pub fn new(sender: RequestSender) -> Self {
+		Self { sender, reported: Arc::default() }
+	}
+}
+
+impl<W: Widget<ControlsState>> Controller<ControlsState, W> for ControlsController {
+	fn event(
+		&mut self,
+		child: &mut W,
+		ctx: &mut druid::EventCtx,
+		event: &Event,
+		data: &mut ControlsState,
+		env: &Env,
+	) {
+		if let Event::Command(command) = event {
+			if let Some(controls) =
+				command.get(Selector::<Vec<ControlInfo>>::new(S_CAMERA_CONTROLS))
+			{
+				data.controls = Arc::new(controls.iter().map(ControlState::from).collect());
+				self.reported = data.controls.clone();
+			}
+		}
+
+		child.event(ctx, event, data, env)
+	}
+
+	fn lifecycle(
+		&mut self,
+		child: &mut W,
+		ctx: &mut druid::LifeCycleCtx,
+		event: &druid::LifeCycle,
+		data: &ControlsState,
+		env: &Env,
+	) {
+		child.lifecycle(ctx, event, data, env)
+	}
+
+	fn update(
+		&mut self,
+		child: &mut W,
+		ctx: &mut druid::UpdateCtx,
+		old_data: &ControlsState,
+		data: &ControlsState,
+		env: &Env,
+	) {
+		if !data.controls.same(&self.reported) && old_data.controls.len() == data.controls.len() {
+			for (old, new) in old_data.controls.iter().zip(data.controls.iter()) {
+				// Only request settings that actually changed the value.
+				if old.setting() != new.setting() {
+					if let Err(err) = self.sender.send(CameraRequest::Control(new.setting())) {
+						ctx.submit_command(status::request_failed(err));
+					}
+				}
+			}
+		}
+
+		child.update(ctx, old_data, data, env)
+	}
+}
@@ -0,0 +1,151 @@
+//! Dropdowns for picking the camera format.
+
+use std::{collections::BTreeSet, sync::Arc};
+
+use druid::{
+	widget::{Controller, CrossAxisAlignment, Flex, Label, ViewSwitcher},
+	Data, Env, Event, Lens, LensExt, Selector, Widget, WidgetExt,
+};
+use druid_widget_nursery::DropdownSelect;
+
+use super::status;
+use crate::camera::{
+	format::{CameraFormats, FormatChoice, PixelFormat, S_CAMERA_FORMATS},
+	CameraRequest, RequestSender,
+};
+
+/// State of the format picker.
+#[derive(Debug, Clone, Default, Data, Lens)]
+pub struct FormatState {
+	/// Formats supported by the camera.
+	available: Arc<Vec<FormatChoice>>,
+	/// Selected format.
+	selected: FormatChoice,
+}
+
+/// Create the widget for the format picker. The dropdowns are rebuilt
+/// whenever their options change.
+pub fn widget(update_sender: RequestSender) -> impl Widget<FormatState> {
+	ViewSwitcher::new(
+		|data: &FormatState, _env| {
+			(data.available.clone(), data.selected.pixel_format, data.selected.resolution)
+		},
+		|_options, data: &FormatState, _env| Box::new(dropdowns(data)),
+	)
+	.controller(FormatController::new(update_sender))
+}
+
+/// Build the dropdowns for the options available with the current selection.
+fn dropdowns(data: &FormatState) -> impl Widget<FormatState> {
+	let selected = data.selected;
+	let pixel_formats: BTreeSet<PixelFormat> =
+		data.available.iter().map(|format| format.pixel_format).collect();
+	let resolutions: BTreeSet<(u32, u32)> = data
+		.available
+		.iter()
+		.filter(|format| format.pixel_format == selected.pixel_format)
+		.map(|format| format.resolution)
+		.collect();
+	let frame_rates: BTreeSet<u32> = data
+		.available
+		.iter()
+		.filter(|format| {
+			format.pixel_format == selected.pixel_format && format.resolution == selected.resolution
+		})
+		.map(|format| format.frame_rate)
+		.collect();
+
+	let pixel_format =
+		DropdownSelect::new(pixel_formats.into_iter().map(|format| (format.to_string(), format)))
+			.lens(FormatState::selected.then(FormatChoice::pixel_format));
+	let resolution = DropdownSelect::new(
+		resolutions.into_iter().rev().map(|(w, h)| (format!("{w}x{h}"), (w, h))),
+	)
+	.lens(FormatState::selected.then(FormatChoice::resolution));
+	let frame_rate =
+		DropdownSelect::new(frame_rates.into_iter().rev().map(|fps| (format!("{fps} FPS"), fps)))
+			.lens(FormatState::selected.then(FormatChoice::frame_rate));
+
+	Flex::column()
+		.cross_axis_alignment(CrossAxisAlignment::Start)
+		.with_child(Label::new("Format:"))
+		.with_child(pixel_format)
+		.with_child(resolution)
+		.with_child(frame_rate)
+}
+
+/// Controller for receiving the camera's formats and requesting the selected
+/// format.
+struct FormatController {
+	sender: RequestSender,
+	/// Format the camera reported to use, which does not need to be requested.
+	reported: Option<FormatChoice>,
+}
+
+impl FormatController {
+	/// Create new FormatController
+	pub fn new(sender: RequestSender) -> Self {
+		Self { sender, reported: None }
+	}
+}
+
+impl<W: Widget<FormatState>> Controller<FormatState, W> for FormatController {
+	fn event(
+		&mut self,
+		child: &mut W,
+		ctx: &mut druid::EventCtx,
+		event: &Event,
+		data: &mut FormatState,
+		env: &Env,
+	) {
+		if let Event::Command(command) = event {
+			if let Some(formats) = command.get(Selector::<CameraFormats>::new(S_CAMERA_FORMATS)) {
+				self.reported = formats.current;
+				data.available = Arc::new(formats.available.clone());
+				data.selected = formats.current.unwrap_or_default();
+			}
+		}
+
+		child.event(ctx, event, data, env);
+
+		// Changing one dropdown can make the others invalid, so pick the
+		// closest available format.
+		if !data.available.contains(&data.selected) {
+			if let Some(closest) = data.selected.closest_in(&data.available) {
+				data.selected = closest;
+			}
+		}
+	}
+
+	fn lifecycle(
+		&mut self,
+		child: &mut W,
+		ctx: &mut druid::LifeCycleCtx,
+		event: &druid::LifeCycle,
+		data: &FormatState,
+		env: &Env,
+	) {
+		child.lifecycle(ctx, event, data, env)
+	}
+
+	fn update(
+		&mut self,
+		child: &mut W,
+		ctx: &mut druid::UpdateCtx,
+		old_data: &FormatState,
+		data: &FormatState,
+		env: &Env,
+	) {
+		if old_data.selected != data.selected
+			&& self.reported != Some(data.selected)
+			&& data.available.contains(&data.selected)
+		{
+			self.reported = Some(data.selected);
+			if let Err(err) = self.sender.send(CameraRequest::Format(data.selected)) {
+				ctx.submit_command(status::request_failed(err));
+			}
+		}
+
+		child.update(ctx, old_data, data, env)
+	}
+}
@@ -0,0 +1,295 @@
+//! Live chart and histogram of the frame intervals, to notice the camera
+//! dropping its frame rate, e.g. in low light.
+
+use std::{
+	collections::VecDeque,
+	sync::Arc,
+	time::{Duration, Instant},
+};
+
+use druid::{
+	kurbo::{BezPath, Line, Rect},
+	widget::{Controller, CrossAxisAlignment, Flex, Label},
+	BoxConstraints, Color, Data, Env, Event, EventCtx, LayoutCtx, Lens, LifeCycle, LifeCycleCtx,
+	PaintCtx, RenderContext, Selector, Size, UpdateCtx, Widget, WidgetExt,
+};
+
+use crate::camera::{
+	stats::{histogram, moving_average, summarize, Metric, MetricSummary},
+	S_FRAME_INTERVAL,
+};
+
+/// Time span shown in the chart.
+const WINDOW: Duration = Duration::from_secs(10);
+/// Number of frames of the moving average.
+const AVERAGE_FRAMES: usize = 15;
+/// Size of the chart.
+const CHART_SIZE: Size = Size::new(250.0, 80.0);
+/// Size of the histogram.
+const HISTOGRAM_SIZE: Size = Size::new(250.0, 40.0);
+/// Number of bars of the histogram.
+const HISTOGRAM_BINS: usize = 25;
+/// Intervals always included in the chart's range, in milliseconds. They are
+/// drawn as reference lines for 30 and 15 FPS.
+const REFERENCE_INTERVALS: [f32; 2] = [1000.0 / 30.0, 1000.0 / 15.0];
+
+/// Frame intervals of the recent frames.
+#[derive(Debug, Clone, Default, Data, Lens)]
+pub struct IntervalHistory {
+	/// Receive time and interval in milliseconds of each frame, oldest first.
+	intervals: Arc<VecDeque<(Instant, f32)>>,
+}
+
+impl IntervalHistory {
+	/// Intervals in milliseconds, oldest first.
+	fn values(&self) -> Vec<f32> {
+		self.intervals.iter().map(|(_, interval)| *interval).collect()
+	}
+
+	/// Largest interval shown, at least the reference intervals.
+	fn range(&self) -> f32 {
+		self.values().iter().chain(&REFERENCE_INTERVALS).copied().fold(0.0, f32::max)
+	}
+}
+
+/// Create the widget for the interval chart and histogram with a summary of
+/// the intervals.
+pub fn widget() -> impl Widget<IntervalHistory> {
+	let summary = Label::new(|data: &IntervalHistory, _env: &Env| {
+		match summarize(Metric::Interval, data.values()) {
+			Some(MetricSummary { average, median, p95, max, .. }) => {
+				let fps = 1000.0 / average;
+				let spread = format!("median {median:.1}, p95 {p95:.1}, max {max:.1} ms");
+				format!("Interval: avg {average:.1} ms ({fps:.1} FPS)\n{spread}")
+			}
+			None => "Interval: -".to_owned(),
+		}
+	});
+
+	Flex::column()
+		.cross_axis_alignment(CrossAxisAlignment::Start)
+		.with_child(IntervalChart)
+		.with_spacer(2.0)
+		.with_child(IntervalHistogram)
+		.with_child(summary)
+		.controller(IntervalController)
+}
+
+/// Chart of the intervals over time, with their moving average.
+struct IntervalChart;
+
+impl Widget<IntervalHistory> for IntervalChart {
+	fn event(
+		&mut self,
+		_ctx: &mut EventCtx,
+		_event: &Event,
+		_data: &mut IntervalHistory,
+		_env: &Env,
+	) {
+	}
+
+	fn lifecycle(
+		&mut self,
+		_ctx: &mut LifeCycleCtx,
+		_event: &LifeCycle,
+		_data: &IntervalHistory,
+		_env: &Env,
+	) {
+	}
+
+	fn update(
+		&mut self,
+		ctx: &mut UpdateCtx,
+		old_data: &IntervalHistory,
+		data: &IntervalHistory,
+		_env: &Env,
+	) {
+		if !old_data.same(data) {
+			ctx.request_paint();
+		}
+	}
+
+	fn layout(
+		&mut self,
+		_ctx: &mut LayoutCtx,
+		bc: &BoxConstraints,
+		_data: &IntervalHistory,
+		_env: &Env,
+	) -> Size {
+		bc.constrain(CHART_SIZE)
+	}
+
+	fn paint(&mut self, ctx: &mut PaintCtx, data: &IntervalHistory, _env: &Env) {
+		let size = ctx.size();
+		let background = ctx.solid_brush(Color::rgba8(0x20, 0x20, 0x20, 0xFF));
+		ctx.fill(size.to_rect(), &background);
+
+		let values = data.values();
+		let max = data.range();
+		let now = Instant::now();
+		// Map time and interval to the chart, newest frames on the right.
+		let to_point = |time: Instant, interval: f32| {
+			let age = now.saturating_duration_since(time).as_secs_f64() / WINDOW.as_secs_f64();
+			let x = size.width * (1.0 - age);
+			let y = size.height * (1.0 - f64::from(interval / max) * 0.9);
+			(x, y)
+		};
+
+		let reference = ctx.solid_brush(Color::rgba8(0x80, 0x80, 0x80, 0xFF));
+		for interval in REFERENCE_INTERVALS {
+			let (_, y) = to_point(now, interval);
+			ctx.stroke(Line::new((0.0, y), (size.width, y)), &reference, 1.0);
+		}
+
+		let intervals_brush = ctx.solid_brush(Color::rgba8(0x40, 0xA0, 0xFF, 0xFF));
+		ctx.stroke(polyline(data.intervals.iter().copied(), to_point), &intervals_brush, 1.0);
+		let average_brush = ctx.solid_brush(Color::rgba8(0xFF, 0xA0, 0x00, 0xFF));
+		let times = data.intervals.iter().map(|(time, _)| *time);
+		let averages = times.zip(moving_average(&values, AVERAGE_FRAMES));
+		ctx.stroke(polyline(averages, to_point), &average_brush, 2.0);
+
+		ctx.stroke(size.to_rect(), &reference, 1.0);
+	}
+}
+
+/// Histogram of the intervals over the same range as the chart, to see how
+/// often frames are late.
+struct IntervalHistogram;
+
+impl Widget<IntervalHistory> for IntervalHistogram {
+	fn event(
+		&mut self,
+		_ctx: &mut EventCtx,
+		_event: &Event,
+		_data: &mut IntervalHistory,
+		_env: &Env,
+	) {
+	}
+
+	fn lifecycle(
+		&mut self,
+		_ctx: &mut LifeCycleCtx,
+		_event: &LifeCycle,
+		_data: &IntervalHistory,
+		_env: &Env,
+	) {
+	}
+
+	fn update(
+		&mut self,
+		ctx: &mut UpdateCtx,
+		old_data: &IntervalHistory,
+		data: &IntervalHistory,
+		_env: &Env,
+	) {
+		if !old_data.same(data) {
+			ctx.request_paint();
+		}
+	}
+
+	fn layout(
+		&mut self,
+		_ctx: &mut LayoutCtx,
+		bc: &BoxConstraints,
+		_data: &IntervalHistory,
+		_env: &Env,
+	) -> Size {
+		bc.constrain(HISTOGRAM_SIZE)
+	}
+
+	fn paint(&mut self, ctx: &mut PaintCtx, data: &IntervalHistory, _env: &Env) {
+		let size = ctx.size();
+		let background = ctx.solid_brush(Color::rgba8(0x20, 0x20, 0x20, 0xFF));
+		ctx.fill(size.to_rect(), &background);
+
+		// Intervals from zero on the left to the chart's maximum on the right.
+		let max = data.range();
+		let counts = histogram(&data.values(), HISTOGRAM_BINS, max);
+		let highest = counts.iter().copied().max().unwrap_or(0).max(1);
+		let bar_width = size.width / HISTOGRAM_BINS as f64;
+		let bar_brush = ctx.solid_brush(Color::rgba8(0x40, 0xA0, 0xFF, 0xFF));
+		for (index, count) in counts.into_iter().enumerate() {
+			let height = size.height * 0.9 * count as f64 / highest as f64;
+			let x = index as f64 * bar_width;
+			let bar = Rect::new(x + 1.0, size.height - height, x + bar_width - 1.0, size.height);
+			ctx.fill(bar, &bar_brush);
+		}
+
+		let reference = ctx.solid_brush(Color::rgba8(0x80, 0x80, 0x80, 0xFF));
+		for interval in REFERENCE_INTERVALS {
+			let x = size.width * f64::from(interval / max);
+			ctx.stroke(Line::new((x, 0.0), (x, size.height)), &reference, 1.0);
+		}
+		ctx.stroke(size.to_rect(), &reference, 1.0);
+	}
+}
+
+/// Path through the points of the chart.
+fn polyline(
+	points: impl Iterator<Item = (Instant, f32)>,
+	to_point: impl Fn(Instant, f32) -> (f64, f64),
+) -> BezPath {
+	let mut path = BezPath::new();
+	for (index, (time, interval)) in points.enumerate() {
+		if index == 0 {
+			path.move_to(to_point(time, interval));
+		} else {
+			path.line_to(to_point(time, interval));
+		}
+	}
+	path
+}
+
+/// Controller for receiving frame intervals and forgetting old ones.
+struct IntervalController;
+
+impl<W: Widget<IntervalHistory>> Controller<IntervalHistory, W> for IntervalController {
+	fn event(
+		&mut self,
+		child: &mut W,
+		ctx: &mut druid::EventCtx,
+		event: &Event,
+		data: &mut IntervalHistory,
+		env: &Env,
+	) {
+		if let Event::Command(command) = event {
+			if let Some(interval) = command.get(Selector::<f32>::new(S_FRAME_INTERVAL)) {
+				// A new history, as the chart only repaints for a changed pointer.
+				let now = Instant::now();
+				let mut intervals = (*data.intervals).clone();
+				intervals.push_back((now, *interval));
+				while let Some((time, _)) = intervals.front() {
+					if now.duration_since(*time) <= WINDOW {
+						break;
+					}
+					intervals.pop_front();
+				}
+				data.intervals = Arc::new(intervals);
+			}
+		}
+
+		child.event(ctx, event, data, env)
+	}
+
+	fn lifecycle(
+		&mut self,
+		child: &mut W,
+		ctx: &mut druid::LifeCycleCtx,
+		event: &druid::LifeCycle,
+		data: &IntervalHistory,
+		env: &Env,
+	) {
+		child.lifecycle(ctx, event, data, env)
+	}
+
+	fn update(
+		&mut self,
+		child: &mut W,
+		ctx: &mut druid::UpdateCtx,
+		old_data: &IntervalHistory,
+		data: &IntervalHistory,
+		env: &Env,
+	) {
+		child.update(ctx, old_data, data, env)
+	}
+}
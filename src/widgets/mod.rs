@@ -1,31 +1,59 @@
 //! Widgets for the UI.
 
 mod cam_picker;
-mod fps;
+mod camera_controls;
+mod format_picker;
+mod interval_chart;
+mod no_camera;
+mod stats;
+mod status;
+mod threshold;
 mod webcam;
+mod zones;
 
 use druid::{
-	widget::{Align, Axis, Flex, Tabs, TabsEdge},
+	widget::{Align, Axis, Either, Flex, Tabs, TabsEdge},
 	Data, Lens, UnitPoint, Widget, WidgetExt,
 };
 
-use self::{cam_picker::PickerSender, fps::Fps};
-use crate::camera::{S_CAMERA_FRAME, S_CAMERA_POINT, S_DIFFERENCE_FRAME, S_PROCESSED_FRAME};
+use crate::{
+	camera::{
+		PipelineControl, RequestSender, S_CAMERA_FRAME, S_CAMERA_POINT, S_DIFFERENCE_FRAME,
+		S_PROCESSED_FRAME, S_STABILISED_FRAME,
+	},
+	output::{zones::ZoneSender, CameraStatus},
+};
 
 /// Root UI widget state.
 #[derive(Debug, Clone, Default, Data, Lens)]
 pub struct RootUIState {
-	/// Index of system camera
-	cam_index: usize,
+	/// System cameras and the picked one
+	cam_picker: cam_picker::CameraPickerState,
+	/// Format of the camera
+	cam_format: format_picker::FormatState,
+	/// Controls of the camera
+	cam_controls: camera_controls::ControlsState,
+	/// Threshold of differences between frames
+	threshold: threshold::ThresholdState,
 	/// State of camera view
 	cam_view: webcam::CameraViewState,
-	/// Camera FPS
-	cam_fps: Fps,
+	/// Pipeline statistics
+	stats: stats::Stats,
+	/// Recent frame intervals
+	intervals: interval_chart::IntervalHistory,
+	/// Camera connection status
+	cam_status: CameraStatus,
 }
 
-/// Build the root UI widget.
-pub fn root_widget(cam_pick_sender: PickerSender) -> impl Widget<RootUIState> {
-	let cam_view = Tabs::new()
+/// Build the root UI widget. The stabilised frames are only shown, if the
+/// pipeline stabilises frames.
+pub fn root_widget(
+	cam_request_sender: RequestSender,
+	zone_sender: ZoneSender,
+	control: PipelineControl,
+	stabilise: bool,
+) -> impl Widget<RootUIState> {
+	let mut cam_view = Tabs::new()
 		.with_axis(Axis::Vertical)
 		.with_edge(TabsEdge::Trailing)
 		.with_tab(
@@ -35,19 +63,52 @@ pub fn root_widget(cam_pick_sender: PickerSender) -> impl Widget<RootUIState> {
 		.with_tab(
 			"Processed",
 			webcam::CameraView::new(S_PROCESSED_FRAME, S_CAMERA_POINT).lens(RootUIState::cam_view),
-		)
-		.with_tab(
-			"Difference",
-			webcam::CameraView::new(S_DIFFERENCE_FRAME, S_CAMERA_POINT).lens(RootUIState::cam_view),
 		);
+	if stabilise {
+		cam_view = cam_view.with_tab(
+			"Stabilised",
+			webcam::CameraView::new(S_STABILISED_FRAME, S_CAMERA_POINT).lens(RootUIState::cam_view),
+		);
+	}
+	let cam_view = cam_view.with_tab(
+		"Difference",
+		webcam::CameraView::new(S_DIFFERENCE_FRAME, S_CAMERA_POINT).lens(RootUIState::cam_view),
+	);
+
+	// Without any camera, offer to rescan or use another source instead.
+	let cam_view = Either::new(
+		|data: &RootUIState, _env| {
+			data.cam_status != CameraStatus::Streaming && !data.cam_picker.has_cameras()
+		},
+		no_camera::widget(cam_request_sender.clone()),
+		cam_view,
+	);
 
-	let cam_dropdown =
-		cam_picker::widget(cam_pick_sender).lens(RootUIState::cam_index).padding((10.0, 10.0));
-	let fps = fps::widget().lens(RootUIState::cam_fps).padding((10.0, 10.0));
+	let status = status::widget().lens(RootUIState::cam_status).padding((10.0, 10.0));
+	let cam_dropdown = cam_picker::widget(cam_request_sender.clone())
+		.lens(RootUIState::cam_picker)
+		.padding((10.0, 10.0));
+	let format_dropdowns = format_picker::widget(cam_request_sender.clone())
+		.lens(RootUIState::cam_format)
+		.padding((10.0, 10.0));
+	let cam_controls = camera_controls::widget(cam_request_sender)
+		.lens(RootUIState::cam_controls)
+		.padding((10.0, 10.0));
+	let threshold = threshold::widget(control).lens(RootUIState::threshold).padding((10.0, 10.0));
+	let stats = stats::widget().lens(RootUIState::stats).padding((10.0, 10.0));
+	let intervals = interval_chart::widget().lens(RootUIState::intervals).padding((10.0, 10.0));
+	let zones = zones::widget(zone_sender).lens(RootUIState::cam_view).padding((10.0, 10.0));
 	let controls = Flex::column()
+		.with_child(status)
 		.with_child(cam_dropdown)
+		.with_child(format_dropdowns)
+		.with_child(cam_controls)
+		.with_child(threshold)
+		.with_default_spacer()
+		.with_child(intervals)
+		.with_child(stats)
 		.with_default_spacer()
-		.with_child(fps)
+		.with_child(zones)
 		.align_vertical(UnitPoint::TOP);
 
 	let layout =
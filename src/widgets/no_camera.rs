@@ -0,0 +1,67 @@
+//! Placeholder shown instead of the camera view while there is no camera.
+
+use druid::{
+	commands,
+	widget::{Button, Controller, Flex, Label},
+	Env, Event, EventCtx, FileDialogOptions, Widget, WidgetExt,
+};
+
+use super::status;
+use crate::camera::{CameraRequest, RequestSender, Source};
+
+/// Create the placeholder widget, which allows scanning for cameras again or
+/// using a recording or synthetic frames instead.
+pub fn widget<T: druid::Data>(request_sender: RequestSender) -> impl Widget<T> {
+	let rescan_sender = request_sender.clone();
+	let rescan = Button::new("Rescan").on_click(move |ctx, _data: &mut T, _env| {
+		if let Err(err) = rescan_sender.send(CameraRequest::Rescan) {
+			ctx.submit_command(status::request_failed(err));
+		}
+	});
+	let replay = Button::new("Open recording").on_click(|ctx, _data: &mut T, _env| {
+		let options = FileDialogOptions::new().select_directories();
+		ctx.submit_command(commands::SHOW_OPEN_PANEL.with(options));
+	});
+	let synthetic_sender = request_sender.clone();
+	let synthetic = Button::new("Use test pattern").on_click(move |ctx, _data: &mut T, _env| {
+		if let Err(err) = synthetic_sender.send(CameraRequest::Open(Source::Synthetic)) {
+			ctx.submit_command(status::request_failed(err));
+		}
+	});
+
+	Flex::column()
+		.with_child(Label::new("No camera found"))
+		.with_default_spacer()
+		.with_child(
+			Flex::row()
+				.with_child(rescan)
+				.with_default_spacer()
+				.with_child(replay)
+				.with_default_spacer()
+				.with_child(synthetic),
+		)
+		.center()
+		.controller(ReplayController { sender: request_sender })
+}
+
+/// Controller opening the directory picked in the file dialog as replay
+/// source.
+struct ReplayController {
+	sender: RequestSender,
+}
+
+impl<T, W: Widget<T>> Controller<T, W> for ReplayController {
+	fn event(&mut self, child: &mut W, ctx: &mut EventCtx, event: &Event, data: &mut T, env: &Env) {
+		if let Event::Command(command) = event {
+			if let Some(file) = command.get(commands::OPEN_FILE) {
+				let source = Source::Replay(file.path().to_owned());
+				if let Err(err) = self.sender.send(CameraRequest::Open(source)) {
+					ctx.submit_command(status::request_failed(err));
+				}
+				ctx.set_handled();
+				return;
+			}
+		}
+		child.event(ctx, event, data, env)
+	}
+}
@@ -0,0 +1,108 @@
+//! Statistics view of the pipeline timings.
+
+use std::sync::Arc;
+
+use druid::{
+	widget::{Controller, CrossAxisAlignment, Flex, Label},
+	Env, Event, Selector, Widget, WidgetExt,
+};
+
+use crate::camera::{
+	stats::{Metric, MetricSummary, PipelineStats},
+	S_PIPELINE_STATS,
+};
+
+/// Data type of statistics messages and therefore also the widget
+pub type Stats = Arc<PipelineStats>;
+
+/// Width of the metric name column.
+const NAME_WIDTH: f64 = 80.0;
+/// Width of the value columns.
+const VALUE_WIDTH: f64 = 50.0;
+
+/// Create the widget for the statistics view, a table of the timings of
+/// each stage in milliseconds.
+pub fn widget() -> impl Widget<Stats> {
+	let summary = Label::new(|stats: &Stats, _env: &Env| {
+		let fps = match stats.get(Metric::Interval) {
+			Some(interval) if interval.average > 0.0 => 1000.0 / interval.average,
+			_ => 0.0,
+		};
+		format!("FPS: {fps:.1}, dropped frames: {}", stats.dropped_frames)
+	});
+
+	let mut header = Flex::row().with_child(Label::new("ms").fix_width(NAME_WIDTH));
+	for title in ["avg", "median", "p95", "max"] {
+		header.add_child(Label::new(title).fix_width(VALUE_WIDTH));
+	}
+
+	let mut table = Flex::column()
+		.cross_axis_alignment(CrossAxisAlignment::Start)
+		.with_child(summary)
+		.with_child(header);
+	for metric in Metric::ALL {
+		table.add_child(
+			Flex::row()
+				.with_child(Label::new(metric.to_string()).fix_width(NAME_WIDTH))
+				.with_child(value_label(metric, |summary| summary.average))
+				.with_child(value_label(metric, |summary| summary.median))
+				.with_child(value_label(metric, |summary| summary.p95))
+				.with_child(value_label(metric, |summary| summary.max)),
+		);
+	}
+
+	table.controller(StatsController)
+}
+
+/// Label for one value of the metric's summary.
+fn value_label(metric: Metric, value: fn(&MetricSummary) -> f32) -> impl Widget<Stats> {
+	Label::new(move |stats: &Stats, _env: &Env| match stats.get(metric) {
+		Some(summary) => format!("{:.1}", value(summary)),
+		None => "-".to_owned(),
+	})
+	.fix_width(VALUE_WIDTH)
+}
+
+/// Controller for receiving pipeline statistics.
+struct StatsController;
+
+impl<W: Widget<Stats>> Controller<Stats, W> for StatsController {
+	fn event(
+		&mut self,
+		child: &mut W,
+		ctx: &mut druid::EventCtx,
+		event: &Event,
+		data: &mut Stats,
+		env: &Env,
+	) {
+		if let Event::Command(command) = event {
+			if let Some(stats) = command.get(Selector::<Stats>::new(S_PIPELINE_STATS)) {
+				*data = stats.clone();
+			}
+		}
+
+		child.event(ctx, event, data, env)
+	}
+
+	fn lifecycle(
+		&mut self,
+		child: &mut W,
+		ctx: &mut druid::LifeCycleCtx,
+		event: &druid::LifeCycle,
+		data: &Stats,
+		env: &Env,
+	) {
+		child.lifecycle(ctx, event, data, env)
+	}
+
+	fn update(
+		&mut self,
+		child: &mut W,
+		ctx: &mut druid::UpdateCtx,
+		old_data: &Stats,
+		data: &Stats,
+		env: &Env,
+	) {
+		child.update(ctx, old_data, data, env)
+	}
+}
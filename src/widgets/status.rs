@@ -0,0 +1,73 @@
+//! Camera status view.
+
+use std::sync::mpsc::SendError;
+
+use druid::{
+	widget::{Controller, Label, LineBreaking},
+	Command, Env, Event, Selector, Widget, WidgetExt,
+};
+
+use crate::{
+	camera::{CameraRequest, S_CAMERA_STATUS},
+	output::CameraStatus,
+};
+
+/// Create the widget for the camera status view.
+pub fn widget() -> impl Widget<CameraStatus> {
+	Label::new(|status: &CameraStatus, _env: &Env| format!("Camera: {status}"))
+		.with_line_break_mode(LineBreaking::WordWrap)
+		.fix_width(250.0)
+		.controller(StatusController)
+}
+
+/// Command showing that a request could not be sent to the camera thread,
+/// because it is gone. The error is also printed.
+pub fn request_failed(err: SendError<CameraRequest>) -> Command {
+	eprintln!("Error sending camera request: {err}");
+	let status = CameraStatus::Error("Camera handler stopped".to_owned());
+	Selector::new(S_CAMERA_STATUS).with(status)
+}
+
+/// Controller for receiving the camera status.
+struct StatusController;
+
+impl<W: Widget<CameraStatus>> Controller<CameraStatus, W> for StatusController {
+	fn event(
+		&mut self,
+		child: &mut W,
+		ctx: &mut druid::EventCtx,
+		event: &Event,
+		data: &mut CameraStatus,
+		env: &Env,
+	) {
+		if let Event::Command(command) = event {
+			if let Some(status) = command.get(Selector::<CameraStatus>::new(S_CAMERA_STATUS)) {
+				*data = status.clone();
+			}
+		}
+
+		child.event(ctx, event, data, env)
+	}
+
+	fn lifecycle(
+		&mut self,
+		child: &mut W,
+		ctx: &mut druid::LifeCycleCtx,
+		event: &druid::LifeCycle,
+		data: &CameraStatus,
+		env: &Env,
+	) {
+		child.lifecycle(ctx, event, data, env)
+	}
+
+	fn update(
+		&mut self,
+		child: &mut W,
+		ctx: &mut druid::UpdateCtx,
+		old_data: &CameraStatus,
+		data: &CameraStatus,
+		env: &Env,
+	) {
+		child.update(ctx, old_data, data, env)
+	}
+}
@@ -0,0 +1,158 @@
+//! Panel for adjusting the threshold of differences between frames and
+//! calibrating it to the camera's noise.
+
+use druid::{
+	lens,
+	widget::{Button, Checkbox, Controller, CrossAxisAlignment, Flex, Label, Slider},
+	Data, Env, Event, Lens, Selector, Widget, WidgetExt,
+};
+
+use crate::camera::{
+	analysis::Threshold,
+	calibration::{CalibrationStatus, CALIBRATION_TIME},
+	PipelineControl, S_CALIBRATION,
+};
+
+/// State of the threshold panel.
+#[derive(Debug, Clone, Default, Data, Lens)]
+pub struct ThresholdState {
+	/// Threshold the pipeline compares differences to.
+	#[data(same_fn = "PartialEq::eq")]
+	threshold: Threshold,
+	/// Progress or result of the last calibration.
+	calibration: String,
+}
+
+/// Value shown by the slider, which is the minimum of adaptive thresholds and
+/// the largest one of per-channel thresholds.
+fn slider_value(threshold: &Threshold) -> u8 {
+	match *threshold {
+		Threshold::Global(value) | Threshold::Otsu(value) => value,
+		Threshold::PerChannel(values) => values.into_iter().max().unwrap_or(0),
+	}
+}
+
+/// Lens to the slider position. Moving the slider replaces per-channel
+/// thresholds by a global one.
+fn slider_position() -> impl Lens<Threshold, f64> {
+	lens::Map::new(
+		|threshold: &Threshold| f64::from(slider_value(threshold)),
+		|threshold: &mut Threshold, position: f64| {
+			let value = position.round() as u8;
+			*threshold = match threshold {
+				Threshold::Otsu(_) => Threshold::Otsu(value),
+				_ => Threshold::Global(value),
+			};
+		},
+	)
+}
+
+/// Lens to whether the threshold adapts to the noise of each frame, keeping
+/// the slider value as minimum.
+fn adaptive() -> impl Lens<Threshold, bool> {
+	lens::Map::new(
+		|threshold: &Threshold| matches!(threshold, Threshold::Otsu(_)),
+		|threshold: &mut Threshold, adaptive: bool| {
+			let value = slider_value(threshold);
+			*threshold = if adaptive { Threshold::Otsu(value) } else { Threshold::Global(value) };
+		},
+	)
+}
+
+/// Create the widget for the threshold panel.
+pub fn widget(control: PipelineControl) -> impl Widget<ThresholdState> {
+	let label = Label::dynamic(|data: &ThresholdState, _env| match data.threshold {
+		Threshold::Global(value) => format!("Threshold: {value}"),
+		Threshold::PerChannel([red, green, blue]) => {
+			format!("Threshold: {red} / {green} / {blue}")
+		}
+		Threshold::Otsu(minimum) => format!("Threshold: adaptive, at least {minimum}"),
+	});
+	let slider = Slider::new().with_range(0.0, 255.0).lens(slider_position());
+	let adaptive_checkbox = Checkbox::new("Adaptive").lens(adaptive());
+	let calibration_control = control.clone();
+	let calibrate = Button::new("Calibrate noise")
+		.on_click(move |_ctx, _data: &mut ThresholdState, _env| calibration_control.calibrate());
+
+	Flex::column()
+		.cross_axis_alignment(CrossAxisAlignment::Start)
+		.with_child(label)
+		.with_child(
+			Flex::row()
+				.with_child(slider)
+				.with_child(adaptive_checkbox)
+				.lens(ThresholdState::threshold),
+		)
+		.with_child(calibrate)
+		.with_child(Label::raw().lens(ThresholdState::calibration))
+		.controller(ThresholdController { control })
+}
+
+/// Controller for showing the pipeline's threshold and calibrations, and
+/// changing the threshold.
+struct ThresholdController {
+	control: PipelineControl,
+}
+
+impl<W: Widget<ThresholdState>> Controller<ThresholdState, W> for ThresholdController {
+	fn event(
+		&mut self,
+		child: &mut W,
+		ctx: &mut druid::EventCtx,
+		event: &Event,
+		data: &mut ThresholdState,
+		env: &Env,
+	) {
+		// Show the threshold given on the command line.
+		if let Event::WindowConnected = event {
+			data.threshold = self.control.threshold();
+		}
+		if let Event::Command(command) = event {
+			if let Some(status) = command.get(Selector::<CalibrationStatus>::new(S_CALIBRATION)) {
+				data.calibration = match status {
+					CalibrationStatus::Measuring => {
+						format!("Hold still for {} seconds...", CALIBRATION_TIME.as_secs())
+					}
+					CalibrationStatus::Done(result) => {
+						let adaptive = matches!(data.threshold, Threshold::Otsu(_));
+						data.threshold = result.threshold;
+						if adaptive {
+							format!("Calibrated: {result}, replacing the adaptive threshold")
+						} else {
+							format!("Calibrated: {result}")
+						}
+					}
+					CalibrationStatus::Failed => "Calibration failed, try again".to_owned(),
+				};
+			}
+		}
+
+		child.event(ctx, event, data, env)
+	}
+
+	fn lifecycle(
+		&mut self,
+		child: &mut W,
+		ctx: &mut druid::LifeCycleCtx,
+		event: &druid::LifeCycle,
+		data: &ThresholdState,
+		env: &Env,
+	) {
+		child.lifecycle(ctx, event, data, env)
+	}
+
+	fn update(
+		&mut self,
+		child: &mut W,
+		ctx: &mut druid::UpdateCtx,
+		old_data: &ThresholdState,
+		data: &ThresholdState,
+		env: &Env,
+	) {
+		if old_data.threshold != data.threshold {
+			self.control.set_threshold(data.threshold);
+		}
+
+		child.update(ctx, old_data, data, env)
+	}
+}
@@ -1,28 +1,63 @@
 //! Webcam view widget.
 
+use std::sync::Arc;
+
+use color_eyre::{eyre::bail, Result};
 use druid::{
-	kurbo::Circle,
+	kurbo::{Affine, Circle},
 	piet::{ImageFormat, InterpolationMode},
 	widget::{FillStrat, Image},
-	BoxConstraints, Color, Data, Env, Event, EventCtx, ImageBuf, LayoutCtx, LifeCycle,
-	LifeCycleCtx, PaintCtx, RenderContext, Selector, SingleUse, Size, UpdateCtx, Widget,
+	BoxConstraints, Color, Data, Env, Event, EventCtx, ImageBuf, LayoutCtx, Lens, LifeCycle,
+	LifeCycleCtx, PaintCtx, Point, Rect, RenderContext, Selector, SingleUse, Size, UpdateCtx,
+	Widget,
+};
+
+use crate::{
+	camera::{analysis::Frame, pool::PooledFrame, S_CAMERA_STATUS, S_FRAME_SIZE},
+	output::{zones::Zone, CameraStatus},
 };
-use image::{ColorType, ImageBuffer, Rgb};
 
 /// `CameraView` state
-#[derive(Debug, Clone, Default, Data)]
+#[derive(Debug, Clone, Data, Lens)]
 pub struct CameraViewState {
-	/// Width and height of image.
+	/// Width and height of the unprocessed frames, which the detected point and
+	/// zones refer to. Processed frames may be smaller, but are shown at the
+	/// same size.
 	image_dimensions: (u32, u32),
 	/// Detected point position on the image
 	detected_point: (u32, u32),
+	/// Whether zone trigger mode is active, which allows drawing zones.
+	pub zone_mode: bool,
+	/// Key chord for newly drawn zones.
+	pub zone_chord: String,
+	/// Motion zones in image coordinates.
+	pub zones: Arc<Vec<Zone>>,
+}
+
+impl Default for CameraViewState {
+	fn default() -> Self {
+		Self {
+			image_dimensions: (0, 0),
+			detected_point: (0, 0),
+			zone_mode: false,
+			zone_chord: "space".to_owned(),
+			zones: Arc::new(Vec::new()),
+		}
+	}
 }
 
 /// `CameraView` widget
 pub struct CameraView {
 	image: Image,
-	frame_selector: Selector<SingleUse<Frame>>,
+	frame_selector: Selector<SingleUse<PooledFrame>>,
 	point_selector: Selector<(u32, u32)>,
+	/// Pixels of the shown image.
+	shown_pixels: Option<Arc<[u8]>>,
+	/// Pixels of the previously shown image, reused for the next one.
+	spare_pixels: Option<Arc<[u8]>>,
+	/// Start and current corner of the zone currently being drawn, in image
+	/// coordinates.
+	zone_drag: Option<(Point, Point)>,
 }
 
 impl CameraView {
@@ -37,6 +72,61 @@ impl CameraView {
 			image,
 			frame_selector: Selector::new(frame_selector),
 			point_selector: Selector::new(point_selector),
+			shown_pixels: None,
+			spare_pixels: None,
+			zone_drag: None,
+		}
+	}
+
+	/// Transformation from image coordinates to widget coordinates.
+	fn image_transform(widget_size: Size, data: &CameraViewState) -> Affine {
+		let (width, height) = data.image_dimensions;
+		let image_size = Size::new(width.into(), height.into());
+		FillStrat::Contain.affine_to_fill(widget_size, image_size)
+	}
+
+	/// Convert a widget position to image coordinates, clamped to the image.
+	fn to_image_point(widget_size: Size, data: &CameraViewState, pos: Point) -> Point {
+		let (width, height) = data.image_dimensions;
+		let point = Self::image_transform(widget_size, data).inverse() * pos;
+		Point::new(point.x.clamp(0.0, width.into()), point.y.clamp(0.0, height.into()))
+	}
+
+	/// Handle mouse events for drawing zones.
+	fn zone_event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut CameraViewState) {
+		let (width, height) = data.image_dimensions;
+		if !data.zone_mode || width == 0 || height == 0 {
+			self.zone_drag = None;
+			return;
+		}
+
+		match event {
+			Event::MouseDown(mouse) => {
+				let start = Self::to_image_point(ctx.size(), data, mouse.pos);
+				self.zone_drag = Some((start, start));
+				ctx.set_active(true);
+			}
+			Event::MouseMove(mouse) if ctx.is_active() => {
+				if let Some((_, current)) = &mut self.zone_drag {
+					*current = Self::to_image_point(ctx.size(), data, mouse.pos);
+					ctx.request_paint();
+				}
+			}
+			Event::MouseUp(_) if ctx.is_active() => {
+				ctx.set_active(false);
+				if let Some((start, end)) = self.zone_drag.take() {
+					let zone = Zone::from_corners(
+						(start.x as u32, start.y as u32),
+						(end.x as u32, end.y as u32),
+						data.zone_chord.clone(),
+					);
+					if zone.width > 0 && zone.height > 0 {
+						Arc::make_mut(&mut data.zones).push(zone);
+					}
+				}
+				ctx.request_paint();
+			}
+			_ => {}
 		}
 	}
 }
@@ -44,15 +134,27 @@ impl CameraView {
 impl Widget<CameraViewState> for CameraView {
 	fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut CameraViewState, env: &Env) {
 		if let Event::Command(command) = event {
-			if let Some(frame) = command.get(self.frame_selector).and_then(SingleUse::take) {
-				data.image_dimensions = frame.dimensions();
-				self.image.set_image_data(frame_to_image(frame));
+			if let Some(dimensions) = command.get(Selector::new(S_FRAME_SIZE)) {
+				data.image_dimensions = *dimensions;
+			} else if let Some(frame) = command.get(self.frame_selector).and_then(SingleUse::take) {
+				// The frame's buffer returns to the pool once it is copied.
+				match frame_to_image(frame.frame(), self.spare_pixels.take()) {
+					Ok((image, pixels)) => {
+						self.image.set_image_data(image);
+						self.spare_pixels = self.shown_pixels.replace(pixels);
+					}
+					Err(err) => {
+						let status = CameraStatus::Error(err.to_string());
+						ctx.submit_command(Selector::new(S_CAMERA_STATUS).with(status));
+					}
+				}
 				ctx.request_paint();
 			} else if let Some(point) = command.get(self.point_selector) {
 				data.detected_point = *point;
 				ctx.request_paint();
 			}
 		}
+		self.zone_event(ctx, event, data);
 
 		self.image.event(ctx, event, data, env)
 	}
@@ -77,6 +179,9 @@ impl Widget<CameraViewState> for CameraView {
 		if old_data.image_dimensions != data.image_dimensions {
 			ctx.request_layout();
 		}
+		if !old_data.zones.same(&data.zones) || old_data.zone_mode != data.zone_mode {
+			ctx.request_paint();
+		}
 
 		self.image.update(ctx, old_data, data, env)
 	}
@@ -101,8 +206,7 @@ impl Widget<CameraViewState> for CameraView {
 		}
 
 		// Draw point
-		let image_size = Size::new(width.into(), height.into());
-		let tansform_matrix = FillStrat::Contain.affine_to_fill(ctx.size(), image_size);
+		let tansform_matrix = Self::image_transform(ctx.size(), data);
 		let x = data.detected_point.0 as f64;
 		let y = data.detected_point.1 as f64;
 
@@ -114,22 +218,50 @@ impl Widget<CameraViewState> for CameraView {
 			let brush = ctx.solid_brush(Color::rgba8(0x00, 0xFF, 0x00, 0xFF));
 			let shape = Circle::new((x, y), 2.5);
 			ctx.fill(shape, &brush);
+
+			// Draw zones, including the one currently drawn.
+			if data.zone_mode {
+				let brush = ctx.solid_brush(Color::rgba8(0xFF, 0xA0, 0x00, 0xFF));
+				for zone in data.zones.iter() {
+					let rect = Rect::new(
+						zone.x.into(),
+						zone.y.into(),
+						(zone.x + zone.width).into(),
+						(zone.y + zone.height).into(),
+					);
+					ctx.stroke(rect, &brush, 2.0);
+				}
+				if let Some((start, end)) = self.zone_drag {
+					ctx.stroke(Rect::from_points(start, end), &brush, 1.0);
+				}
+			}
 		});
 	}
 }
 
-/// Type of camera frame image.
-type Frame = ImageBuffer<Rgb<u8>, Vec<u8>>;
-
-/// Convert a frame to a [`Image`] widget compatible type.
-fn frame_to_image(frame: Frame) -> ImageBuf {
-	let raw = frame.into_flat_samples();
-	if raw.color_hint != Some(ColorType::Rgb8) || raw.layout.channels != 3 {
-		panic!("Color format did not fit!");
+/// Convert a frame to a [`Image`] widget compatible type. The pixels are
+/// copied into the given spare buffer if possible, which is the case when the
+/// image using it was dropped. Returns the image and its pixels.
+fn frame_to_image(frame: &Frame, spare: Option<Arc<[u8]>>) -> Result<(ImageBuf, Arc<[u8]>)> {
+	let (raw, format, channels) = match frame {
+		Frame::Rgb(image) => (image.as_flat_samples(), ImageFormat::Rgb, 3),
+		Frame::Luma(image) => (image.as_flat_samples(), ImageFormat::Grayscale, 1),
+	};
+	if raw.layout.channels != channels {
+		bail!("Color format {:?} of frame does not fit", raw.color_hint);
 	}
 
 	let width = raw.layout.width as usize;
 	let height = raw.layout.height as usize;
 
-	ImageBuf::from_raw(raw.samples, ImageFormat::Rgb, width, height)
+	let reused = spare.and_then(|mut pixels| match Arc::get_mut(&mut pixels) {
+		Some(buffer) if buffer.len() == raw.samples.len() => {
+			buffer.copy_from_slice(raw.samples);
+			Some(pixels)
+		}
+		_ => None,
+	});
+	let pixels = reused.unwrap_or_else(|| Arc::from(raw.samples));
+
+	Ok((ImageBuf::from_raw(pixels.clone(), format, width, height), pixels))
 }
@@ -0,0 +1,90 @@
+//! Controls for the zone trigger mode.
+
+use std::sync::Arc;
+
+use druid::{
+	widget::{Button, Checkbox, Controller, CrossAxisAlignment, Flex, Label, TextBox},
+	Data, Env, Widget, WidgetExt,
+};
+
+use super::webcam::CameraViewState;
+use crate::output::zones::ZoneSender;
+
+/// Create the widget for configuring zone trigger mode. Zones are drawn on the
+/// camera view while the mode is active.
+pub fn widget(zone_sender: ZoneSender) -> impl Widget<CameraViewState> {
+	let mode = Checkbox::new("Zone trigger mode").lens(CameraViewState::zone_mode);
+	let chord = Flex::row()
+		.with_child(Label::new("Chord for new zones:"))
+		.with_default_spacer()
+		.with_child(
+			TextBox::new().with_placeholder("ctrl+space").lens(CameraViewState::zone_chord),
+		);
+	let count =
+		Label::new(|data: &CameraViewState, _env: &Env| format!("Zones: {}", data.zones.len()));
+	let clear = Button::new("Clear zones")
+		.on_click(|_ctx, data: &mut CameraViewState, _env| data.zones = Arc::new(Vec::new()));
+
+	Flex::column()
+		.cross_axis_alignment(CrossAxisAlignment::Start)
+		.with_child(mode)
+		.with_default_spacer()
+		.with_child(chord)
+		.with_default_spacer()
+		.with_child(Flex::row().with_child(count).with_default_spacer().with_child(clear))
+		.controller(ZoneController::new(zone_sender))
+}
+
+/// Controller for sending the zones to the zone trigger when they change.
+struct ZoneController {
+	sender: ZoneSender,
+}
+
+impl ZoneController {
+	/// Create new ZoneController
+	pub fn new(sender: ZoneSender) -> Self {
+		Self { sender }
+	}
+}
+
+impl<W: Widget<CameraViewState>> Controller<CameraViewState, W> for ZoneController {
+	fn event(
+		&mut self,
+		child: &mut W,
+		ctx: &mut druid::EventCtx,
+		event: &druid::Event,
+		data: &mut CameraViewState,
+		env: &Env,
+	) {
+		child.event(ctx, event, data, env)
+	}
+
+	fn lifecycle(
+		&mut self,
+		child: &mut W,
+		ctx: &mut druid::LifeCycleCtx,
+		event: &druid::LifeCycle,
+		data: &CameraViewState,
+		env: &Env,
+	) {
+		child.lifecycle(ctx, event, data, env)
+	}
+
+	fn update(
+		&mut self,
+		child: &mut W,
+		ctx: &mut druid::UpdateCtx,
+		old_data: &CameraViewState,
+		data: &CameraViewState,
+		env: &Env,
+	) {
+		if !old_data.zones.same(&data.zones) || old_data.zone_mode != data.zone_mode {
+			let zones = if data.zone_mode { data.zones.to_vec() } else { Vec::new() };
+			// The zone trigger is not available on all platforms, so the receiver
+			// might be gone.
+			self.sender.send(zones).ok();
+		}
+
+		child.update(ctx, old_data, data, env)
+	}
+}
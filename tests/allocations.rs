@@ -0,0 +1,152 @@
+//! Allocations of the frame pipeline. This is a test binary of its own, as it
+//! replaces the global allocator to count them.
+
+use std::{
+	alloc::{GlobalAlloc, Layout, System},
+	cell::Cell,
+	sync::atomic::{AtomicUsize, Ordering},
+	time::Instant,
+};
+
+use image::{Rgb, RgbImage};
+use webcam_mouse::{
+	analysis::{ColorMode, Frame, Threshold},
+	testing::{mailbox, Droppable},
+	FramePolicy, FramePool, Preprocessor,
+};
+
+/// Number of allocations by threads that enabled counting.
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+/// Number of bytes allocated by threads that enabled counting.
+static ALLOCATED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+	/// Whether allocations of this thread are counted.
+	static COUNTING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Allocator counting allocations, so tests can check them.
+struct CountingAllocator;
+
+impl CountingAllocator {
+	fn count(size: usize) {
+		if COUNTING.try_with(Cell::get).unwrap_or(false) {
+			ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+			ALLOCATED_BYTES.fetch_add(size, Ordering::Relaxed);
+		}
+	}
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+	unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+		Self::count(layout.size());
+		System.alloc(layout)
+	}
+
+	unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+		System.dealloc(ptr, layout);
+	}
+
+	unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+		Self::count(new_size);
+		System.realloc(ptr, layout, new_size)
+	}
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Frame captured by the camera, which the mailbox may drop.
+struct Captured(RgbImage);
+
+impl Droppable for Captured {
+	fn droppable(&self) -> bool {
+		true
+	}
+}
+
+/// Draw a bright square moving to the right on a dark background into a frame
+/// of the pool, like the synthetic source.
+fn captured_frame(pool: &FramePool, index: u32) -> RgbImage {
+	let mut frame: RgbImage = pool.image(640, 480);
+	let left = 100 + index * 20;
+	for (x, y, pixel) in frame.enumerate_pixels_mut() {
+		*pixel = if (left..left + 40).contains(&x) && (200..240).contains(&y) {
+			Rgb([0xFF, 0xFF, 0xFF])
+		} else {
+			Rgb([0x20, 0x20, 0x20])
+		};
+	}
+	frame
+}
+
+/// Send moving frames through the mailbox, then run the preprocessor and the
+/// difference of the analysis on them. Returns the number of allocations and
+/// allocated bytes per analysed frame. With more than one capture per frame,
+/// the mailbox drops all but the latest.
+fn count_pipeline_allocations(
+	color_mode: ColorMode,
+	processing_width: u32,
+	captures_per_frame: u32,
+) -> Vec<(usize, usize)> {
+	let pool = FramePool::new();
+	let (sender, receiver) = mailbox(FramePolicy::Latest);
+	let preprocessor = Preprocessor::new(Some(processing_width), color_mode, pool.clone());
+	let mut previous: Option<Frame> = None;
+	let mut counts = Vec::new();
+	for index in 0..6 {
+		let start = (ALLOCATIONS.load(Ordering::Relaxed), ALLOCATED_BYTES.load(Ordering::Relaxed));
+		COUNTING.with(|counting| counting.set(true));
+
+		for capture in 0..captures_per_frame {
+			let frame = captured_frame(&pool, index * captures_per_frame + capture);
+			sender
+				.send_recycling(Captured(frame), |Captured(dropped)| pool.recycle_image(dropped))
+				.unwrap();
+		}
+		let Captured(frame) = receiver.recv().unwrap();
+		assert_eq!(receiver.take_dropped(), u64::from(captures_per_frame - 1));
+		let prepared = preprocessor.prepare(frame, Instant::now(), 0);
+		let difference = previous.take().map(|previous| {
+			let mask =
+				previous.difference(&prepared.processed, Threshold::default(), &pool).unwrap();
+			assert!(mask.find_right_movement().is_some());
+			mask.into_frame()
+		});
+		// Frames sent to the UI, which returns them to the pool after displaying.
+		pool.recycle_frame(prepared.original);
+		pool.recycle_frame(pool.copy_frame(&prepared.processed));
+		if let Some(difference) = difference {
+			pool.recycle_frame(difference);
+		}
+		previous = Some(prepared.processed);
+
+		COUNTING.with(|counting| counting.set(false));
+		counts.push((
+			ALLOCATIONS.load(Ordering::Relaxed) - start.0,
+			ALLOCATED_BYTES.load(Ordering::Relaxed) - start.1,
+		));
+	}
+	counts
+}
+
+#[test]
+fn recycled_frames_need_no_allocations() {
+	// Run on a single thread, so that all allocations are counted and the
+	// thread's scratch memory is warmed up by the first frames.
+	let threads = rayon::ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+	// The last run captures frames faster than they are analysed.
+	let runs = [(ColorMode::Rgb, 640, 1), (ColorMode::Luma, 320, 1), (ColorMode::Luma, 320, 2)];
+	for (color_mode, processing_width, captures) in runs {
+		let counts =
+			threads.install(|| count_pipeline_allocations(color_mode, processing_width, captures));
+		// The first frames fill the pool.
+		assert!(counts[0].1 > 640 * 480, "{counts:?}");
+		// Afterwards, only a few small allocations remain, e.g. for the rows
+		// with motion.
+		for (allocations, bytes) in &counts[2..] {
+			assert!(*allocations <= 4, "{counts:?}");
+			assert!(*bytes < 4096, "{counts:?}");
+		}
+	}
+}